@@ -0,0 +1,123 @@
+// Lexer throughput over the .ema sources shipped with the framework.
+//
+// Run with `cargo bench --bench lexer`. The `scaling` group lexes the same
+// page repeated 1x..16x, and a page of `<script>` and `<style>` elements
+// whose raw text is read up to its closing tag; with a linear-time lexer
+// the reported throughput stays flat as the input grows.
+// `components/streaming` iterates tokens without collecting them or
+// keeping trivia.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+#[allow(dead_code)]
+#[path = "../emadocs-compiler.rs"]
+mod compiler;
+
+use compiler::Lexer;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn example_sources() -> Vec<(String, String)> {
+    let root = manifest_dir();
+    let files = [
+        root.join("examples/test.ema"),
+        root.join("../../examples/todo-app.ema"),
+    ];
+
+    files
+        .iter()
+        .filter_map(|path| {
+            let source = fs::read_to_string(path).ok()?;
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some((name, source))
+        })
+        .collect()
+}
+
+fn component_sources() -> Vec<String> {
+    let pattern = manifest_dir().join("../../components/*/*.ema");
+    glob::glob(&pattern.to_string_lossy())
+        .map(|paths| {
+            paths
+                .filter_map(Result::ok)
+                .filter_map(|path: PathBuf| fs::read_to_string(path).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn lex(source: &str) -> usize {
+    let mut lexer = Lexer::new(source, "bench.ema");
+    lexer.tokenize().map(|tokens| tokens.len()).unwrap_or(0)
+}
+
+fn stream(source: &str) -> usize {
+    let mut lexer = Lexer::new(source, "bench.ema");
+    lexer.set_skip_trivia(true);
+    lexer.count()
+}
+
+fn bench_examples(c: &mut Criterion) {
+    let mut group = c.benchmark_group("examples");
+    for (name, source) in example_sources() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(&name), &source, |b, source| {
+            b.iter(|| lex(black_box(source)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_components(c: &mut Criterion) {
+    let sources = component_sources();
+    let total: usize = sources.iter().map(String::len).sum();
+
+    let mut group = c.benchmark_group("components");
+    group.throughput(Throughput::Bytes(total as u64));
+    group.bench_function("all", |b| {
+        b.iter(|| sources.iter().map(|source| lex(black_box(source))).sum::<usize>())
+    });
+    group.bench_function("streaming", |b| {
+        b.iter(|| sources.iter().map(|source| stream(black_box(source))).sum::<usize>())
+    });
+    group.finish();
+}
+
+/// A page of `count` script and style elements, the raw text of each
+/// mentioning other tags before its own closing tag.
+fn raw_text_page(count: usize) -> String {
+    let element = concat!(
+        "  <script>if (a </b) { document.body.innerHTML = \"<p>\" + a + \"</p>\"; }</SCRIPT>\n",
+        "  <style>.card > p { content: \"</div>\"; }</style>\n",
+    );
+    format!("page Raw {{\n{}}}\n", element.repeat(count))
+}
+
+fn bench_scaling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scaling");
+    if let Some((_, page)) = example_sources().into_iter().next() {
+        for copies in [1, 4, 16] {
+            let source = page.repeat(copies);
+            group.throughput(Throughput::Bytes(source.len() as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(copies), &source, |b, source| {
+                b.iter(|| lex(black_box(source)))
+            });
+        }
+    }
+    for copies in [1, 4, 16] {
+        let source = raw_text_page(100 * copies);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new("raw-text", copies), &source, |b, source| {
+            b.iter(|| lex(black_box(source)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_examples, bench_components, bench_scaling);
+criterion_main!(benches);
@@ -8,10 +8,11 @@
  * @license MIT
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
 use serde::{Deserialize, Serialize};
 use regex::Regex;
@@ -32,1102 +33,6794 @@ pub enum TokenType {
     
     // Operators
     Assign, Equal, NotEqual, Less, Greater, LessEqual, GreaterEqual,
-    Plus, Minus, Multiply, Divide, Modulo, And, Or, Not, Arrow,
+    Plus, Minus, Multiply, Divide, Modulo, And, Or, Not, Arrow, Tilde, Caret,
+    StrictEqual, StrictNotEqual, Nullish, Power, Increment, Decrement,
+    PlusAssign, MinusAssign, MultiplyAssign, DivideAssign, ModuloAssign,
+    PowerAssign, AmpersandAssign, PipeAssign, CaretAssign, AndAssign, OrAssign,
+    NullishAssign,
+
+    // `<<` and `<<=`; a `>>` is two `>` tokens, so `Array<Array<T>>` closes
+    // both lists, which the expression parser joins back
+    ShiftLeft, ShiftLeftAssign,
     
     // Punctuation
     LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
-    Semicolon, Comma, Dot, Colon, Question, Pipe,
+    Semicolon, Comma, Dot, Colon, Question, Pipe, Ampersand, Hash, At, Dollar,
+    OptionalChain, Spread,
     
     // Tags
     OpeningTagStart, ClosingTagStart, TagEnd, SelfClosingTagEnd,
+
+    // `[[` and `]]` around an interpolation in markup
+    InterpolationStart, InterpolationEnd,
     
     // Literals
-    Identifier, String, Number, TemplateLiteral,
+    Identifier, String, Number, BigInt, Text,
+
+    // `/pattern/flags`
+    Regex,
+
+    // CSS numbers with a unit: `80px`, `0.8s`, `50%`
+    Dimension, Percentage,
+
+    // Template literals: `a${b}c` is TemplateStart, TemplateChunk("a"),
+    // TemplateExprStart, <tokens of b>, TemplateExprEnd, TemplateChunk("c"),
+    // TemplateEnd
+    TemplateStart, TemplateChunk, TemplateExprStart, TemplateExprEnd, TemplateEnd,
     
     // Special
-    Comment, Whitespace, Newline, Eof
+    Comment, Whitespace, Newline, Unknown, Eof
 }
 
+/// A token borrowing its text from the source. `value` is the token's text,
+/// except for strings, where it is the text between the quotes.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Token {
+pub struct Token<'src> {
     pub token_type: TokenType,
-    pub value: String,
-    pub line: usize,
-    pub column: usize,
+    pub value: &'src str,
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(token_type: TokenType, value: String, line: usize, column: usize) -> Self {
+impl<'src> Token<'src> {
+    pub fn new(token_type: TokenType, value: &'src str, span: Span) -> Self {
         Self {
             token_type,
             value,
-            line,
-            column,
+            span,
+        }
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::Whitespace | TokenType::Newline | TokenType::Comment
+        )
+    }
+}
+
+// ===================================
+// SOURCE SPANS
+// ===================================
+
+/// A location in the source. `offset` is a byte offset into the input,
+/// `line` and `column` are 1-based and count characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The half-open source range `start..end` a token or node was read from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub file: Arc<str>,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(file: Arc<str>, start: Position, end: Position) -> Self {
+        Self { file, start, end }
+    }
+
+    /// Returns the span covering both `self` and `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span::new(self.file.clone(), self.start, other.end)
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start.line, self.start.column)
+    }
+}
+
+// ===================================
+// DIAGNOSTICS
+// ===================================
+
+/// Stable diagnostic codes, so tooling can match on errors without parsing
+/// their messages.
+pub mod codes {
+    pub const UNTERMINATED_STRING: &str = "E0001";
+    pub const UNTERMINATED_TEMPLATE: &str = "E0002";
+    pub const UNTERMINATED_COMMENT: &str = "E0003";
+    pub const UNEXPECTED_CHARACTER: &str = "E0004";
+    pub const INVALID_NUMBER: &str = "E0005";
+    pub const PROP_DEFAULT_TYPE: &str = "E0006";
+    pub const UNKNOWN_ANIMATION: &str = "E0007";
+    pub const UNKNOWN_WATCH_TARGET: &str = "E0008";
+    pub const UNEXPECTED_TOKEN: &str = "E0009";
+    pub const INVALID_KEYFRAME: &str = "E0010";
+    pub const INVALID_ROUTE: &str = "E0011";
+    pub const MISSING_ENUM_VALUE: &str = "E0012";
+    pub const NESTING_TOO_DEEP: &str = "E0013";
+    pub const MISMATCHED_TAG: &str = "E0014";
+    pub const UNTERMINATED_REGEX: &str = "E0015";
+    pub const DUPLICATE_ENUM_MEMBER: &str = "E0016";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub code: String,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(code: &str, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            span,
         }
     }
 }
 
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: [{}] {}", self.span, self.code, self.message)
+    }
+}
+
 // ===================================
 // AST NODE DEFINITIONS
 // ===================================
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ASTNode {
-    Program(Vec<ASTNode>),
+    Program {
+        body: Vec<ASTNode>,
+        span: Span,
+    },
     Page {
         name: String,
-        attributes: HashMap<String, String>,
+        attributes: Vec<Attribute>,
         body: Vec<ASTNode>,
+        span: Span,
     },
     Component {
         name: String,
-        type_params: Vec<String>,
+        type_params: Vec<TypeParameter>,
         props: Vec<ASTNode>,
         events: Vec<ASTNode>,
         state: Vec<ASTNode>,
         methods: Vec<ASTNode>,
-        render: Option<Box<ASTNode>>,
+        computed: Vec<ASTNode>,
+        watchers: Vec<ASTNode>,
+        lifecycle: Vec<ASTNode>,
+        render: Vec<ASTNode>,
+        span: Span,
     },
+    /// `style Button { ... }` or `style section#hero { ... }`: rules,
+    /// declarations and at-rules under the block's selectors.
     Style {
-        selector: String,
+        selectors: Vec<Selector>,
         rules: Vec<ASTNode>,
+        span: Span,
     },
+    /// `event click on button#save { ... }`, or a named handler as in
+    /// `event onAddTodo(text: string) { ... }` with no target.
     Event {
         event_type: String,
-        target: String,
-        body: Vec<ASTNode>,
+        target: Vec<Selector>,
+        parameters: Vec<ASTNode>,
+        body: Vec<Stmt>,
+        span: Span,
     },
+    /// `state Name { todos: Todo[] = []; }`; `properties` are
+    /// `StateVariable`s.
     State {
         name: String,
         properties: Vec<ASTNode>,
+        span: Span,
     },
+    /// `api Name { baseUrl: "..."; async get(id: string) { ... } }`.
     Api {
         name: String,
-        properties: HashMap<String, String>,
+        properties: Vec<(String, Value)>,
         methods: Vec<ASTNode>,
+        span: Span,
     },
+    /// `router { ... }` or `<router>...</router>`.
     Router {
         routes: Vec<ASTNode>,
+        span: Span,
     },
+    /// `route "/users/:id" -> UserDetailPage;` or
+    /// `<route path="/users/:id" component={UserDetailPage} />`. `params`
+    /// names the path's `:name` segments in order, with `*` for a wildcard
+    /// segment; other attributes, such as `guard` or `layout`, are kept in
+    /// `options`.
     Route {
         path: String,
+        params: Vec<String>,
         component: String,
-        options: HashMap<String, String>,
+        options: Vec<(String, Expr)>,
+        span: Span,
     },
     Layout {
         name: String,
-        render: Option<Box<ASTNode>>,
+        render: Vec<ASTNode>,
+        span: Span,
     },
+    /// `animation fadeIn { from { ... } to { ... } }`.
     Animation {
         name: String,
         keyframes: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `from { ... }` or `0%, 100% { ... }` in an animation or `@keyframes`.
+    Keyframe {
+        offsets: Vec<KeyframeOffset>,
+        declarations: Vec<ASTNode>,
+        span: Span,
     },
+    /// `type Name<T> = Type;`, or `type Name { fields }` for an object type.
     Type {
         name: String,
-        definition: Option<Box<ASTNode>>,
+        type_params: Vec<TypeParameter>,
+        definition: TypeExpr,
+        span: Span,
+    },
+    /// `interface Name<T> extends Base { fields }`.
+    Interface {
+        name: String,
+        type_params: Vec<TypeParameter>,
+        extends: Vec<TypeExpr>,
+        fields: Vec<(String, TypeExpr)>,
+        span: Span,
+    },
+    /// `enum Name { A, B = 2, C = "c" }`, with each member's value as
+    /// written.
+    Enum {
+        name: String,
+        members: Vec<EnumMember>,
+        span: Span,
     },
     Hook {
         name: String,
         parameters: Vec<ASTNode>,
-        body: Vec<ASTNode>,
+        body: Vec<Stmt>,
+        span: Span,
     },
+    /// `plugin Name { name: "UI"; components: [Button]; init() { ... } }`.
+    /// A `methods: { track: (event: string) => void; }` member declares
+    /// method signatures.
     Plugin {
         name: String,
-        properties: HashMap<String, String>,
+        properties: Vec<(String, Value)>,
+        signatures: Vec<(String, TypeExpr)>,
+        methods: Vec<ASTNode>,
+        span: Span,
     },
+    /// `config { entry: "./src/index.ema"; devServer: { port: 3000; }; }`.
     Config {
-        properties: HashMap<String, String>,
+        properties: Vec<(String, Value)>,
+        span: Span,
     },
+    /// `import Default, { a, b as c } from "./m.ema";`,
+    /// `import * as ns from "./m.ema";` or, with no bindings, the side-effect
+    /// import `import "./theme.css";`.
     Import {
-        specifiers: Vec<String>,
+        default: Option<String>,
+        namespace: Option<String>,
+        specifiers: Vec<ModuleSpecifier>,
         source: String,
+        span: Span,
     },
+    /// `export { a as b };`, `export * from "./m.ema";`, `export default x;`
+    /// or an exported declaration. `source` is empty unless re-exporting.
     Export {
-        specifiers: Vec<String>,
+        clause: ExportClause,
         source: String,
         is_default: bool,
+        span: Span,
+    },
+    /// A top-level `const`, `let` or `var` declaration.
+    Variable {
+        statement: Stmt,
+        span: Span,
+    },
+    /// Any other top-level script statement, as in
+    /// `document.head.appendChild(style);`.
+    Script {
+        statement: Stmt,
+        span: Span,
     },
     Element {
         tag_name: String,
-        attributes: HashMap<String, String>,
+        attributes: Vec<Attribute>,
         children: Vec<ASTNode>,
         self_closing: bool,
+        span: Span,
     },
     Text {
         value: String,
+        span: Span,
     },
+    /// `{expression}` in markup.
     Expression {
-        expression: String,
+        expression: Expr,
+        span: Span,
+    },
+    /// `[[expression]]` in text or inside an attribute value.
+    Interpolation {
+        expression: Expr,
+        span: Span,
+    },
+    /// `prop name: Type = default;` in a component.
+    Prop {
+        name: String,
+        prop_type: TypeExpr,
+        default: Option<Value>,
+        span: Span,
+    },
+    /// `event onClick: () => void;` in a component.
+    EventDeclaration {
+        name: String,
+        signature: TypeExpr,
+        span: Span,
+    },
+    /// `state name: Type = initial;` in a component.
+    StateVariable {
+        name: String,
+        type_annotation: Option<TypeExpr>,
+        initial: Option<Expr>,
+        span: Span,
+    },
+    /// `function name(parameters) { ... }` in a component or at the top
+    /// level.
+    Method {
+        name: String,
+        is_async: bool,
+        parameters: Vec<ASTNode>,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    /// `computed name() { ... }` in a component: a getter whose value is
+    /// cached until state or an attribute changes.
+    Computed {
+        name: String,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    /// `watch name(value, previous) { ... }` in a component, run when the
+    /// state variable `name` changes.
+    Watch {
+        target: String,
+        parameters: Vec<ASTNode>,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    /// `mounted() { ... }` or `unmounted() { ... }` in a component.
+    Lifecycle {
+        hook: LifecycleHook,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    /// `name`, `name: Type` or `name = default` in a parameter list.
+    Parameter {
+        name: String,
+        type_annotation: Option<TypeExpr>,
+        default: Option<Expr>,
+        span: Span,
+    },
+    /// `&:hover { ... }` or `.title { ... }` nested in a style block.
+    Rule {
+        selectors: Vec<Selector>,
+        children: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `padding: 80px 40px;`, with `!important` split off.
+    Declaration {
+        property: String,
+        value: String,
+        important: bool,
+        span: Span,
+    },
+    /// `@media (max-width: 480px) { ... }`, or `@import url(...);` with no
+    /// block.
+    AtRule {
+        name: String,
+        prelude: String,
+        block: Option<Vec<ASTNode>>,
+        span: Span,
     },
 }
 
-// ===================================
-// LEXER
-// ===================================
-
-pub struct Lexer {
-    input: String,
-    position: usize,
-    line: usize,
-    column: usize,
-    keywords: HashMap<String, TokenType>,
-}
-
-impl Lexer {
-    pub fn new(input: String) -> Self {
-        let mut keywords = HashMap::new();
-        keywords.insert("page".to_string(), TokenType::Page);
-        keywords.insert("component".to_string(), TokenType::Component);
-        keywords.insert("style".to_string(), TokenType::Style);
-        keywords.insert("event".to_string(), TokenType::Event);
-        keywords.insert("state".to_string(), TokenType::State);
-        keywords.insert("api".to_string(), TokenType::Api);
-        keywords.insert("router".to_string(), TokenType::Router);
-        keywords.insert("route".to_string(), TokenType::Route);
-        keywords.insert("layout".to_string(), TokenType::Layout);
-        keywords.insert("animation".to_string(), TokenType::Animation);
-        keywords.insert("type".to_string(), TokenType::Type);
-        keywords.insert("hook".to_string(), TokenType::Hook);
-        keywords.insert("plugin".to_string(), TokenType::Plugin);
-        keywords.insert("config".to_string(), TokenType::Config);
-        keywords.insert("import".to_string(), TokenType::Import);
-        keywords.insert("export".to_string(), TokenType::Export);
-        keywords.insert("from".to_string(), TokenType::From);
-        keywords.insert("as".to_string(), TokenType::As);
-        keywords.insert("if".to_string(), TokenType::If);
-        keywords.insert("else".to_string(), TokenType::Else);
-        keywords.insert("for".to_string(), TokenType::For);
-        keywords.insert("while".to_string(), TokenType::While);
-        keywords.insert("function".to_string(), TokenType::Function);
-        keywords.insert("async".to_string(), TokenType::Async);
-        keywords.insert("await".to_string(), TokenType::Await);
-        keywords.insert("return".to_string(), TokenType::Return);
-        keywords.insert("const".to_string(), TokenType::Const);
-        keywords.insert("let".to_string(), TokenType::Let);
-        keywords.insert("var".to_string(), TokenType::Var);
-        keywords.insert("true".to_string(), TokenType::True);
-        keywords.insert("false".to_string(), TokenType::False);
-        keywords.insert("null".to_string(), TokenType::Null);
-        keywords.insert("undefined".to_string(), TokenType::Undefined);
-        keywords.insert("class".to_string(), TokenType::Class);
-        keywords.insert("extends".to_string(), TokenType::Extends);
-        keywords.insert("implements".to_string(), TokenType::Implements);
-        keywords.insert("interface".to_string(), TokenType::Interface);
-        keywords.insert("enum".to_string(), TokenType::Enum);
-        keywords.insert("namespace".to_string(), TokenType::Namespace);
-        keywords.insert("render".to_string(), TokenType::Render);
-        keywords.insert("computed".to_string(), TokenType::Computed);
-        keywords.insert("watch".to_string(), TokenType::Watch);
-        keywords.insert("mounted".to_string(), TokenType::Mounted);
-        keywords.insert("unmounted".to_string(), TokenType::Unmounted);
-
-        Self {
-            input,
-            position: 0,
-            line: 1,
-            column: 1,
-            keywords,
+impl ASTNode {
+    pub fn span(&self) -> &Span {
+        match self {
+            ASTNode::Program { span, .. }
+            | ASTNode::Page { span, .. }
+            | ASTNode::Component { span, .. }
+            | ASTNode::Style { span, .. }
+            | ASTNode::Event { span, .. }
+            | ASTNode::State { span, .. }
+            | ASTNode::Api { span, .. }
+            | ASTNode::Router { span, .. }
+            | ASTNode::Route { span, .. }
+            | ASTNode::Layout { span, .. }
+            | ASTNode::Animation { span, .. }
+            | ASTNode::Keyframe { span, .. }
+            | ASTNode::Type { span, .. }
+            | ASTNode::Interface { span, .. }
+            | ASTNode::Enum { span, .. }
+            | ASTNode::Hook { span, .. }
+            | ASTNode::Plugin { span, .. }
+            | ASTNode::Config { span, .. }
+            | ASTNode::Import { span, .. }
+            | ASTNode::Export { span, .. }
+            | ASTNode::Variable { span, .. }
+            | ASTNode::Script { span, .. }
+            | ASTNode::Element { span, .. }
+            | ASTNode::Text { span, .. }
+            | ASTNode::Expression { span, .. }
+            | ASTNode::Interpolation { span, .. }
+            | ASTNode::Prop { span, .. }
+            | ASTNode::EventDeclaration { span, .. }
+            | ASTNode::StateVariable { span, .. }
+            | ASTNode::Method { span, .. }
+            | ASTNode::Computed { span, .. }
+            | ASTNode::Watch { span, .. }
+            | ASTNode::Lifecycle { span, .. }
+            | ASTNode::Parameter { span, .. }
+            | ASTNode::Rule { span, .. }
+            | ASTNode::Declaration { span, .. }
+            | ASTNode::AtRule { span, .. } => span,
         }
     }
+}
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
-        let mut tokens = Vec::new();
-
-        while self.position < self.input.len() {
-            let current = self.input.chars().nth(self.position).unwrap();
-
-            if current.is_whitespace() {
-                if current == '\n' {
-                    tokens.push(Token::new(TokenType::Newline, "\n".to_string(), self.line, self.column));
-                    self.line += 1;
-                    self.column = 1;
-                } else {
-                    tokens.push(Token::new(TokenType::Whitespace, current.to_string(), self.line, self.column));
-                    self.column += 1;
-                }
-                self.position += 1;
-                continue;
-            }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    pub value: AttributeValue,
+    pub span: Span,
+}
 
-            // Comments
-            if current == '/' && self.position + 1 < self.input.len() {
-                let next = self.input.chars().nth(self.position + 1).unwrap();
-                if next == '/' {
-                    tokens.push(self.read_line_comment()?);
-                    continue;
-                } else if next == '*' {
-                    tokens.push(self.read_block_comment()?);
-                    continue;
-                }
-            }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AttributeValue {
+    /// `disabled`, with no value.
+    Boolean,
+    /// `title="Home"`.
+    Static(String),
+    /// `class="ema-badge--[[variant]]"`: `Text` and `Interpolation` parts.
+    Interpolated(Vec<ASTNode>),
+    /// `onclick={handler}`.
+    Expression(Expr),
+}
 
-            // Strings
-            if current == '"' || current == '\'' {
-                tokens.push(self.read_string()?);
-                continue;
-            }
+/// A CSS selector: compound selectors joined by combinators, as in
+/// `section#hero > .title:hover`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Selector {
+    pub compounds: Vec<CompoundSelector>,
+}
 
-            // Template literals
-            if current == '`' {
-                tokens.push(self.read_template_literal()?);
-                continue;
-            }
+/// Simple selectors written together, with the combinator before them.
+/// Only a nested selector's first compound may have one, as in `> li`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompoundSelector {
+    pub combinator: Option<Combinator>,
+    pub parts: Vec<SimpleSelector>,
+}
 
-            // Numbers
-            if current.is_ascii_digit() {
-                tokens.push(self.read_number()?);
-                continue;
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Combinator {
+    /// Whitespace.
+    Descendant,
+    /// `>`.
+    Child,
+    /// `+`.
+    NextSibling,
+    /// `~`.
+    SubsequentSibling,
+}
 
-            // Identifiers and keywords
-            if current.is_alphabetic() || current == '_' {
-                tokens.push(self.read_identifier()?);
-                continue;
-            }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SimpleSelector {
+    /// `&`, the parent rule's selector.
+    Nesting,
+    /// `--primary` in `&--primary`, appended to the parent's selector.
+    Suffix(String),
+    Type(String),
+    Universal,
+    Id(String),
+    Class(String),
+    /// `[type="h1"]`, the text between the brackets.
+    Attribute(String),
+    /// `:hover`, or `:not(:disabled)` with its argument as written.
+    PseudoClass {
+        name: String,
+        argument: Option<String>,
+    },
+    PseudoElement(String),
+    /// A token this parser doesn't model, kept as written.
+    Raw(String),
+}
 
-            // Operators and punctuation
-            match current {
-                '=' => {
-                    if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '=' {
-                        tokens.push(Token::new(TokenType::Equal, "==".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '>' {
-                        tokens.push(Token::new(TokenType::Arrow, "=>".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else {
-                        tokens.push(Token::new(TokenType::Assign, "=".to_string(), self.line, self.column));
-                        self.position += 1;
-                        self.column += 1;
-                    }
-                }
-                '!' => {
-                    if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '=' {
-                        tokens.push(Token::new(TokenType::NotEqual, "!=".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else {
-                        tokens.push(Token::new(TokenType::Not, "!".to_string(), self.line, self.column));
-                        self.position += 1;
-                        self.column += 1;
-                    }
-                }
-                '<' => {
-                    if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '/' {
-                        tokens.push(Token::new(TokenType::ClosingTagStart, "</".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '=' {
-                        tokens.push(Token::new(TokenType::LessEqual, "<=".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else {
-                        tokens.push(Token::new(TokenType::OpeningTagStart, "<".to_string(), self.line, self.column));
-                        self.position += 1;
-                        self.column += 1;
-                    }
-                }
-                '>' => {
-                    if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '=' {
-                        tokens.push(Token::new(TokenType::GreaterEqual, ">=".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else {
-                        tokens.push(Token::new(TokenType::TagEnd, ">".to_string(), self.line, self.column));
-                        self.position += 1;
-                        self.column += 1;
-                    }
-                }
-                '+' => {
-                    tokens.push(Token::new(TokenType::Plus, "+".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '-' => {
-                    tokens.push(Token::new(TokenType::Minus, "-".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '*' => {
-                    tokens.push(Token::new(TokenType::Multiply, "*".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '/' => {
-                    tokens.push(Token::new(TokenType::Divide, "/".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '%' => {
-                    tokens.push(Token::new(TokenType::Modulo, "%".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '&' => {
-                    if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '&' {
-                        tokens.push(Token::new(TokenType::And, "&&".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else {
-                        tokens.push(Token::new(TokenType::And, "&".to_string(), self.line, self.column));
-                        self.position += 1;
-                        self.column += 1;
-                    }
-                }
-                '|' => {
-                    if self.position + 1 < self.input.len() && self.input.chars().nth(self.position + 1).unwrap() == '|' {
-                        tokens.push(Token::new(TokenType::Or, "||".to_string(), self.line, self.column));
-                        self.position += 2;
-                        self.column += 2;
-                    } else {
-                        tokens.push(Token::new(TokenType::Pipe, "|".to_string(), self.line, self.column));
-                        self.position += 1;
-                        self.column += 1;
-                    }
-                }
-                '(' => {
-                    tokens.push(Token::new(TokenType::LeftParen, "(".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                ')' => {
-                    tokens.push(Token::new(TokenType::RightParen, ")".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '{' => {
-                    tokens.push(Token::new(TokenType::LeftBrace, "{".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '}' => {
-                    tokens.push(Token::new(TokenType::RightBrace, "}".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '[' => {
-                    tokens.push(Token::new(TokenType::LeftBracket, "[".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                ']' => {
-                    tokens.push(Token::new(TokenType::RightBracket, "]".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                ';' => {
-                    tokens.push(Token::new(TokenType::Semicolon, ";".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                ',' => {
-                    tokens.push(Token::new(TokenType::Comma, ",".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '.' => {
-                    tokens.push(Token::new(TokenType::Dot, ".".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                ':' => {
-                    tokens.push(Token::new(TokenType::Colon, ":".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                '?' => {
-                    tokens.push(Token::new(TokenType::Question, "?".to_string(), self.line, self.column));
-                    self.position += 1;
-                    self.column += 1;
-                }
-                _ => {
-                    // Unknown character, skip
-                    self.position += 1;
-                    self.column += 1;
-                }
-            }
+impl Selector {
+    /// The component a style block like `style Button` is for.
+    pub fn component_name(&self) -> Option<&str> {
+        match self.compounds.as_slice() {
+            [CompoundSelector { combinator: None, parts }] => match parts.as_slice() {
+                [SimpleSelector::Type(name)] if name.starts_with(|c: char| c.is_ascii_uppercase()) => Some(name),
+                _ => None,
+            },
+            _ => None,
         }
-
-        tokens.push(Token::new(TokenType::Eof, "".to_string(), self.line, self.column));
-        Ok(tokens)
     }
 
-    fn read_line_comment(&mut self) -> Result<Token, String> {
-        let start = self.position;
-        while self.position < self.input.len() && self.input.chars().nth(self.position).unwrap() != '\n' {
-            self.position += 1;
-        }
-        let value = self.input[start..self.position].to_string();
-        let token = Token::new(TokenType::Comment, value, self.line, self.column);
-        self.column += value.len();
-        Ok(token)
+    fn has_nesting(&self) -> bool {
+        self.compounds.iter().any(|compound| compound.parts.contains(&SimpleSelector::Nesting))
     }
 
-    fn read_block_comment(&mut self) -> Result<Token, String> {
-        let start = self.position;
-        self.position += 2; // Skip /*
-        self.column += 2;
-
-        while self.position < self.input.len() - 1 {
-            if self.input.chars().nth(self.position).unwrap() == '*' 
-                && self.input.chars().nth(self.position + 1).unwrap() == '/' {
-                self.position += 2;
-                self.column += 2;
-                break;
-            }
-            if self.input.chars().nth(self.position).unwrap() == '\n' {
-                self.line += 1;
-                self.column = 1;
-            } else {
-                self.column += 1;
+    /// The selector as CSS nested under `parent`: `&` stands for the parent,
+    /// and a selector without one is the parent's descendant.
+    pub fn to_css(&self, parent: Option<&str>) -> String {
+        let mut css = String::new();
+        if let Some(parent) = parent {
+            if !self.has_nesting() {
+                css.push_str(parent);
+                if self.compounds.first().is_none_or(|compound| compound.combinator.is_none()) {
+                    css.push(' ');
+                }
             }
-            self.position += 1;
         }
 
-        let value = self.input[start..self.position].to_string();
-        Ok(Token::new(TokenType::Comment, value, self.line, self.column))
-    }
-
-    fn read_string(&mut self) -> Result<Token, String> {
-        let quote = self.input.chars().nth(self.position).unwrap();
-        self.position += 1;
-        self.column += 1;
-
-        let start = self.position;
-        while self.position < self.input.len() && self.input.chars().nth(self.position).unwrap() != quote {
-            if self.input.chars().nth(self.position).unwrap() == '\\' && self.position + 1 < self.input.len() {
-                self.position += 2;
-                self.column += 2;
-            } else {
-                self.position += 1;
-                self.column += 1;
+        for (index, compound) in self.compounds.iter().enumerate() {
+            match compound.combinator {
+                Some(Combinator::Descendant) if index > 0 => css.push(' '),
+                Some(Combinator::Child) => css.push_str(if css.is_empty() { "> " } else { " > " }),
+                Some(Combinator::NextSibling) => css.push_str(if css.is_empty() { "+ " } else { " + " }),
+                Some(Combinator::SubsequentSibling) => css.push_str(if css.is_empty() { "~ " } else { " ~ " }),
+                _ => {}
             }
-        }
-
-        if self.position < self.input.len() {
-            self.position += 1;
-            self.column += 1;
-        }
-
-        let value = self.input[start..self.position - 1].to_string();
-        Ok(Token::new(TokenType::String, value, self.line, self.column))
-    }
-
-    fn read_template_literal(&mut self) -> Result<Token, String> {
-        self.position += 1; // Skip `
-        self.column += 1;
-
-        let start = self.position;
-        while self.position < self.input.len() && self.input.chars().nth(self.position).unwrap() != '`' {
-            if self.input.chars().nth(self.position).unwrap() == '\\' && self.position + 1 < self.input.len() {
-                self.position += 2;
-                self.column += 2;
-            } else {
-                self.position += 1;
-                self.column += 1;
+            for part in &compound.parts {
+                match part {
+                    SimpleSelector::Nesting => css.push_str(parent.unwrap_or("&")),
+                    SimpleSelector::Suffix(text) | SimpleSelector::Type(text) | SimpleSelector::Raw(text) => css.push_str(text),
+                    SimpleSelector::Universal => css.push('*'),
+                    SimpleSelector::Id(name) => css.push_str(&format!("#{}", name)),
+                    SimpleSelector::Class(name) => css.push_str(&format!(".{}", name)),
+                    SimpleSelector::Attribute(text) => css.push_str(&format!("[{}]", text)),
+                    SimpleSelector::PseudoClass { name, argument: None } => css.push_str(&format!(":{}", name)),
+                    SimpleSelector::PseudoClass { name, argument: Some(argument) } => {
+                        css.push_str(&format!(":{}({})", name, argument))
+                    }
+                    SimpleSelector::PseudoElement(name) => css.push_str(&format!("::{}", name)),
+                }
             }
         }
+        css
+    }
+}
 
-        if self.position < self.input.len() {
-            self.position += 1;
-            self.column += 1;
-        }
-
-        let value = self.input[start..self.position - 1].to_string();
-        Ok(Token::new(TokenType::TemplateLiteral, value, self.line, self.column))
+impl std::fmt::Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_css(None))
     }
+}
 
-    fn read_number(&mut self) -> Result<Token, String> {
-        let start = self.position;
+/// When a component's `mounted` or `unmounted` block runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifecycleHook {
+    Mounted,
+    Unmounted,
+}
 
-        while self.position < self.input.len() 
-            && (self.input.chars().nth(self.position).unwrap().is_ascii_digit() 
-                || self.input.chars().nth(self.position).unwrap() == '.') {
-            self.position += 1;
-            self.column += 1;
-        }
+/// Where a keyframe falls in its animation: `from`, `to` or a percentage.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum KeyframeOffset {
+    From,
+    To,
+    Percentage(f64),
+}
 
-        let value = self.input[start..self.position].to_string();
-        Ok(Token::new(TokenType::Number, value, self.line, self.column))
+impl std::fmt::Display for KeyframeOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyframeOffset::From => write!(f, "from"),
+            KeyframeOffset::To => write!(f, "to"),
+            KeyframeOffset::Percentage(percentage) => write!(f, "{}%", percentage),
+        }
     }
+}
 
-    fn read_identifier(&mut self) -> Result<Token, String> {
-        let start = self.position;
+/// `name` or `name as alias` in an import or export list. Either may be
+/// a string, as in `import { "a-b" as ab }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleSpecifier {
+    pub name: String,
+    pub alias: Option<String>,
+}
 
-        while self.position < self.input.len() 
-            && (self.input.chars().nth(self.position).unwrap().is_alphanumeric() 
-                || self.input.chars().nth(self.position).unwrap() == '_') {
-            self.position += 1;
-            self.column += 1;
+impl std::fmt::Display for ModuleSpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = |name: &str| if is_identifier_name(name) { name.to_string() } else { quote_js_string(name) };
+        match &self.alias {
+            Some(alias) => write!(f, "{} as {}", name(&self.name), name(alias)),
+            None => write!(f, "{}", name(&self.name)),
         }
-
-        let value = self.input[start..self.position].to_string();
-        let token_type = self.keywords.get(&value)
-            .cloned()
-            .unwrap_or(TokenType::Identifier);
-        
-        Ok(Token::new(token_type, value, self.line, self.column))
     }
 }
 
-// ===================================
-// PARSER
-// ===================================
+/// What an `export` exports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExportClause {
+    /// `export { a, b as c }`.
+    Named(Vec<ModuleSpecifier>),
+    /// `export * from "..."`, or `export * as ns from "..."`.
+    All(Option<String>),
+    /// `export default expression;`.
+    Expression(Expr),
+    /// `export const x = 1;`.
+    Variable(Stmt),
+    /// `export function f() {}`, `export component Button { ... }` and other
+    /// declarations.
+    Declaration(Box<ASTNode>),
+}
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+/// A type annotation, as in `prop size: "sm" | "md" | "lg" = "md";`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TypeExpr {
+    /// `string`, `number`, `boolean`, `any`, `void`, `null` and the other
+    /// built-in types.
+    Primitive(String),
+    /// A literal type: `"premium"`, `42`, `true`.
+    Literal(Value),
+    /// A declared type or class by name: `Todo`, `Function`, `Date`.
+    Named(String),
+    /// `Type[]`.
+    Array(Box<TypeExpr>),
+    /// `Name<Arguments>`, as in `Promise<User>`.
+    Generic {
+        name: String,
+        arguments: Vec<TypeExpr>,
+    },
+    /// `A | B | C`.
+    Union(Vec<TypeExpr>),
+    /// `Type?`, and the type of `prop name?: Type`.
+    Optional(Box<TypeExpr>),
+    /// `(id: number, text: string) => void`.
+    Function {
+        parameters: Vec<(String, TypeExpr)>,
+        return_type: Box<TypeExpr>,
+    },
+    /// `{ id: number; avatar?: string }`: fields in order, with `Optional`
+    /// types for `?` fields.
+    Object(Vec<(String, TypeExpr)>),
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
-    }
+/// `T`, `T extends Base` or `T = Default` in a type declaration's or a
+/// component's `<...>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeParameter {
+    pub name: String,
+    pub constraint: Option<TypeExpr>,
+    pub default: Option<TypeExpr>,
+}
 
-    pub fn parse(&mut self) -> Result<ASTNode, String> {
-        let mut statements = Vec::new();
+/// A member of an `enum`, with its value as written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnumMember {
+    pub name: String,
+    pub value: Option<Expr>,
+    pub span: Span,
+}
 
-        while !self.is_at_end() {
-            if let Some(statement) = self.parse_statement()? {
-                statements.push(statement);
+const PRIMITIVE_TYPES: &[&str] = &[
+    "string", "number", "boolean", "bigint", "symbol", "object",
+    "any", "unknown", "never", "void", "null", "undefined",
+];
+
+impl TypeExpr {
+    /// The literal values a value of this type is limited to, as in the
+    /// variants of `"minimal" | "neo"`; `None` when it is not a literal type.
+    pub fn literal_values(&self) -> Option<Vec<&Value>> {
+        match self {
+            TypeExpr::Literal(value) => Some(vec![value]),
+            TypeExpr::Union(members) => {
+                let mut values = Vec::new();
+                for member in members {
+                    values.extend(member.literal_values()?);
+                }
+                Some(values)
             }
+            TypeExpr::Optional(inner) => inner.literal_values(),
+            _ => None,
         }
-
-        Ok(ASTNode::Program(statements))
     }
 
-    fn is_at_end(&self) -> bool {
-        self.current >= self.tokens.len() || self.tokens[self.current].token_type == TokenType::Eof
+    /// Whether `value` may have this type. Named, generic and function types
+    /// and values only known at runtime are always accepted.
+    pub fn accepts(&self, value: &Value) -> bool {
+        if let Value::Expression(_) | Value::Reference(_) = value {
+            return true;
+        }
+        match self {
+            TypeExpr::Primitive(name) => match name.as_str() {
+                "string" => matches!(value, Value::String(_)),
+                "number" => matches!(value, Value::Number(_)),
+                "boolean" => matches!(value, Value::Boolean(_)),
+                "object" => matches!(value, Value::Object(_) | Value::Array(_) | Value::Null),
+                "null" => matches!(value, Value::Null),
+                "undefined" | "void" => matches!(value, Value::Undefined),
+                "never" => false,
+                _ => true,
+            },
+            TypeExpr::Literal(literal) => literal == value,
+            TypeExpr::Array(element) => match value {
+                Value::Array(items) => items.iter().all(|item| element.accepts(item)),
+                _ => false,
+            },
+            TypeExpr::Union(members) => members.iter().any(|member| member.accepts(value)),
+            TypeExpr::Optional(inner) => matches!(value, Value::Null | Value::Undefined) || inner.accepts(value),
+            TypeExpr::Generic { name, arguments } if name == "Array" && arguments.len() == 1 => {
+                TypeExpr::Array(Box::new(arguments[0].clone())).accepts(value)
+            }
+            TypeExpr::Object(fields) => match value {
+                Value::Object(entries) => fields.iter().all(|(name, field_type)| {
+                    match entries.iter().find(|(key, _)| key == name) {
+                        Some((_, entry)) => field_type.accepts(entry),
+                        None => matches!(field_type, TypeExpr::Optional(_)),
+                    }
+                }),
+                _ => false,
+            },
+            TypeExpr::Named(_) | TypeExpr::Generic { .. } | TypeExpr::Function { .. } => true,
+        }
     }
 
-    fn peek(&self) -> Option<&Token> {
-        if self.is_at_end() {
-            None
-        } else {
-            Some(&self.tokens[self.current])
+    /// The JavaScript `typeof`-style name of values of this type, as used
+    /// by the runtime's prop checks.
+    pub fn runtime_type(&self) -> &'static str {
+        match self {
+            TypeExpr::Primitive(name) => match name.as_str() {
+                "string" => "string",
+                "number" => "number",
+                "boolean" => "boolean",
+                "object" => "object",
+                _ => "any",
+            },
+            TypeExpr::Literal(value) => value.runtime_type(),
+            TypeExpr::Named(name) if name == "Function" => "function",
+            TypeExpr::Named(_) => "object",
+            TypeExpr::Array(_) => "array",
+            TypeExpr::Generic { name, .. } if name == "Array" => "array",
+            TypeExpr::Generic { .. } => "object",
+            TypeExpr::Union(members) => {
+                let first = members.first().map_or("any", |member| member.runtime_type());
+                if members.iter().all(|member| member.runtime_type() == first) { first } else { "any" }
+            }
+            TypeExpr::Optional(inner) => inner.runtime_type(),
+            TypeExpr::Function { .. } => "function",
+            TypeExpr::Object(_) => "object",
         }
     }
+}
 
-    fn previous(&self) -> Option<&Token> {
-        if self.current == 0 {
-            None
-        } else {
-            Some(&self.tokens[self.current - 1])
+impl std::fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeExpr::Primitive(name) | TypeExpr::Named(name) => write!(f, "{}", name),
+            TypeExpr::Literal(Value::String(text)) => write!(f, "{}", quote_type_string(text)),
+            TypeExpr::Literal(value) => write!(f, "{}", value.to_js()),
+            TypeExpr::Array(element) => match **element {
+                TypeExpr::Union(_) | TypeExpr::Function { .. } => write!(f, "({})[]", element),
+                _ => write!(f, "{}[]", element),
+            },
+            TypeExpr::Generic { name, arguments } => {
+                let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+                write!(f, "{}<{}>", name, arguments.join(", "))
+            }
+            TypeExpr::Union(members) => {
+                let members: Vec<String> = members.iter().map(|member| member.to_string()).collect();
+                write!(f, "{}", members.join(" | "))
+            }
+            TypeExpr::Optional(inner) => write!(f, "{}?", inner),
+            TypeExpr::Function { parameters, return_type } => {
+                let parameters: Vec<String> = parameters.iter()
+                    .map(|(name, parameter_type)| match parameter_type {
+                        TypeExpr::Optional(inner) => format!("{}?: {}", name, inner),
+                        _ => format!("{}: {}", name, parameter_type),
+                    })
+                    .collect();
+                write!(f, "({}) => {}", parameters.join(", "), return_type)
+            }
+            TypeExpr::Object(fields) if fields.is_empty() => write!(f, "{{}}"),
+            TypeExpr::Object(fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|(name, field_type)| match field_type {
+                        TypeExpr::Optional(inner) => format!("{}?: {}", name, inner),
+                        _ => format!("{}: {}", name, field_type),
+                    })
+                    .collect();
+                write!(f, "{{ {} }}", fields.join("; "))
+            }
         }
     }
+}
 
-    fn advance(&mut self) -> Option<&Token> {
-        if !self.is_at_end() {
-            self.current += 1;
+/// A value written in source and known at compile time, such as a prop
+/// default. Anything else is kept as `Expression` source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    String(String),
+    Number(f64),
+    Boolean(bool),
+    Null,
+    Undefined,
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+    /// A name, as in `plugins: [EmadocsUI, Sass]`, or a dotted path like
+    /// `Emadocs.UI`.
+    Reference(String),
+    Expression(String),
+}
+
+impl Value {
+    /// The value as a JavaScript literal.
+    pub fn to_js(&self) -> String {
+        match self {
+            Value::String(text) => quote_js_string(text),
+            Value::Number(number) => number.to_string(),
+            Value::Boolean(value) => value.to_string(),
+            Value::Null => "null".to_string(),
+            Value::Undefined => "undefined".to_string(),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_js).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::Object(entries) => {
+                let entries: Vec<String> = entries.iter()
+                    .map(|(key, value)| {
+                        let key = if is_identifier_name(key) { key.clone() } else { quote_js_string(key) };
+                        format!("{}: {}", key, value.to_js())
+                    })
+                    .collect();
+                format!("{{ {} }}", entries.join(", "))
+            }
+            Value::Reference(source) | Value::Expression(source) => source.clone(),
         }
-        self.previous()
     }
 
-    fn check(&self, token_type: &TokenType) -> bool {
-        if self.is_at_end() {
-            false
-        } else {
-            self.tokens[self.current].token_type == *token_type
+    fn runtime_type(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Object(_) | Value::Null => "object",
+            Value::Undefined | Value::Reference(_) | Value::Expression(_) => "any",
         }
     }
+}
+
+/// An expression from script code, a `{...}` hole or a `[[...]]`
+/// interpolation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Expr {
+    Identifier(String),
+    /// `"text"`, `42`, `true`, `null` or `undefined`.
+    Literal(Value),
+    /// `` `a${b}c` ``: the raw text chunks around the embedded expressions,
+    /// one more chunk than expressions.
+    Template {
+        quasis: Vec<String>,
+        expressions: Vec<Expr>,
+    },
+    Array(Vec<Expr>),
+    Object(Vec<Property>),
+    /// `object.property` or `object?.property`.
+    Member {
+        object: Box<Expr>,
+        property: String,
+        optional: bool,
+    },
+    /// `object[index]` or `object?.[index]`.
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        optional: bool,
+    },
+    /// `callee(arguments)` or `callee?.(arguments)`.
+    Call {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+        optional: bool,
+    },
+    New {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+    },
+    /// `!a`, `-a`, `typeof a`.
+    Unary {
+        operator: String,
+        operand: Box<Expr>,
+    },
+    /// `++a` or `a--`.
+    Update {
+        operator: String,
+        prefix: bool,
+        operand: Box<Expr>,
+    },
+    /// `a + b`, `a && b`, `a ?? b`.
+    Binary {
+        operator: String,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// `test ? consequent : alternate`.
+    Conditional {
+        test: Box<Expr>,
+        consequent: Box<Expr>,
+        alternate: Box<Expr>,
+    },
+    /// `target = value`, `target += value`.
+    Assign {
+        operator: String,
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    /// `(a, b) => body` or `async item => body`.
+    Arrow {
+        parameters: Vec<ASTNode>,
+        body: ArrowBody,
+        is_async: bool,
+    },
+    /// `function name(parameters) { ... }` used as a value.
+    Function {
+        name: Option<String>,
+        parameters: Vec<ASTNode>,
+        body: Vec<Stmt>,
+        is_async: bool,
+    },
+    Await(Box<Expr>),
+    /// `...items` in an array, object or argument list.
+    Spread(Box<Expr>),
+    /// `/pattern/flags`, as written.
+    Regex(String),
+    /// Markup used as a value, as in `cond ? <spinner /> : "Add"`.
+    Markup(Box<ASTNode>),
+    /// Source the expression grammar can't parse, kept as written.
+    Raw(String),
+}
+
+/// The body of an arrow function: `x => x * 2` or `x => { ... }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ArrowBody {
+    Expression(Box<Expr>),
+    Block(Vec<Stmt>),
+}
+
+/// A member of an object literal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Property {
+    /// `key: value` or `"key": value`.
+    KeyValue {
+        key: String,
+        value: Expr,
+    },
+    /// `[key]: value`.
+    Computed {
+        key: Expr,
+        value: Expr,
+    },
+    /// `name`, short for `name: name`.
+    Shorthand(String),
+    Spread(Expr),
+    /// `get key() { ... }` or `set key(value) { ... }`; `kind` is `get` or
+    /// `set`.
+    Accessor {
+        kind: String,
+        key: String,
+        parameters: Vec<ASTNode>,
+        body: Vec<Stmt>,
+    },
+}
+
+/// A statement in an event, hook or function body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Stmt {
+    /// `const a = 1, b;`. Each binding is a name or a destructuring
+    /// pattern as written, as in `const [user, setUser] = useState(null)`.
+    Variable {
+        kind: String,
+        declarations: Vec<(String, Option<Expr>)>,
+    },
+    Function {
+        name: String,
+        parameters: Vec<ASTNode>,
+        body: Vec<Stmt>,
+        is_async: bool,
+    },
+    Expression(Expr),
+    If {
+        test: Expr,
+        consequent: Box<Stmt>,
+        alternate: Option<Box<Stmt>>,
+    },
+    /// `for (init; test; update) body`.
+    For {
+        init: Option<Box<Stmt>>,
+        test: Option<Expr>,
+        update: Option<Expr>,
+        body: Box<Stmt>,
+    },
+    /// `for (const item of items) body`, or `in` when `of` is false.
+    ForIn {
+        kind: Option<String>,
+        binding: String,
+        of: bool,
+        object: Expr,
+        body: Box<Stmt>,
+    },
+    While {
+        test: Expr,
+        body: Box<Stmt>,
+    },
+    DoWhile {
+        body: Box<Stmt>,
+        test: Expr,
+    },
+    Switch {
+        discriminant: Expr,
+        cases: Vec<SwitchCase>,
+    },
+    Return(Option<Expr>),
+    Throw(Expr),
+    Break(Option<String>),
+    Continue(Option<String>),
+    /// `try { ... } catch (parameter) { ... } finally { ... }`.
+    Try {
+        block: Vec<Stmt>,
+        parameter: Option<String>,
+        handler: Option<Vec<Stmt>>,
+        finalizer: Option<Vec<Stmt>>,
+    },
+    Block(Vec<Stmt>),
+    /// Source the statement grammar can't parse, kept as written.
+    Raw(String),
+}
+
+/// `case test:` followed by its statements; `default:` has no test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwitchCase {
+    pub test: Option<Expr>,
+    pub body: Vec<Stmt>,
+}
+
+impl Expr {
+    /// How tightly the expression binds, from 1 (assignment, arrows) to 18
+    /// (primaries). Used to decide where printed source needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Raw(_) => 0,
+            Expr::Assign { .. } | Expr::Arrow { .. } | Expr::Spread(_) => 1,
+            Expr::Conditional { .. } => 2,
+            Expr::Binary { operator, .. } => binary_precedence(operator),
+            Expr::Unary { .. } | Expr::Await(_) => 15,
+            Expr::Update { prefix: true, .. } => 15,
+            Expr::Update { prefix: false, .. } => 16,
+            Expr::Member { .. } | Expr::Index { .. } | Expr::Call { .. } | Expr::New { .. } => 17,
+            _ => 18,
+        }
+    }
+}
+
+/// Binding strength of a binary operator, in the scale of
+/// `Expr::precedence`.
+fn binary_precedence(operator: &str) -> u8 {
+    match operator {
+        "??" => 3,
+        "||" => 4,
+        "&&" => 5,
+        "|" => 6,
+        "^" => 7,
+        "&" => 8,
+        "==" | "!=" | "===" | "!==" => 9,
+        "<" | ">" | "<=" | ">=" | "instanceof" | "in" => 10,
+        "<<" | ">>" | ">>>" => 11,
+        "+" | "-" => 12,
+        "*" | "/" | "%" => 13,
+        "**" => 14,
+        _ => 0,
+    }
+}
+
+/// Writes `expr`, parenthesized when it binds less tightly than `min`.
+fn write_operand(f: &mut std::fmt::Formatter<'_>, expr: &Expr, min: u8, indent: usize) -> std::fmt::Result {
+    if expr.precedence() < min {
+        write!(f, "({})", Indented(expr, indent))
+    } else {
+        expr.write_js(f, indent)
+    }
+}
+
+fn write_list(f: &mut std::fmt::Formatter<'_>, items: &[Expr], indent: usize) -> std::fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write_operand(f, item, 1, indent)?;
+    }
+    Ok(())
+}
+
+/// Prints the expression as JavaScript.
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_js(f, 0)
+    }
+}
+
+/// An expression printed inside statements nested `indent` levels deep,
+/// which its function bodies are indented from.
+struct Indented<'a>(&'a Expr, usize);
+
+impl std::fmt::Display for Indented<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.write_js(f, self.1)
+    }
+}
+
+impl Expr {
+    fn write_js(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+        match self {
+            Expr::Identifier(name) | Expr::Regex(name) | Expr::Raw(name) => write!(f, "{}", name),
+            Expr::Literal(value) => write!(f, "{}", value.to_js()),
+            Expr::Template { quasis, expressions } => {
+                write!(f, "`")?;
+                for (index, quasi) in quasis.iter().enumerate() {
+                    write!(f, "{}", quasi)?;
+                    if let Some(expression) = expressions.get(index) {
+                        write!(f, "${{{}}}", Indented(expression, indent))?;
+                    }
+                }
+                write!(f, "`")
+            }
+            Expr::Array(items) => {
+                write!(f, "[")?;
+                write_list(f, items, indent)?;
+                write!(f, "]")
+            }
+            Expr::Object(properties) => {
+                if properties.is_empty() {
+                    return write!(f, "{{}}");
+                }
+                write!(f, "{{ ")?;
+                for (index, property) in properties.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match property {
+                        Property::KeyValue { key, value } => {
+                            if is_identifier_name(key) {
+                                write!(f, "{}: ", key)?;
+                            } else {
+                                write!(f, "{}: ", quote_js_string(key))?;
+                            }
+                            write_operand(f, value, 1, indent)?;
+                        }
+                        Property::Computed { key, value } => {
+                            write!(f, "[{}]: ", Indented(key, indent))?;
+                            write_operand(f, value, 1, indent)?;
+                        }
+                        Property::Shorthand(name) => write!(f, "{}", name)?,
+                        Property::Spread(value) => {
+                            write!(f, "...")?;
+                            write_operand(f, value, 2, indent)?;
+                        }
+                        Property::Accessor { kind, key, parameters, body } => {
+                            let key = if is_identifier_name(key) { key.clone() } else { quote_js_string(key) };
+                            write!(f, "{} {}({}) {}", kind, key, parameters_source(parameters), block_js(body, indent))?;
+                        }
+                    }
+                }
+                write!(f, " }}")
+            }
+            Expr::Member { object, property, optional } => {
+                write_operand(f, object, 17, indent)?;
+                write!(f, "{}{}", if *optional { "?." } else { "." }, property)
+            }
+            Expr::Index { object, index, optional } => {
+                write_operand(f, object, 17, indent)?;
+                write!(f, "{}[{}]", if *optional { "?." } else { "" }, Indented(index, indent))
+            }
+            Expr::Call { callee, arguments, optional } => {
+                write_operand(f, callee, 17, indent)?;
+                write!(f, "{}(", if *optional { "?." } else { "" })?;
+                write_list(f, arguments, indent)?;
+                write!(f, ")")
+            }
+            Expr::New { callee, arguments } => {
+                write!(f, "new ")?;
+                // `new (factory())()` keeps the call out of the callee
+                match **callee {
+                    Expr::Call { .. } => write!(f, "({})", Indented(callee, indent))?,
+                    _ => write_operand(f, callee, 17, indent)?,
+                }
+                write!(f, "(")?;
+                write_list(f, arguments, indent)?;
+                write!(f, ")")
+            }
+            Expr::Unary { operator, operand } => {
+                let word = operator.starts_with(|c: char| c.is_ascii_alphabetic());
+                let doubled = matches!(&**operand, Expr::Unary { operator: inner, .. } | Expr::Update { operator: inner, prefix: true, .. }
+                    if inner.starts_with(operator.as_str()));
+                if word {
+                    write!(f, "{} ", operator)?;
+                } else {
+                    write!(f, "{}", operator)?;
+                }
+                if doubled {
+                    write!(f, "({})", Indented(operand, indent))
+                } else {
+                    write_operand(f, operand, 15, indent)
+                }
+            }
+            Expr::Update { operator, prefix: true, operand } => {
+                write!(f, "{}", operator)?;
+                write_operand(f, operand, 16, indent)
+            }
+            Expr::Update { operator, prefix: false, operand } => {
+                write_operand(f, operand, 17, indent)?;
+                write!(f, "{}", operator)
+            }
+            Expr::Binary { operator, left, right } => {
+                let precedence = binary_precedence(operator);
+                // `**` groups right, and its left side can't be a bare unary
+                let (left_min, right_min) = if operator == "**" {
+                    (16, precedence)
+                } else {
+                    (precedence, precedence + 1)
+                };
+                // `??` can't be mixed with `||` or `&&` without parentheses
+                let mixes = |side: &Expr| matches!(side, Expr::Binary { operator: inner, .. }
+                    if (operator == "??") != (inner == "??") && ["??", "||", "&&"].contains(&inner.as_str())
+                        && ["??", "||", "&&"].contains(&operator.as_str()));
+                if mixes(left) {
+                    write!(f, "({})", Indented(left, indent))?;
+                } else {
+                    write_operand(f, left, left_min, indent)?;
+                }
+                write!(f, " {} ", operator)?;
+                if mixes(right) {
+                    write!(f, "({})", Indented(right, indent))
+                } else {
+                    write_operand(f, right, right_min, indent)
+                }
+            }
+            Expr::Conditional { test, consequent, alternate } => {
+                write_operand(f, test, 3, indent)?;
+                write!(f, " ? ")?;
+                write_operand(f, consequent, 1, indent)?;
+                write!(f, " : ")?;
+                write_operand(f, alternate, 1, indent)
+            }
+            Expr::Assign { operator, target, value } => {
+                write_operand(f, target, 16, indent)?;
+                write!(f, " {} ", operator)?;
+                write_operand(f, value, 1, indent)
+            }
+            Expr::Arrow { parameters, body, is_async } => {
+                if *is_async {
+                    write!(f, "async ")?;
+                }
+                write!(f, "({}) => ", parameters_source(parameters))?;
+                match body {
+                    ArrowBody::Expression(expression) => match **expression {
+                        // An object body would read as a block
+                        Expr::Object(_) => write!(f, "({})", Indented(expression, indent)),
+                        _ => write_operand(f, expression, 1, indent),
+                    },
+                    ArrowBody::Block(body) => write!(f, "{}", block_js(body, indent)),
+                }
+            }
+            Expr::Function { name, parameters, body, is_async } => {
+                if *is_async {
+                    write!(f, "async ")?;
+                }
+                write!(f, "function")?;
+                if let Some(name) = name {
+                    write!(f, " {}", name)?;
+                }
+                write!(f, "({}) ", parameters_source(parameters))?;
+                write!(f, "{}", block_js(body, indent))
+            }
+            Expr::Await(operand) => {
+                write!(f, "await ")?;
+                write_operand(f, operand, 15, indent)
+            }
+            Expr::Spread(operand) => {
+                write!(f, "...")?;
+                write_operand(f, operand, 2, indent)
+            }
+            Expr::Markup(node) => write!(f, "{}", markup_source(node)),
+        }
+    }
+}
+
+/// A parameter list as JavaScript, type annotations dropped.
+fn parameters_source(parameters: &[ASTNode]) -> String {
+    parameters.iter()
+        .filter_map(|parameter| match parameter {
+            ASTNode::Parameter { name, default: Some(default), .. } => Some(format!("{} = {}", name, default)),
+            ASTNode::Parameter { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Stmt {
+    /// The statement as JavaScript lines indented `indent` levels.
+    pub fn to_js(&self, indent: usize) -> String {
+        let pad = "  ".repeat(indent);
+        match self {
+            Stmt::Variable { .. } => format!("{}{};\n", pad, self.head(indent)),
+            Stmt::Function { name, parameters, body, is_async } => format!(
+                "{}{}function {}({}) {}\n",
+                pad,
+                if *is_async { "async " } else { "" },
+                name,
+                parameters_source(parameters),
+                block_js(body, indent)
+            ),
+            Stmt::Expression(_) => format!("{}{};\n", pad, self.head(indent)),
+            Stmt::If { test, consequent, alternate } => {
+                let mut js = format!("{}if ({}) {}", pad, Indented(test, indent), branch_js(consequent, indent));
+                match alternate {
+                    Some(alternate) if matches!(**alternate, Stmt::If { .. }) => {
+                        js.push_str(" else ");
+                        js.push_str(alternate.to_js(indent).trim_start());
+                    }
+                    Some(alternate) => js.push_str(&format!(" else {}\n", branch_js(alternate, indent))),
+                    None => js.push('\n'),
+                }
+                js
+            }
+            Stmt::For { init, test, update, body } => {
+                let init = init.as_ref().map(|init| init.head(indent)).unwrap_or_default();
+                let test = test.as_ref().map(|test| format!(" {}", Indented(test, indent))).unwrap_or_default();
+                let update = update.as_ref().map(|update| format!(" {}", Indented(update, indent))).unwrap_or_default();
+                format!("{}for ({};{};{}) {}\n", pad, init, test, update, branch_js(body, indent))
+            }
+            Stmt::ForIn { kind, binding, of, object, body } => format!(
+                "{}for ({}{} {} {}) {}\n",
+                pad,
+                kind.as_ref().map(|kind| format!("{} ", kind)).unwrap_or_default(),
+                binding,
+                if *of { "of" } else { "in" },
+                Indented(object, indent),
+                branch_js(body, indent)
+            ),
+            Stmt::While { test, body } => {
+                format!("{}while ({}) {}\n", pad, Indented(test, indent), branch_js(body, indent))
+            }
+            Stmt::DoWhile { body, test } => {
+                format!("{}do {} while ({});\n", pad, branch_js(body, indent), Indented(test, indent))
+            }
+            Stmt::Switch { discriminant, cases } => {
+                let mut js = format!("{}switch ({}) {{\n", pad, Indented(discriminant, indent));
+                for case in cases {
+                    match &case.test {
+                        Some(test) => js.push_str(&format!("{}  case {}:\n", pad, Indented(test, indent + 1))),
+                        None => js.push_str(&format!("{}  default:\n", pad)),
+                    }
+                    for statement in &case.body {
+                        js.push_str(&statement.to_js(indent + 2));
+                    }
+                }
+                js.push_str(&format!("{}}}\n", pad));
+                js
+            }
+            Stmt::Return(None) => format!("{}return;\n", pad),
+            Stmt::Return(Some(argument)) => format!("{}return {};\n", pad, Indented(argument, indent)),
+            Stmt::Throw(argument) => format!("{}throw {};\n", pad, Indented(argument, indent)),
+            Stmt::Break(label) => format!("{}break{};\n", pad, label.as_ref().map(|label| format!(" {}", label)).unwrap_or_default()),
+            Stmt::Continue(label) => format!("{}continue{};\n", pad, label.as_ref().map(|label| format!(" {}", label)).unwrap_or_default()),
+            Stmt::Try { block, parameter, handler, finalizer } => {
+                let mut js = format!("{}try {}", pad, block_js(block, indent));
+                if let Some(handler) = handler {
+                    match parameter {
+                        Some(parameter) => js.push_str(&format!(" catch ({}) ", parameter)),
+                        None => js.push_str(" catch "),
+                    }
+                    js.push_str(&block_js(handler, indent));
+                }
+                if let Some(finalizer) = finalizer {
+                    js.push_str(" finally ");
+                    js.push_str(&block_js(finalizer, indent));
+                }
+                js.push('\n');
+                js
+            }
+            Stmt::Block(body) => format!("{}{}\n", pad, block_js(body, indent)),
+            Stmt::Raw(source) if source.ends_with(';') || source.ends_with('}') => format!("{}{}\n", pad, source),
+            Stmt::Raw(source) => format!("{}{};\n", pad, source),
+        }
+    }
+
+    /// The statement without its indentation and `;`, as in the first
+    /// clause of a `for` head.
+    fn head(&self, indent: usize) -> String {
+        match self {
+            Stmt::Variable { kind, declarations } => {
+                let declarations: Vec<String> = declarations.iter()
+                    .map(|(binding, init)| match init {
+                        Some(init) => format!("{} = {}", binding, Indented(init, indent)),
+                        None => binding.clone(),
+                    })
+                    .collect();
+                format!("{} {}", kind, declarations.join(", "))
+            }
+            // A statement starting with `{` or `function` would read as a
+            // block or a declaration
+            Stmt::Expression(expression @ (Expr::Object(_) | Expr::Function { .. })) => {
+                format!("({})", Indented(expression, indent))
+            }
+            Stmt::Expression(expression) => Indented(expression, indent).to_string(),
+            statement => statement.to_js(0).trim_end().trim_end_matches(';').to_string(),
+        }
+    }
+}
+
+/// Resolves the names a component's computed and watch bodies and its
+/// render's values read from the element: state variables become
+/// `this.state.name`, and props, computed values and methods `this.name`.
+/// Writing a state variable, or calling its `setName` setter, goes through
+/// `this.setState` so the element renders again. Names bound in the body
+/// shadow them.
+struct InstanceNames<'a> {
+    state: &'a [&'a str],
+    members: &'a [&'a str],
+    // Names bound locally, innermost scope last
+    scopes: Vec<Vec<String>>,
+}
+
+impl<'a> InstanceNames<'a> {
+    fn new(state: &'a [&'a str], members: &'a [&'a str]) -> Self {
+        Self { state, members, scopes: Vec::new() }
+    }
+
+    /// `statements` with the element's names resolved.
+    fn resolve_body(&mut self, statements: &[Stmt]) -> Vec<Stmt> {
+        let mut statements = statements.to_vec();
+        self.block(&mut statements);
+        statements
+    }
+
+    /// `expression` with the element's names resolved.
+    fn resolve_expr(&mut self, expression: &Expr) -> Expr {
+        let mut expression = expression.clone();
+        self.expr(&mut expression);
+        expression
+    }
+
+    fn resolve(&self, name: &str) -> Option<Expr> {
+        if self.scopes.iter().flatten().any(|bound| bound == name) {
+            return None;
+        }
+        // A prop such as `v-if` is read as `this['v-if']`
+        let access = |object: Expr| match name.contains('-') {
+            true => Expr::Index {
+                object: Box::new(object),
+                index: Box::new(Expr::Literal(Value::String(name.to_string()))),
+                optional: false,
+            },
+            false => Expr::Member { object: Box::new(object), property: name.to_string(), optional: false },
+        };
+        let this = Expr::Identifier("this".to_string());
+        if self.state.contains(&name) {
+            Some(access(Expr::Member { object: Box::new(this), property: "state".to_string(), optional: false }))
+        } else if self.members.contains(&name) {
+            Some(access(this))
+        } else {
+            None
+        }
+    }
+
+    /// Whether `name` is a state variable here, not shadowed by a local.
+    fn is_state(&self, name: &str) -> bool {
+        self.state.contains(&name) && !self.scopes.iter().flatten().any(|bound| bound == name)
+    }
+
+    /// `expression` as a `this.setState` call when it writes a state
+    /// variable: `count = 1`, `count += 1`, `count++` or `setCount(1)`.
+    fn state_write(&mut self, expression: &Expr) -> Option<Expr> {
+        let (name, value) = match expression {
+            Expr::Assign { operator, target, value } => {
+                let name = match &**target {
+                    Expr::Identifier(name) if self.is_state(name) => name.clone(),
+                    _ => return None,
+                };
+                let mut value = (**value).clone();
+                self.expr(&mut value);
+                match operator.strip_suffix('=').filter(|operator| !operator.is_empty()) {
+                    Some(operator) => {
+                        let current = self.resolve(&name)?;
+                        (name, Expr::Binary { operator: operator.to_string(), left: Box::new(current), right: Box::new(value) })
+                    }
+                    None => (name, value),
+                }
+            }
+            Expr::Update { operator, operand, .. } => {
+                let name = match &**operand {
+                    Expr::Identifier(name) if self.is_state(name) => name.clone(),
+                    _ => return None,
+                };
+                let current = self.resolve(&name)?;
+                let value = Expr::Binary {
+                    operator: operator[..1].to_string(),
+                    left: Box::new(current),
+                    right: Box::new(Expr::Literal(Value::Number(1.0))),
+                };
+                (name, value)
+            }
+            Expr::Call { callee, arguments, .. } if arguments.len() == 1 => {
+                let setter = match &**callee {
+                    Expr::Identifier(setter) if self.resolve(setter).is_none() => setter,
+                    _ => return None,
+                };
+                let mut chars = setter.strip_prefix("set")?.chars();
+                let first = chars.next().filter(char::is_ascii_uppercase)?;
+                let name = format!("{}{}", first.to_ascii_lowercase(), chars.as_str());
+                if !self.is_state(&name) || self.scopes.iter().flatten().any(|bound| bound == setter) {
+                    return None;
+                }
+                let mut value = arguments[0].clone();
+                self.expr(&mut value);
+                (name, value)
+            }
+            _ => return None,
+        };
+        let set_state = Expr::Member { object: Box::new(Expr::Identifier("this".to_string())), property: "setState".to_string(), optional: false };
+        Some(Expr::Call {
+            callee: Box::new(set_state),
+            arguments: vec![Expr::Literal(Value::String(name)), value],
+            optional: false,
+        })
+    }
+
+    /// Binds the names in `pattern`, a name or a destructuring pattern as
+    /// written, in the innermost scope.
+    fn bind(&mut self, pattern: &str) {
+        let names = pattern
+            .split(|c: char| !is_identifier_part(c))
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.extend(names);
+        }
+    }
+
+    /// Runs `resolve` in a scope of its own, with `parameters` bound.
+    fn scoped<T>(&mut self, parameters: &mut [ASTNode], resolve: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(Vec::new());
+        for parameter in parameters.iter_mut() {
+            if let ASTNode::Parameter { name, default, .. } = parameter {
+                if let Some(default) = default {
+                    self.expr(default);
+                }
+                self.bind(name);
+            }
+        }
+        let resolved = resolve(self);
+        self.scopes.pop();
+        resolved
+    }
+
+    fn block(&mut self, statements: &mut [Stmt]) {
+        self.scoped(&mut [], |names| {
+            for statement in statements {
+                names.stmt(statement);
+            }
+        });
+    }
+
+    fn stmt(&mut self, statement: &mut Stmt) {
+        match statement {
+            Stmt::Variable { declarations, .. } => {
+                for (binding, init) in declarations {
+                    if let Some(init) = init {
+                        self.expr(init);
+                    }
+                    self.bind(binding);
+                }
+            }
+            Stmt::Function { name, parameters, body, .. } => {
+                self.bind(name);
+                self.scoped(parameters, |names| names.block(body));
+            }
+            Stmt::Expression(expression) | Stmt::Throw(expression) => self.expr(expression),
+            Stmt::Return(expression) => {
+                if let Some(expression) = expression {
+                    self.expr(expression);
+                }
+            }
+            Stmt::If { test, consequent, alternate } => {
+                self.expr(test);
+                self.stmt(consequent);
+                if let Some(alternate) = alternate {
+                    self.stmt(alternate);
+                }
+            }
+            Stmt::For { init, test, update, body } => self.scoped(&mut [], |names| {
+                if let Some(init) = init {
+                    names.stmt(init);
+                }
+                for expression in [test, update].into_iter().flatten() {
+                    names.expr(expression);
+                }
+                names.stmt(body);
+            }),
+            Stmt::ForIn { binding, object, body, .. } => {
+                self.expr(object);
+                self.scoped(&mut [], |names| {
+                    names.bind(binding);
+                    names.stmt(body);
+                });
+            }
+            Stmt::While { test, body } | Stmt::DoWhile { body, test } => {
+                self.expr(test);
+                self.stmt(body);
+            }
+            Stmt::Switch { discriminant, cases } => {
+                self.expr(discriminant);
+                for case in cases {
+                    if let Some(test) = &mut case.test {
+                        self.expr(test);
+                    }
+                    self.block(&mut case.body);
+                }
+            }
+            Stmt::Try { block, parameter, handler, finalizer } => {
+                self.block(block);
+                if let Some(handler) = handler {
+                    self.scoped(&mut [], |names| {
+                        if let Some(parameter) = parameter {
+                            names.bind(parameter);
+                        }
+                        names.block(handler);
+                    });
+                }
+                if let Some(finalizer) = finalizer {
+                    self.block(finalizer);
+                }
+            }
+            Stmt::Block(body) => self.block(body),
+            Stmt::Break(_) | Stmt::Continue(_) | Stmt::Raw(_) => {}
+        }
+    }
+
+    fn expr(&mut self, expression: &mut Expr) {
+        // `{v-if}` parses as `v - if`
+        if let Some(resolved) = hyphenated_name(expression).and_then(|name| self.resolve(&name)) {
+            *expression = resolved;
+            return;
+        }
+        if let Some(write) = self.state_write(expression) {
+            *expression = write;
+            return;
+        }
+        match expression {
+            Expr::Identifier(name) => {
+                if let Some(resolved) = self.resolve(name) {
+                    *expression = resolved;
+                }
+            }
+            Expr::Template { expressions: items, .. } | Expr::Array(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            Expr::Object(properties) => {
+                for property in properties {
+                    match property {
+                        Property::KeyValue { value, .. } | Property::Spread(value) => self.expr(value),
+                        Property::Computed { key, value } => {
+                            self.expr(key);
+                            self.expr(value);
+                        }
+                        Property::Shorthand(name) => {
+                            if let Some(value) = self.resolve(name) {
+                                *property = Property::KeyValue { key: name.clone(), value };
+                            }
+                        }
+                        Property::Accessor { parameters, body, .. } => self.scoped(parameters, |names| names.block(body)),
+                    }
+                }
+            }
+            Expr::Member { object, .. } => self.expr(object),
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::Call { callee, arguments, .. } | Expr::New { callee, arguments } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            Expr::Unary { operand, .. }
+            | Expr::Update { operand, .. }
+            | Expr::Await(operand)
+            | Expr::Spread(operand) => self.expr(operand),
+            Expr::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Conditional { test, consequent, alternate } => {
+                self.expr(test);
+                self.expr(consequent);
+                self.expr(alternate);
+            }
+            Expr::Assign { target, value, .. } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            Expr::Arrow { parameters, body, .. } => self.scoped(parameters, |names| match body {
+                ArrowBody::Expression(expression) => names.expr(expression),
+                ArrowBody::Block(body) => names.block(body),
+            }),
+            Expr::Function { name, parameters, body, .. } => self.scoped(parameters, |names| {
+                if let Some(name) = name {
+                    names.bind(name);
+                }
+                names.block(body);
+            }),
+            Expr::Markup(node) => self.markup(node),
+            Expr::Literal(_) | Expr::Regex(_) | Expr::Raw(_) => {}
+        }
+    }
+
+    /// Resolves the values markup interpolates, in its attributes and
+    /// children.
+    fn markup(&mut self, node: &mut ASTNode) {
+        match node {
+            ASTNode::Element { attributes, children, .. } => {
+                for attribute in attributes {
+                    match &mut attribute.value {
+                        AttributeValue::Expression(expression) => self.expr(expression),
+                        AttributeValue::Interpolated(parts) => {
+                            for part in parts {
+                                self.markup(part);
+                            }
+                        }
+                        AttributeValue::Boolean | AttributeValue::Static(_) => {}
+                    }
+                }
+                for child in children {
+                    self.markup(child);
+                }
+            }
+            ASTNode::Expression { expression, .. } | ASTNode::Interpolation { expression, .. } => self.expr(expression),
+            _ => {}
+        }
+    }
+}
+
+/// Whether `expression` evaluates to markup, as `cond ? <a /> : <b />`,
+/// `cond && <a />` or `items.map(item => <li />)` do.
+fn contains_markup(expression: &Expr) -> bool {
+    match expression {
+        Expr::Markup(_) => true,
+        Expr::Conditional { consequent, alternate, .. } => contains_markup(consequent) || contains_markup(alternate),
+        Expr::Binary { operator, right, .. } => operator == "&&" && contains_markup(right),
+        Expr::Call { arguments, .. } => arguments.iter().any(|argument| match argument {
+            Expr::Arrow { body: ArrowBody::Expression(body), .. } => contains_markup(body),
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
+/// The name `a-b-c` when `expression` is `a - b - c`, identifiers only.
+fn hyphenated_name(expression: &Expr) -> Option<String> {
+    match expression {
+        Expr::Binary { operator, left, right } if operator == "-" => {
+            let first = match &**left {
+                Expr::Identifier(first) => first.clone(),
+                left => hyphenated_name(left)?,
+            };
+            match &**right {
+                Expr::Identifier(last) => Some(format!("{}-{}", first, last)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Where rendered markup goes: a page's HTML, or the template literal a
+/// component's `getTemplate` returns.
+enum MarkupTarget<'n, 'a> {
+    Page,
+    Template(&'n mut InstanceNames<'a>),
+}
+
+impl MarkupTarget<'_, '_> {
+    /// `html` as it must be written to the target.
+    fn literal(&self, html: &str) -> String {
+        match self {
+            MarkupTarget::Page => html.to_string(),
+            MarkupTarget::Template(_) => template_text(html),
+        }
+    }
+}
+
+/// The statements of a component's `mounted` or `unmounted` blocks, for
+/// the body of the matching custom element callback, reading the element's
+/// names through `instance`.
+fn lifecycle_js(lifecycle: &[ASTNode], hook: LifecycleHook, instance: &mut InstanceNames) -> String {
+    let mut js = String::new();
+    for node in lifecycle {
+        if let ASTNode::Lifecycle { hook: block_hook, body, .. } = node {
+            if *block_hook == hook {
+                for statement in &instance.resolve_body(body) {
+                    js.push_str(&statement.to_js(2));
+                }
+            }
+        }
+    }
+    js
+}
+
+/// `{ statements }`, one per line, with the `}` at `indent`.
+fn block_js(statements: &[Stmt], indent: usize) -> String {
+    if statements.is_empty() {
+        return "{}".to_string();
+    }
+    let mut js = String::from("{\n");
+    for statement in statements {
+        js.push_str(&statement.to_js(indent + 1));
+    }
+    js.push_str(&"  ".repeat(indent));
+    js.push('}');
+    js
+}
+
+/// The body of an `if`, `for` or `while`, braced even when written as a
+/// single statement.
+fn branch_js(statement: &Stmt, indent: usize) -> String {
+    match statement {
+        Stmt::Block(body) => block_js(body, indent),
+        statement => block_js(std::slice::from_ref(statement), indent),
+    }
+}
+
+/// A JavaScript string literal for `text`, single-quoted.
+fn quote_js_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('\'');
+    for c in text.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '\'' => quoted.push_str("\\'"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// `text` as a double-quoted string literal type. The `/` of a `*/` is
+/// escaped as well, so the type can sit in a JSDoc comment.
+fn quote_type_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    let mut previous = None;
+    for c in text.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '/' if previous == Some('*') => quoted.push_str("\\/"),
+            c => quoted.push(c),
+        }
+        previous = Some(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The text a string literal stands for, escape sequences resolved.
+/// `raw` is the literal without its quotes.
+fn unescape_string(raw: &str) -> String {
+    if !raw.contains('\\') {
+        return raw.to_string();
+    }
+
+    let mut text = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            Some('t') => text.push('\t'),
+            Some('0') => text.push('\0'),
+            Some('\n') => {} // A line continuation
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// Whether `name` can be written as a bare object key or after `.`.
+fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_identifier_start)
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Whether a token is a name: an identifier or a keyword, which are names
+/// too after `.` and in object keys.
+fn is_word(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Identifier
+            | TokenType::Page | TokenType::Component | TokenType::Style | TokenType::Event | TokenType::State
+            | TokenType::Api | TokenType::Router | TokenType::Route | TokenType::Layout | TokenType::Animation
+            | TokenType::Type | TokenType::Hook | TokenType::Plugin | TokenType::Config | TokenType::Import
+            | TokenType::Export | TokenType::From | TokenType::As | TokenType::If | TokenType::Else
+            | TokenType::For | TokenType::While | TokenType::Function | TokenType::Async | TokenType::Await
+            | TokenType::Return | TokenType::Const | TokenType::Let | TokenType::Var | TokenType::True
+            | TokenType::False | TokenType::Null | TokenType::Undefined | TokenType::Class | TokenType::Extends
+            | TokenType::Implements | TokenType::Interface | TokenType::Enum | TokenType::Namespace
+            | TokenType::Render | TokenType::Computed | TokenType::Watch | TokenType::Mounted | TokenType::Unmounted
+    )
+}
+
+/// The binary operator a token stands for, if any.
+fn binary_operator(token: &Token) -> Option<&'static str> {
+    let operator = match token.token_type {
+        TokenType::Nullish => "??",
+        TokenType::Or => "||",
+        TokenType::And => "&&",
+        TokenType::Pipe => "|",
+        TokenType::Caret => "^",
+        TokenType::Ampersand => "&",
+        TokenType::Equal => "==",
+        TokenType::NotEqual => "!=",
+        TokenType::StrictEqual => "===",
+        TokenType::StrictNotEqual => "!==",
+        TokenType::Less => "<",
+        TokenType::Greater => ">",
+        TokenType::LessEqual => "<=",
+        TokenType::GreaterEqual => ">=",
+        TokenType::Plus => "+",
+        TokenType::Minus => "-",
+        TokenType::Multiply => "*",
+        TokenType::Divide => "/",
+        TokenType::Modulo => "%",
+        TokenType::Power => "**",
+        TokenType::ShiftLeft => "<<",
+        TokenType::Identifier if token.value == "instanceof" => "instanceof",
+        TokenType::Identifier if token.value == "in" => "in",
+        _ => return None,
+    };
+    Some(operator)
+}
+
+/// Source form of markup inside an expression, as in
+/// `todos.map(todo => <TodoItem todo={todo} />)`.
+fn markup_source(node: &ASTNode) -> String {
+    match node {
+        ASTNode::Element { tag_name, attributes, children, self_closing, .. } => {
+            let mut source = format!("<{}", tag_name);
+            for attribute in attributes {
+                match &attribute.value {
+                    AttributeValue::Boolean => source.push_str(&format!(" {}", attribute.name)),
+                    AttributeValue::Expression(expression) => {
+                        source.push_str(&format!(" {}={{{}}}", attribute.name, expression))
+                    }
+                    value => source.push_str(&format!(" {}=\"{}\"", attribute.name, interpolated_source(value))),
+                }
+            }
+            if *self_closing {
+                source.push_str(" />");
+                return source;
+            }
+            source.push('>');
+            for child in children {
+                source.push_str(&markup_source(child));
+            }
+            source.push_str(&format!("</{}>", tag_name));
+            source
+        }
+        ASTNode::Text { value, .. } => value.clone(),
+        ASTNode::Interpolation { expression, .. } => format!("[[{}]]", expression),
+        ASTNode::Expression { expression, .. } => format!("{{{}}}", expression),
+        _ => String::new(),
+    }
+}
+
+// ===================================
+// CONCRETE SYNTAX TREE
+// ===================================
+
+/// The kind of a syntax tree node. Statement kinds mirror the `ASTNode`
+/// variant built from the same tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyntaxKind {
+    Root,
+    Page, Component, Style, Event, State, Api, Router, Layout, Animation,
+    Type, Hook, Plugin, Config, Import, Export,
+    Element, Attribute, Interpolation, Expression,
+    Prop, EventDeclaration, StateVariable, Method, Parameter, Render,
+    Rule, Declaration, AtRule, Keyframe, Route, Interface, Enum,
+    Computed, Watch, Lifecycle, Variable, Script,
+    /// Tokens skipped while recovering from a parse error.
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyntaxElement<'src> {
+    Node(#[serde(borrow)] SyntaxNode<'src>),
+    Token(#[serde(borrow)] Token<'src>),
+}
+
+/// A lossless syntax tree node: every token of the input, trivia included,
+/// belongs to exactly one node, so the tree prints back to the source byte
+/// for byte. Trivia in front of a node belongs to the enclosing node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntaxNode<'src> {
+    pub kind: SyntaxKind,
+    #[serde(borrow)]
+    pub children: Vec<SyntaxElement<'src>>,
+}
+
+impl<'src> SyntaxNode<'src> {
+    pub fn new(kind: SyntaxKind) -> Self {
+        Self {
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    /// The tokens under this node in source order.
+    pub fn tokens(&self) -> Vec<&Token<'src>> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a Token<'src>>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode<'src>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// The source text of this node, rebuilt from its tokens. For the root
+    /// this is the whole input.
+    pub fn text(&self, source: &str) -> String {
+        self.tokens()
+            .iter()
+            .map(|token| &source[token.span.start.offset..token.span.end.offset])
+            .collect()
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        let tokens = self.tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Some(first.span.to(&last.span)),
+            _ => None,
+        }
+    }
+}
+
+/// An owned copy of a `SyntaxNode`, for holding on to the tree once the
+/// source it borrows from is gone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntaxTree {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxTreeElement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyntaxTreeElement {
+    Node(SyntaxTree),
+    /// A token with its source text, quotes included.
+    Token { token_type: TokenType, text: String, span: Span },
+}
+
+impl SyntaxTree {
+    pub fn new(node: &SyntaxNode, source: &str) -> Self {
+        let children = node
+            .children
+            .iter()
+            .map(|child| match child {
+                SyntaxElement::Node(node) => SyntaxTreeElement::Node(SyntaxTree::new(node, source)),
+                SyntaxElement::Token(token) => SyntaxTreeElement::Token {
+                    token_type: token.token_type.clone(),
+                    text: source[token.span.start.offset..token.span.end.offset].to_string(),
+                    span: token.span.clone(),
+                },
+            })
+            .collect();
+        Self { kind: node.kind, children }
+    }
+
+    /// The source text under this node. For the root this is the whole
+    /// input.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        text
+    }
+
+    fn push_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                SyntaxTreeElement::Node(node) => node.push_text(text),
+                SyntaxTreeElement::Token { text: token, .. } => text.push_str(token),
+            }
+        }
+    }
+}
+
+/// What the parser records while it runs. Nodes are opened and closed at
+/// indices into its significant (non-trivia) tokens; trivia is placed when
+/// the tree is built.
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    Open(SyntaxKind, usize),
+    Close(usize),
+}
+
+/// A point to open a node at once the parser knows what it parsed.
+#[derive(Debug, Clone, Copy)]
+struct Checkpoint {
+    event: usize,
+    token: usize,
+}
+
+// ===================================
+// LEXER
+// ===================================
+
+/// The lexer switches rules between the regions of an .ema file: `<` is a
+/// tag in markup but a comparison after an operand, `80px 40px` is one CSS
+/// value rather than numbers and identifiers, and `&copy; 2024` is text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LexMode {
+    /// Declarations, statements and `{expression}` holes.
+    Script,
+    /// Element content between an opening and a closing tag.
+    Markup,
+    /// Inside `<name ...>` or `</name>`.
+    Tag,
+    /// `style` and `animation` blocks and top-level at-rules.
+    Style,
+    /// The text of a template literal, outside its `${...}` holes.
+    Template,
+}
+
+#[derive(Debug, Clone)]
+struct ModeFrame<'src> {
+    mode: LexMode,
+    // `{` opened and not yet closed inside this frame
+    depth: usize,
+    // Tag: the tag name once read; Markup: the element it is the content of
+    tag_name: Option<&'src str>,
+    // Tag: whether this is a `</name>` closing tag
+    closing: bool,
+    // Template: where the opening backtick is, for unterminated literals
+    opened_at: Position,
+    // Script: whether this is a `[[...]]` hole, closed by `]]`
+    interpolation: bool,
+    // Script: `[` opened and not yet closed, so `a[b[0]]` is not a `]]`
+    brackets: usize,
+}
+
+impl ModeFrame<'_> {
+    fn new(mode: LexMode) -> Self {
+        Self {
+            mode,
+            depth: 0,
+            tag_name: None,
+            closing: false,
+            opened_at: Position::default(),
+            interpolation: false,
+            brackets: 0,
+        }
+    }
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Elements laid out within a line of text, so the whitespace around them
+/// shows.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "button", "cite", "code", "data", "dfn", "em", "i", "img", "input",
+    "kbd", "label", "mark", "q", "s", "samp", "select", "small", "span", "strong", "sub", "sup", "textarea",
+    "time", "u", "var",
+];
+
+/// Tags whose `>` is not followed by content: HTML void elements and the
+/// `<component Name>` declaration header.
+fn is_void_tag(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name == "component" || VOID_ELEMENTS.contains(&name.as_str())
+}
+
+/// Lexes borrowed source text. Besides `tokenize`, a `Lexer` is an
+/// iterator yielding tokens one at a time, ending with `Eof`; tokens borrow
+/// their text from the input, so lexing does not allocate per token.
+pub struct Lexer<'src> {
+    input: &'src str,
+    file: Arc<str>,
+    position: usize,
+    line: usize,
+    column: usize,
+    keywords: HashMap<&'static str, TokenType>,
+    diagnostics: Vec<Diagnostic>,
+    modes: Vec<ModeFrame<'src>>,
+    last_significant: Option<TokenType>,
+    /// The significant token before `last_significant`.
+    before_last: Option<TokenType>,
+    skip_trivia: bool,
+    finished: bool,
+}
+
+impl<'src> Lexer<'src> {
+    pub fn new(input: &'src str, file: &str) -> Self {
+        let mut keywords = HashMap::new();
+        keywords.insert("page", TokenType::Page);
+        keywords.insert("component", TokenType::Component);
+        keywords.insert("style", TokenType::Style);
+        keywords.insert("event", TokenType::Event);
+        keywords.insert("state", TokenType::State);
+        keywords.insert("api", TokenType::Api);
+        keywords.insert("router", TokenType::Router);
+        keywords.insert("route", TokenType::Route);
+        keywords.insert("layout", TokenType::Layout);
+        keywords.insert("animation", TokenType::Animation);
+        keywords.insert("type", TokenType::Type);
+        keywords.insert("hook", TokenType::Hook);
+        keywords.insert("plugin", TokenType::Plugin);
+        keywords.insert("config", TokenType::Config);
+        keywords.insert("import", TokenType::Import);
+        keywords.insert("export", TokenType::Export);
+        keywords.insert("from", TokenType::From);
+        keywords.insert("as", TokenType::As);
+        keywords.insert("if", TokenType::If);
+        keywords.insert("else", TokenType::Else);
+        keywords.insert("for", TokenType::For);
+        keywords.insert("while", TokenType::While);
+        keywords.insert("function", TokenType::Function);
+        keywords.insert("async", TokenType::Async);
+        keywords.insert("await", TokenType::Await);
+        keywords.insert("return", TokenType::Return);
+        keywords.insert("const", TokenType::Const);
+        keywords.insert("let", TokenType::Let);
+        keywords.insert("var", TokenType::Var);
+        keywords.insert("true", TokenType::True);
+        keywords.insert("false", TokenType::False);
+        keywords.insert("null", TokenType::Null);
+        keywords.insert("undefined", TokenType::Undefined);
+        keywords.insert("class", TokenType::Class);
+        keywords.insert("extends", TokenType::Extends);
+        keywords.insert("implements", TokenType::Implements);
+        keywords.insert("interface", TokenType::Interface);
+        keywords.insert("enum", TokenType::Enum);
+        keywords.insert("namespace", TokenType::Namespace);
+        keywords.insert("render", TokenType::Render);
+        keywords.insert("computed", TokenType::Computed);
+        keywords.insert("watch", TokenType::Watch);
+        keywords.insert("mounted", TokenType::Mounted);
+        keywords.insert("unmounted", TokenType::Unmounted);
+
+        Self {
+            input,
+            file: Arc::from(file),
+            position: 0,
+            line: 1,
+            column: 1,
+            keywords,
+            diagnostics: Vec::new(),
+            modes: vec![ModeFrame::new(LexMode::Script)],
+            last_significant: None,
+            before_last: None,
+            skip_trivia: false,
+            finished: false,
+        }
+    }
+
+    /// Leaves whitespace, newlines and comments out of the token stream.
+    pub fn set_skip_trivia(&mut self, value: bool) {
+        self.skip_trivia = value;
+    }
+
+    /// Tokenizes the whole input. Lexing recovers from malformed input, so
+    /// every problem is available from `diagnostics()`; the first one is
+    /// also returned as the error.
+    pub fn tokenize(&mut self) -> Result<Vec<Token<'src>>, Diagnostic> {
+        let tokens: Vec<Token<'src>> = self.by_ref().collect();
+
+        match self.diagnostics.first() {
+            Some(diagnostic) => Err(diagnostic.clone()),
+            None => Ok(tokens),
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token<'src>> {
+        if self.finished {
+            return None;
+        }
+
+        if self.peek_char().is_none() {
+            self.finished = true;
+            let end = self.current_position();
+            let unterminated: Vec<Position> = self.modes.iter()
+                .filter(|frame| frame.mode == LexMode::Template)
+                .map(|frame| frame.opened_at)
+                .collect();
+            for start in unterminated {
+                let span = Span::new(self.file.clone(), start, end);
+                self.error(codes::UNTERMINATED_TEMPLATE, "unterminated template literal".to_string(), &span);
+            }
+            return Some(self.make_token(TokenType::Eof, "", end));
+        }
+
+        let token = match self.mode() {
+            LexMode::Script => self.lex_script(),
+            LexMode::Markup => self.lex_markup(),
+            LexMode::Tag => self.lex_tag(),
+            LexMode::Style => self.lex_style(),
+            LexMode::Template => self.lex_template(),
+        };
+        if !token.is_trivia() {
+            self.before_last = self.last_significant.replace(token.token_type.clone());
+        }
+        Some(token)
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn error(&mut self, code: &str, message: String, span: &Span) {
+        self.diagnostics.push(Diagnostic::new(code, message, span.clone()));
+    }
+
+    // ---- mode stack ----
+
+    fn mode(&self) -> LexMode {
+        self.modes.last().map(|frame| frame.mode).unwrap_or(LexMode::Script)
+    }
+
+    fn frame_mut(&mut self) -> &mut ModeFrame<'src> {
+        if self.modes.is_empty() {
+            self.modes.push(ModeFrame::new(LexMode::Script));
+        }
+        self.modes.last_mut().unwrap()
+    }
+
+    fn push_mode(&mut self, mode: LexMode) {
+        self.modes.push(ModeFrame::new(mode));
+    }
+
+    /// Leaves the current mode. The root script frame is never popped, so
+    /// stray closers at the top level cannot empty the stack.
+    fn pop_mode(&mut self) -> Option<ModeFrame<'src>> {
+        if self.modes.len() > 1 {
+            self.modes.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Whether the previous token ended a statement or block, i.e. a
+    /// declaration keyword here starts a new declaration.
+    fn at_statement_start(&self) -> bool {
+        matches!(
+            self.last_significant,
+            None | Some(TokenType::Semicolon)
+                | Some(TokenType::LeftBrace)
+                | Some(TokenType::RightBrace)
+                | Some(TokenType::TagEnd)
+                | Some(TokenType::SelfClosingTagEnd)
+        )
+    }
+
+    /// Whether the `style` or `animation` just read declares CSS: a selector,
+    /// at-rule or `{` follows. Followed by `.`, `=`, `(`, `:` or `,` it is
+    /// a name in script (`style.textContent = ...`, `{ animation: ... }`).
+    fn at_style_block(&self) -> bool {
+        let rest = &self.input[self.position..];
+        let after = rest.trim_start();
+        let spaced = after.len() < rest.len();
+        let mut chars = after.chars();
+        match chars.next() {
+            Some('{' | '@' | '#') => true,
+            // `style .card`, `style [hidden]`, `style *` but not `style[key]`
+            Some('.') => spaced && chars.next().is_some_and(is_css_name_part),
+            Some('[' | '*') => spaced,
+            Some(c) => spaced && is_name_start(c),
+            None => false,
+        }
+    }
+
+    /// Whether a `<` here opens a tag rather than comparing. After an operand
+    /// (`a < b`, `Button<T>`) it is an operator.
+    fn tag_allowed(&self) -> bool {
+        !self.follows_operand()
+    }
+
+    /// Whether the previous token ends an operand, so an operator follows:
+    /// `/` divides and `<` compares. Anywhere else an operand starts, and
+    /// they open a regular expression or a tag. A keyword after `.` is a
+    /// property name (`this.state < 3`).
+    fn follows_operand(&self) -> bool {
+        if matches!(self.before_last, Some(TokenType::Dot) | Some(TokenType::OptionalChain))
+            && self.last_significant.as_ref().is_some_and(is_word)
+        {
+            return true;
+        }
+        matches!(
+            self.last_significant,
+            Some(TokenType::Identifier)
+                | Some(TokenType::Number)
+                | Some(TokenType::BigInt)
+                | Some(TokenType::String)
+                | Some(TokenType::TemplateEnd)
+                | Some(TokenType::RightParen)
+                | Some(TokenType::RightBracket)
+                | Some(TokenType::True)
+                | Some(TokenType::False)
+                | Some(TokenType::Null)
+                | Some(TokenType::Undefined)
+                | Some(TokenType::Regex)
+        )
+    }
+
+    // ---- script mode ----
+
+    fn lex_script(&mut self) -> Token<'src> {
+        let current = self.peek_char().unwrap_or('\0');
+        if let Some(token) = self.lex_trivia(current, true) {
+            return token;
+        }
+
+        // Strings
+        if current == '"' || current == '\'' {
+            return self.read_string(false);
+        }
+
+        // Template literals
+        if current == '`' {
+            let token = self.punct(TokenType::TemplateStart, 1);
+            let mut frame = ModeFrame::new(LexMode::Template);
+            frame.opened_at = token.span.start;
+            self.modes.push(frame);
+            return token;
+        }
+
+        // Numbers
+        let next = self.peek_next_char();
+        if current.is_ascii_digit() || (current == '.' && next.is_some_and(|c| c.is_ascii_digit())) {
+            return self.read_number();
+        }
+
+        // Identifiers and keywords; `style` and `animation` declarations
+        // switch to CSS rules until their block closes
+        if is_identifier_start(current) {
+            let statement_start = self.at_statement_start();
+            let token = self.read_identifier();
+            if statement_start
+                && matches!(token.token_type, TokenType::Style | TokenType::Animation)
+                && self.at_style_block()
+            {
+                self.push_mode(LexMode::Style);
+            }
+            return token;
+        }
+
+        // Top-level at-rules (`@media`, `@keyframes`) are CSS as well
+        if current == '@' && self.at_statement_start() {
+            self.push_mode(LexMode::Style);
+            return self.lex_style();
+        }
+
+        // Operators and punctuation
+        match current {
+            '=' => match next {
+                Some('=') if self.peek_third_char() == Some('=') => self.punct(TokenType::StrictEqual, 3),
+                Some('=') => self.punct(TokenType::Equal, 2),
+                Some('>') => self.punct(TokenType::Arrow, 2),
+                _ => self.punct(TokenType::Assign, 1),
+            },
+            '!' => match next {
+                Some('=') if self.peek_third_char() == Some('=') => self.punct(TokenType::StrictNotEqual, 3),
+                Some('=') => self.punct(TokenType::NotEqual, 2),
+                _ => self.punct(TokenType::Not, 1),
+            },
+            '<' => match next {
+                Some('=') => self.punct(TokenType::LessEqual, 2),
+                Some('<') if self.peek_third_char() == Some('=') => self.punct(TokenType::ShiftLeftAssign, 3),
+                Some('<') => self.punct(TokenType::ShiftLeft, 2),
+                Some(c) if self.tag_allowed() && (is_name_start(c) || c == '>' || self.input[self.position..].starts_with("<[[")) => {
+                    let token = self.punct(TokenType::OpeningTagStart, 1);
+                    self.push_mode(LexMode::Tag);
+                    token
+                }
+                _ => self.punct(TokenType::Less, 1),
+            },
+            '>' => match next {
+                Some('=') => self.punct(TokenType::GreaterEqual, 2),
+                _ => self.punct(TokenType::Greater, 1),
+            },
+            '+' => match next {
+                Some('+') => self.punct(TokenType::Increment, 2),
+                Some('=') => self.punct(TokenType::PlusAssign, 2),
+                _ => self.punct(TokenType::Plus, 1),
+            },
+            '-' => match next {
+                Some('-') => self.punct(TokenType::Decrement, 2),
+                Some('=') => self.punct(TokenType::MinusAssign, 2),
+                _ => self.punct(TokenType::Minus, 1),
+            },
+            '*' => match next {
+                Some('*') if self.peek_third_char() == Some('=') => self.punct(TokenType::PowerAssign, 3),
+                Some('*') => self.punct(TokenType::Power, 2),
+                Some('=') => self.punct(TokenType::MultiplyAssign, 2),
+                _ => self.punct(TokenType::Multiply, 1),
+            },
+            '/' if !self.follows_operand() => self.read_regex(),
+            '/' => match next {
+                Some('=') => self.punct(TokenType::DivideAssign, 2),
+                _ => self.punct(TokenType::Divide, 1),
+            },
+            '%' => match next {
+                Some('=') => self.punct(TokenType::ModuloAssign, 2),
+                _ => self.punct(TokenType::Modulo, 1),
+            },
+            '&' => match next {
+                Some('&') if self.peek_third_char() == Some('=') => self.punct(TokenType::AndAssign, 3),
+                Some('&') => self.punct(TokenType::And, 2),
+                Some('=') => self.punct(TokenType::AmpersandAssign, 2),
+                _ => self.punct(TokenType::Ampersand, 1),
+            },
+            '|' => match next {
+                Some('|') if self.peek_third_char() == Some('=') => self.punct(TokenType::OrAssign, 3),
+                Some('|') => self.punct(TokenType::Or, 2),
+                Some('=') => self.punct(TokenType::PipeAssign, 2),
+                _ => self.punct(TokenType::Pipe, 1),
+            },
+            '(' => self.punct(TokenType::LeftParen, 1),
+            ')' => self.punct(TokenType::RightParen, 1),
+            '{' => {
+                self.frame_mut().depth += 1;
+                self.punct(TokenType::LeftBrace, 1)
+            }
+            '}' => {
+                if self.frame_mut().depth > 0 {
+                    self.frame_mut().depth -= 1;
+                    return self.punct(TokenType::RightBrace, 1);
+                }
+
+                // The `}` closing an expression hole returns to the markup,
+                // tag or template literal that opened it
+                let closes_template = self.modes.len() > 1
+                    && self.modes[self.modes.len() - 2].mode == LexMode::Template;
+                let token_type = if closes_template { TokenType::TemplateExprEnd } else { TokenType::RightBrace };
+                let token = self.punct(token_type, 1);
+                self.pop_mode();
+                token
+            }
+            '[' => {
+                self.frame_mut().brackets += 1;
+                self.punct(TokenType::LeftBracket, 1)
+            }
+            ']' => {
+                let frame = self.frame_mut();
+                if frame.interpolation && frame.brackets == 0 && next == Some(']') {
+                    let token = self.punct(TokenType::InterpolationEnd, 2);
+                    self.pop_mode();
+                    return token;
+                }
+                frame.brackets = frame.brackets.saturating_sub(1);
+                self.punct(TokenType::RightBracket, 1)
+            }
+            ';' => self.punct(TokenType::Semicolon, 1),
+            ',' => self.punct(TokenType::Comma, 1),
+            '.' if next == Some('.') && self.peek_third_char() == Some('.') => self.punct(TokenType::Spread, 3),
+            '.' => self.punct(TokenType::Dot, 1),
+            ':' => self.punct(TokenType::Colon, 1),
+            '?' => match next {
+                Some('?') if self.peek_third_char() == Some('=') => self.punct(TokenType::NullishAssign, 3),
+                Some('?') => self.punct(TokenType::Nullish, 2),
+                // `a?.5:b` is a ternary, not optional chaining
+                Some('.') if !self.peek_third_char().is_some_and(|c| c.is_ascii_digit()) => {
+                    self.punct(TokenType::OptionalChain, 2)
+                }
+                _ => self.punct(TokenType::Question, 1),
+            },
+            '~' => self.punct(TokenType::Tilde, 1),
+            '^' => match next {
+                Some('=') => self.punct(TokenType::CaretAssign, 2),
+                _ => self.punct(TokenType::Caret, 1),
+            },
+            '#' => self.punct(TokenType::Hash, 1),
+            '@' => self.punct(TokenType::At, 1),
+            _ => self.unexpected_character(current),
+        }
+    }
+
+    /// A regular expression literal, `/pattern/flags`. A `/` escaped or in
+    /// a character class doesn't end it; a line break does, unterminated.
+    fn read_regex(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        self.bump(); // Skip /
+        let mut in_class = false;
+        let mut terminated = false;
+        while let Some(c) = self.peek_char() {
+            match c {
+                '\n' | '\r' => break,
+                '\\' => {
+                    self.bump();
+                    if matches!(self.peek_char(), Some('\n' | '\r') | None) {
+                        break;
+                    }
+                }
+                '[' => in_class = true,
+                ']' => in_class = false,
+                '/' if !in_class => {
+                    self.bump();
+                    terminated = true;
+                    break;
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+        if terminated {
+            self.bump_while(is_identifier_part); // Flags
+        }
+
+        let value = self.slice(start.offset, self.position);
+        let token = self.make_token(TokenType::Regex, value, start);
+        if !terminated {
+            self.error(codes::UNTERMINATED_REGEX, "unterminated regular expression".to_string(), &token.span);
+        }
+        token
+    }
+
+    /// Whitespace and comments, shared by the script and style modes.
+    fn lex_trivia(&mut self, current: char, line_comments: bool) -> Option<Token<'src>> {
+        // Whitespace (a byte order mark is kept as zero-width trivia)
+        if current.is_whitespace() || current == '\u{FEFF}' {
+            return Some(self.read_whitespace());
+        }
+
+        // Comments
+        if current == '/' {
+            match self.peek_next_char() {
+                Some('/') if line_comments => return Some(self.read_line_comment()),
+                Some('*') => return Some(self.read_block_comment()),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // ---- tag mode ----
+
+    /// Inside `<name ...>` / `</name>`: tag and attribute names, `=`, quoted
+    /// values and `{expression}` values.
+    fn lex_tag(&mut self) -> Token<'src> {
+        let current = self.peek_char().unwrap_or('\0');
+        if current.is_whitespace() {
+            return self.read_whitespace();
+        }
+
+        let next = self.peek_next_char();
+        match current {
+            '>' => {
+                let token = self.punct(TokenType::TagEnd, 1);
+                self.end_tag();
+                token
+            }
+            '/' if next == Some('>') => {
+                let token = self.punct(TokenType::SelfClosingTagEnd, 2);
+                self.pop_mode();
+                token
+            }
+            '=' => self.punct(TokenType::Assign, 1),
+            '"' | '\'' => self.read_string(true),
+            '{' => {
+                let token = self.punct(TokenType::LeftBrace, 1);
+                self.push_mode(LexMode::Script);
+                token
+            }
+            '[' if next == Some('[') => self.start_interpolation(),
+            '[' => self.punct(TokenType::LeftBracket, 1),
+            ']' => self.punct(TokenType::RightBracket, 1),
+            c if is_identifier_start(c) || c == '@' || c == ':' => self.read_tag_name(),
+            _ => self.unexpected_character(current),
+        }
+    }
+
+    /// Tag and attribute names keep their hyphens and namespaces
+    /// (`data-size`, `on:click`, `@click`) and are never keywords.
+    fn read_tag_name(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        self.bump();
+        self.bump_while(|c| is_identifier_part(c) || c == '-' || c == ':' || c == '.');
+        let value = self.slice(start.offset, self.position);
+
+        let frame = self.frame_mut();
+        if frame.tag_name.is_none() {
+            frame.tag_name = Some(value);
+        }
+        self.make_token(TokenType::Identifier, value, start)
+    }
+
+    /// Called on the `>` of a tag: an opening tag continues with its content,
+    /// except for void elements and the `<component Name>` declaration
+    /// header, whose body is script.
+    fn end_tag(&mut self) {
+        let frame = match self.pop_mode() {
+            Some(frame) => frame,
+            None => return,
+        };
+        if frame.closing {
+            return;
+        }
+
+        if is_void_tag(frame.tag_name.unwrap_or("")) {
+            return;
+        }
+
+        let mut markup = ModeFrame::new(LexMode::Markup);
+        markup.tag_name = frame.tag_name;
+        self.modes.push(markup);
+    }
+
+    /// After `</`: leaves the content of the element the tag closes and of
+    /// any left unclosed inside it. A closing tag matching no open element
+    /// leaves the content as is, and one whose name isn't plain text
+    /// (`</[[type]]>`) closes the innermost element.
+    fn close_elements(&mut self) {
+        let rest = &self.input[self.position..];
+        let end = rest
+            .find(|c: char| !(is_identifier_part(c) || c == '-' || c == ':' || c == '.'))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        if name.is_empty() {
+            self.pop_mode();
+            return;
+        }
+
+        let open = self.modes.iter()
+            .rev()
+            .take_while(|frame| frame.mode == LexMode::Markup)
+            .position(|frame| frame.tag_name.is_some_and(|tag| tag.eq_ignore_ascii_case(name)));
+        if let Some(offset) = open {
+            for _ in 0..=offset {
+                self.pop_mode();
+            }
+        }
+    }
+
+    // ---- markup mode ----
+
+    /// Element content: text runs, child tags, `<!-- -->` comments and
+    /// `{expression}` holes.
+    fn lex_markup(&mut self) -> Token<'src> {
+        let current = self.peek_char().unwrap_or('\0');
+        let rest = &self.input[self.position..];
+
+        // `<script>` and `<style>` content is raw text up to the closing tag
+        let raw_element = self.modes.last()
+            .and_then(|frame| frame.tag_name)
+            .map(|name| name.to_ascii_lowercase())
+            .filter(|name| RAW_TEXT_ELEMENTS.contains(&name.as_str()));
+        if let Some(name) = raw_element {
+            if !rest.starts_with("</") {
+                return self.read_raw_text(&name);
+            }
+        }
+
+        if current.is_whitespace() || current == '\u{FEFF}' {
+            return self.read_whitespace();
+        }
+
+        let next = self.peek_next_char();
+        match current {
+            '<' if rest.starts_with("<!--") => self.read_markup_comment(),
+            '<' if next == Some('/') => {
+                let token = self.punct(TokenType::ClosingTagStart, 2);
+                self.close_elements();
+                let mut tag = ModeFrame::new(LexMode::Tag);
+                tag.closing = true;
+                self.modes.push(tag);
+                token
+            }
+            '<' if next.is_some_and(|c| is_name_start(c) || c == '>' || c == '[') => {
+                let token = self.punct(TokenType::OpeningTagStart, 1);
+                self.push_mode(LexMode::Tag);
+                token
+            }
+            '{' => {
+                let token = self.punct(TokenType::LeftBrace, 1);
+                self.push_mode(LexMode::Script);
+                token
+            }
+            '[' if next == Some('[') => self.start_interpolation(),
+            _ => self.read_text(),
+        }
+    }
+
+    /// Emits `[[` and lexes the interpolated expression as script up to the
+    /// matching `]]`.
+    fn start_interpolation(&mut self) -> Token<'src> {
+        let token = self.punct(TokenType::InterpolationStart, 2);
+        let mut frame = ModeFrame::new(LexMode::Script);
+        frame.interpolation = true;
+        self.modes.push(frame);
+        token
+    }
+
+    /// Reads a run of text up to the next tag, `{` or `[[`. Trailing whitespace is
+    /// left for the whitespace tokens so text values never end in blanks.
+    fn read_text(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        let rest = &self.input[self.position..];
+
+        let mut text_end = 0;
+        let mut chars = rest.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            let next = chars.peek().map(|&(_, c)| c);
+            let starts_tag = c == '<'
+                && next.is_some_and(|n| n == '/' || n == '>' || n == '[' || is_name_start(n) || rest[index..].starts_with("<!--"));
+            let starts_interpolation = c == '[' && next == Some('[');
+            if index > 0 && (c == '{' || starts_tag || starts_interpolation) {
+                break;
+            }
+            if !c.is_whitespace() {
+                text_end = index + c.len_utf8();
+            }
+        }
+
+        let end = self.position + text_end;
+        while self.position < end {
+            self.bump();
+        }
+        let value = self.slice(start.offset, self.position);
+        self.make_token(TokenType::Text, value, start)
+    }
+
+    fn read_raw_text(&mut self, tag_name: &str) -> Token<'src> {
+        let start = self.current_position();
+        let rest = &self.input[self.position..];
+        let name = tag_name.as_bytes();
+        let len = rest
+            .match_indices("</")
+            .map(|(index, _)| index)
+            .find(|index| rest.as_bytes().get(index + 2..index + 2 + name.len()).is_some_and(|tag| tag.eq_ignore_ascii_case(name)))
+            .unwrap_or(rest.len());
+
+        let end = self.position + len;
+        while self.position < end {
+            self.bump();
+        }
+        let value = self.slice(start.offset, self.position);
+        self.make_token(TokenType::Text, value, start)
+    }
+
+    fn read_markup_comment(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        let rest = &self.input[self.position..];
+        let (len, terminated) = match rest[4..].find("-->") {
+            Some(index) => (4 + index + 3, true),
+            None => (rest.len(), false),
+        };
+
+        let end = self.position + len;
+        while self.position < end {
+            self.bump();
+        }
+        let value = self.slice(start.offset, self.position);
+        let token = self.make_token(TokenType::Comment, value, start);
+        if !terminated {
+            self.error(codes::UNTERMINATED_COMMENT, "unterminated markup comment".to_string(), &token.span);
+        }
+        token
+    }
+
+    // ---- template mode ----
+
+    /// Between the backticks of a template literal: raw text chunks, the
+    /// closing backtick, and `${` holes lexed as script until their `}`.
+    fn lex_template(&mut self) -> Token<'src> {
+        let rest = &self.input[self.position..];
+        if rest.starts_with('`') {
+            let token = self.punct(TokenType::TemplateEnd, 1);
+            self.pop_mode();
+            return token;
+        }
+        if rest.starts_with("${") {
+            let token = self.punct(TokenType::TemplateExprStart, 2);
+            self.push_mode(LexMode::Script);
+            return token;
+        }
+        self.read_template_chunk()
+    }
+
+    /// Reads template text up to the next `${` or closing backtick. The value
+    /// is the raw source text, escapes included.
+    fn read_template_chunk(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        while let Some(current) = self.peek_char() {
+            if current == '`' || (current == '$' && self.peek_next_char() == Some('{')) {
+                break;
+            }
+            self.bump();
+            if current == '\\' {
+                self.bump();
+            }
+        }
+        let value = self.slice(start.offset, self.position);
+        self.make_token(TokenType::TemplateChunk, value, start)
+    }
+
+    // ---- style mode ----
+
+    /// `style`/`animation` blocks and at-rules: hyphenated CSS identifiers,
+    /// `#id`/`#hex` and `@rule` words, combinators, and `//` comments (except
+    /// in `url(//...)` or after a `:` as in `https://`). The frame ends with
+    /// the block's closing brace, or with `;` for a block-less at-rule.
+    fn lex_style(&mut self) -> Token<'src> {
+        let current = self.peek_char().unwrap_or('\0');
+        let line_comments = !matches!(self.previous_char(), Some('(') | Some(':'));
+        if let Some(token) = self.lex_trivia(current, line_comments) {
+            return token;
+        }
+
+        if current == '"' || current == '\'' {
+            return self.read_string(false);
+        }
+
+        let next = self.peek_next_char();
+        if current.is_ascii_digit() || (current == '.' && next.is_some_and(|c| c.is_ascii_digit())) {
+            return self.read_css_number();
+        }
+
+        if is_name_start(current)
+            || (current == '-' && next.is_some_and(|c| is_name_start(c) || c == '-'))
+        {
+            return self.read_css_word(TokenType::Identifier, 0);
+        }
+
+        match current {
+            '#' if next.is_some_and(is_css_name_part) => self.read_css_word(TokenType::Hash, 1),
+            '@' if next.is_some_and(is_css_name_part) => self.read_css_word(TokenType::At, 1),
+            '{' => {
+                self.frame_mut().depth += 1;
+                self.punct(TokenType::LeftBrace, 1)
+            }
+            '}' => {
+                let token = self.punct(TokenType::RightBrace, 1);
+                let frame = self.frame_mut();
+                frame.depth = frame.depth.saturating_sub(1);
+                if frame.depth == 0 {
+                    self.pop_mode();
+                }
+                token
+            }
+            ';' => {
+                let token = self.punct(TokenType::Semicolon, 1);
+                if self.frame_mut().depth == 0 {
+                    self.pop_mode();
+                }
+                token
+            }
+            '(' => self.punct(TokenType::LeftParen, 1),
+            ')' => self.punct(TokenType::RightParen, 1),
+            '[' => self.punct(TokenType::LeftBracket, 1),
+            ']' => self.punct(TokenType::RightBracket, 1),
+            ',' => self.punct(TokenType::Comma, 1),
+            '.' => self.punct(TokenType::Dot, 1),
+            ':' => self.punct(TokenType::Colon, 1),
+            '&' => self.punct(TokenType::Ampersand, 1),
+            '>' => self.punct(TokenType::Greater, 1),
+            '+' => self.punct(TokenType::Plus, 1),
+            '-' => self.punct(TokenType::Minus, 1),
+            '~' => self.punct(TokenType::Tilde, 1),
+            '*' => self.punct(TokenType::Multiply, 1),
+            '/' => self.punct(TokenType::Divide, 1),
+            '%' => self.punct(TokenType::Modulo, 1),
+            '=' => self.punct(TokenType::Assign, 1),
+            '!' => self.punct(TokenType::Not, 1),
+            '|' => self.punct(TokenType::Pipe, 1),
+            '^' => self.punct(TokenType::Caret, 1),
+            '$' => self.punct(TokenType::Dollar, 1),
+            '#' => self.punct(TokenType::Hash, 1),
+            '@' => self.punct(TokenType::At, 1),
+            _ => self.unexpected_character(current),
+        }
+    }
+
+    /// Reads a CSS identifier, optionally behind a `prefix_len`-byte sigil
+    /// (`#fff`, `@media`). CSS words are never keywords.
+    fn read_css_word(&mut self, token_type: TokenType, prefix_len: usize) -> Token<'src> {
+        let start = self.current_position();
+        for _ in 0..prefix_len {
+            self.bump();
+        }
+        self.bump_while(is_css_name_part);
+        let value = self.slice(start.offset, self.position);
+        self.make_token(token_type, value, start)
+    }
+
+    fn unexpected_character(&mut self, current: char) -> Token<'src> {
+        let start = self.current_position();
+        self.bump();
+        let token = self.make_token(TokenType::Unknown, self.slice(start.offset, self.position), start);
+        self.error(codes::UNEXPECTED_CHARACTER, format!("unexpected character '{}'", current), &token.span);
+        token
+    }
+
+    fn current_position(&self) -> Position {
+        Position {
+            offset: self.position,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// Builds a token spanning from `start` to the current cursor.
+    fn make_token(&self, token_type: TokenType, value: &'src str, start: Position) -> Token<'src> {
+        Token::new(token_type, value, Span::new(self.file.clone(), start, self.current_position()))
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &'src str {
+        &self.input[start..end]
+    }
+
+    /// Returns the character under the cursor without consuming it.
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    /// Returns the character before the cursor.
+    fn previous_char(&self) -> Option<char> {
+        self.input[..self.position].chars().next_back()
+    }
+
+    /// Returns the character after the one under the cursor.
+    fn peek_next_char(&self) -> Option<char> {
+        let mut chars = self.input[self.position..].chars();
+        chars.next();
+        chars.next()
+    }
+
+    fn peek_third_char(&self) -> Option<char> {
+        self.input[self.position..].chars().nth(2)
+    }
+
+    /// Consumes one character, keeping `position` on a UTF-8 boundary and
+    /// `line`/`column` in sync with it.
+    fn bump(&mut self) -> Option<char> {
+        let current = self.peek_char()?;
+        self.position += current.len_utf8();
+        if current == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if current != '\u{FEFF}' {
+            self.column += 1;
+        }
+        Some(current)
+    }
+
+    /// Consumes characters while `predicate` holds.
+    fn bump_while<F: Fn(char) -> bool>(&mut self, predicate: F) {
+        while let Some(current) = self.peek_char() {
+            if !predicate(current) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    /// Builds an ASCII operator or punctuation token of `len` bytes.
+    fn punct(&mut self, token_type: TokenType, len: usize) -> Token<'src> {
+        let start = self.current_position();
+        let value = self.slice(self.position, self.position + len);
+        self.position += len;
+        self.column += len;
+        self.make_token(token_type, value, start)
+    }
+
+    /// Reads a newline, or a run of other whitespace as one token.
+    fn read_whitespace(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        let token_type = if self.bump() == Some('\n') {
+            TokenType::Newline
+        } else {
+            self.bump_while(|c| (c.is_whitespace() && c != '\n') || c == '\u{FEFF}');
+            TokenType::Whitespace
+        };
+        self.make_token(token_type, self.slice(start.offset, self.position), start)
+    }
+
+    fn read_line_comment(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        self.bump_while(|c| c != '\n');
+        let value = self.slice(start.offset, self.position);
+        self.make_token(TokenType::Comment, value, start)
+    }
+
+    fn read_block_comment(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        self.bump(); // Skip /*
+        self.bump();
+
+        let mut terminated = false;
+        while let Some(current) = self.bump() {
+            if current == '*' && self.peek_char() == Some('/') {
+                self.bump();
+                terminated = true;
+                break;
+            }
+        }
+
+        let value = self.slice(start.offset, self.position);
+        let token = self.make_token(TokenType::Comment, value, start);
+        if !terminated {
+            self.error(codes::UNTERMINATED_COMMENT, "unterminated block comment".to_string(), &token.span);
+        }
+        token
+    }
+
+    /// Reads a quoted string. Script and CSS strings end at an unescaped
+    /// newline; attribute values may span lines.
+    fn read_string(&mut self, multiline: bool) -> Token<'src> {
+        let start = self.current_position();
+        let quote = self.bump().unwrap();
+
+        let content_start = self.position;
+        let mut terminated = false;
+        while let Some(current) = self.peek_char() {
+            if current == '\n' && !multiline {
+                break;
+            }
+            self.bump();
+            if current == quote {
+                terminated = true;
+                break;
+            }
+            if current == '\\' {
+                self.bump();
+            }
+        }
+
+        let end = if terminated { self.position - quote.len_utf8() } else { self.position };
+        let value = self.slice(content_start, end);
+        let token = self.make_token(TokenType::String, value, start);
+        if !terminated {
+            self.error(codes::UNTERMINATED_STRING, "unterminated string literal".to_string(), &token.span);
+        }
+        token
+    }
+
+    /// Reads a JavaScript numeric literal: decimal with optional fraction and
+    /// exponent, `0x`/`0o`/`0b` integers, `_` separators between digits, and
+    /// a trailing `n` for BigInt.
+    fn read_number(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        let prefix = self.input[self.position..].get(..2).map(str::to_ascii_lowercase);
+        let radix = match prefix.as_deref() {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+
+        let mut valid = true;
+        let mut integer = true;
+        if radix != 10 {
+            self.bump();
+            self.bump();
+            valid &= self.read_digits(radix);
+        } else {
+            if self.peek_char() != Some('.') {
+                valid &= self.read_digits(10);
+            }
+            if self.peek_char() == Some('.') && self.peek_next_char().is_some_and(|c| c.is_ascii_digit()) {
+                self.bump();
+                valid &= self.read_digits(10);
+                integer = false;
+            }
+            if self.at_exponent() {
+                self.bump();
+                if matches!(self.peek_char(), Some('+') | Some('-')) {
+                    self.bump();
+                }
+                valid &= self.read_digits(10);
+                integer = false;
+            }
+        }
+
+        let mut token_type = TokenType::Number;
+        if self.peek_char() == Some('n') {
+            self.bump();
+            token_type = TokenType::BigInt;
+            valid &= integer;
+        }
+
+        // `3in` or `0b12`: the literal runs straight into a name or digit
+        if self.peek_char().is_some_and(is_identifier_part) {
+            self.bump_while(is_identifier_part);
+            valid = false;
+        }
+
+        let value = self.slice(start.offset, self.position);
+        let token = self.make_token(token_type, value, start);
+        if !valid {
+            self.error(codes::INVALID_NUMBER, format!("invalid numeric literal '{}'", token.value), &token.span);
+        }
+        token
+    }
+
+    /// Consumes digits of `radix` and `_` separators. Returns false when
+    /// there are no digits or a separator is not between two digits.
+    fn read_digits(&mut self, radix: u32) -> bool {
+        let mut digits = 0;
+        let mut valid = true;
+        let mut after_separator = false;
+
+        while let Some(current) = self.peek_char() {
+            if current == '_' {
+                if digits == 0 || after_separator {
+                    valid = false;
+                }
+                after_separator = true;
+            } else if current.is_digit(radix) {
+                digits += 1;
+                after_separator = false;
+            } else {
+                break;
+            }
+            self.bump();
+        }
+
+        valid && digits > 0 && !after_separator
+    }
+
+    /// Whether the cursor is on an exponent: `e` or `E`, an optional sign,
+    /// then a digit. In CSS this keeps `1em` a dimension.
+    fn at_exponent(&self) -> bool {
+        let mut chars = self.input[self.position..].chars();
+        if !matches!(chars.next(), Some('e') | Some('E')) {
+            return false;
+        }
+        match chars.next() {
+            Some('+') | Some('-') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    /// Reads a CSS number and its unit: `80px` and `0.8s` are dimensions,
+    /// `50%` is a percentage and a bare `1.5` stays a number.
+    fn read_css_number(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        self.bump_while(|c| c.is_ascii_digit());
+        if self.peek_char() == Some('.') && self.peek_next_char().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            self.bump_while(|c| c.is_ascii_digit());
+        }
+        if self.at_exponent() {
+            self.bump();
+            if matches!(self.peek_char(), Some('+') | Some('-')) {
+                self.bump();
+            }
+            self.bump_while(|c| c.is_ascii_digit());
+        }
+
+        let token_type = match self.peek_char() {
+            Some('%') => {
+                self.bump();
+                TokenType::Percentage
+            }
+            Some(c) if is_name_start(c) => {
+                self.bump_while(is_identifier_part);
+                TokenType::Dimension
+            }
+            _ => TokenType::Number,
+        };
+
+        let value = self.slice(start.offset, self.position);
+        self.make_token(token_type, value, start)
+    }
+
+    fn read_identifier(&mut self) -> Token<'src> {
+        let start = self.current_position();
+        self.bump_while(is_identifier_part);
+
+        let value = self.slice(start.offset, self.position);
+        let token_type = self.keywords.get(value)
+            .cloned()
+            .unwrap_or(TokenType::Identifier);
+
+        self.make_token(token_type, value, start)
+    }
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Token<'src>;
+
+    fn next(&mut self) -> Option<Token<'src>> {
+        loop {
+            let token = self.next_token()?;
+            if !(self.skip_trivia && token.is_trivia()) {
+                return Some(token);
+            }
+        }
+    }
+}
+
+// Identifiers may use any Unicode letter ("Kullanıcılar", "größe"), `_` or
+// `$`, and may continue with digits, combining marks and the ZWJ/ZWNJ
+// joiners used by some scripts. Columns are counted in characters, not
+// bytes.
+fn is_identifier_start(c: char) -> bool {
+    is_name_start(c) || c == '$'
+}
+
+fn is_identifier_part(c: char) -> bool {
+    c.is_alphanumeric()
+        || c == '_'
+        || c == '$'
+        || c == '\u{200C}'
+        || c == '\u{200D}'
+        || ('\u{0300}'..='\u{036F}').contains(&c)
+}
+
+/// Starts a tag or CSS name, which unlike an identifier can't start with
+/// `$`.
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// CSS names have no `$`, which starts the `$=` attribute matcher.
+fn is_css_name_part(c: char) -> bool {
+    (is_identifier_part(c) && c != '$') || c == '-'
+}
+
+// ===================================
+// PARSER
+// ===================================
+
+/// The members of a component body, sorted by kind.
+#[derive(Default)]
+struct ComponentMembers {
+    props: Vec<ASTNode>,
+    events: Vec<ASTNode>,
+    state: Vec<ASTNode>,
+    methods: Vec<ASTNode>,
+    computed: Vec<ASTNode>,
+    watchers: Vec<ASTNode>,
+    lifecycle: Vec<ASTNode>,
+    // The `render` block's root elements, in order
+    render: Vec<ASTNode>,
+}
+
+/// How deeply expressions, statements, elements, types and style rules
+/// may nest. The parser recurses once per level, so this bounds its stack.
+const MAX_NESTING_DEPTH: usize = 128;
+
+pub struct Parser<'src> {
+    tokens: Vec<Token<'src>>,
+    current: usize,
+    // Levels of nesting the parser is inside, up to `MAX_NESTING_DEPTH`
+    depth: usize,
+    // Names of the elements whose children are being parsed, outermost first
+    open_elements: Vec<String>,
+    // Every token including trivia, and the index there of each of `tokens`
+    all_tokens: Vec<Token<'src>>,
+    positions: Vec<usize>,
+    events: Vec<Event>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'src> Parser<'src> {
+    pub fn new(all_tokens: Vec<Token<'src>>) -> Self {
+        let positions: Vec<usize> = all_tokens.iter()
+            .enumerate()
+            .filter(|(_, token)| !token.is_trivia())
+            .map(|(index, _)| index)
+            .collect();
+        let tokens = positions.iter().map(|&index| all_tokens[index].clone()).collect();
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            open_elements: Vec::new(),
+            all_tokens,
+            positions,
+            events: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Parses the whole input. Parsing recovers from an error at the next
+    /// declaration or tag, so the program holds every statement that
+    /// parsed and every error is available from `diagnostics()`. Nesting
+    /// past `MAX_NESTING_DEPTH` is the exception: it ends the parse.
+    pub fn parse(&mut self) -> ASTNode {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end() {
+            let checkpoint = self.checkpoint();
+            if self.at_script_statement() {
+                match self.parse_script_statement() {
+                    Ok(statement) => statements.push(statement),
+                    Err(error) => {
+                        self.report(error);
+                        self.rewind(checkpoint);
+                        self.synchronize();
+                        self.wrap(checkpoint, SyntaxKind::Error);
+                    }
+                }
+                continue;
+            }
+            if !self.at_statement_start() {
+                let error = self.unexpected("a declaration");
+                self.report(error);
+                self.synchronize();
+                self.wrap(checkpoint, SyntaxKind::Error);
+                continue;
+            }
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    let too_deep = error.code == codes::NESTING_TOO_DEEP;
+                    self.report(error);
+                    self.rewind(checkpoint);
+                    if too_deep {
+                        // Finding where the construct ends would take the
+                        // recursion the limit prevents; give up on the rest
+                        while !self.is_at_end() {
+                            self.advance();
+                        }
+                    } else {
+                        self.synchronize();
+                    }
+                    self.wrap(checkpoint, SyntaxKind::Error);
+                }
+            }
+        }
+
+        let span = match (self.tokens.first(), self.tokens.last()) {
+            (Some(first), Some(last)) => first.span.to(&last.span),
+            _ => self.previous_span(),
+        };
+
+        ASTNode::Program {
+            body: statements,
+            span,
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Whether the next token starts a statement `parse_statement` knows.
+    fn at_statement_start(&self) -> bool {
+        let starts = self.peek().is_some_and(|token| matches!(
+            token.token_type,
+            TokenType::Page | TokenType::Component | TokenType::Style | TokenType::Event | TokenType::State
+                | TokenType::Api | TokenType::Router | TokenType::Layout | TokenType::Animation | TokenType::Type
+                | TokenType::Interface | TokenType::Enum | TokenType::Hook | TokenType::Plugin | TokenType::Config
+                | TokenType::Import | TokenType::Export | TokenType::Function | TokenType::OpeningTagStart | TokenType::At
+                | TokenType::Const | TokenType::Let | TokenType::Var
+        ));
+        starts || self.check_async_function()
+    }
+
+    /// Whether a line starts with a plain script statement: a name, or a
+    /// declaration keyword used as one (`style.textContent = ...`, but not
+    /// `style .card { ... }`).
+    fn at_script_statement(&self) -> bool {
+        let named = match self.peek() {
+            Some(token) if matches!(token.token_type, TokenType::Identifier | TokenType::Await) => true,
+            Some(token) if is_word(&token.token_type) => {
+                self.tokens.get(self.current + 1).is_some_and(|next| match next.token_type {
+                    TokenType::Dot => next.span.start.offset == token.span.end.offset,
+                    TokenType::Assign => true,
+                    _ => false,
+                })
+            }
+            _ => false,
+        };
+        named && self.starts_line()
+    }
+
+    fn parse_script_statement(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens[self.current].span.clone();
+        let statement = self.nested(Self::parse_stmt)?;
+        self.wrap(checkpoint, SyntaxKind::Script);
+        Ok(ASTNode::Script { statement, span: self.span_from(&start) })
+    }
+
+    /// Skips past a token no statement starts with, or a statement that
+    /// failed to parse: up to the next token that starts one outside
+    /// brackets, or through the `}` closing the statement's block. As the
+    /// error may have left brackets unbalanced, a statement starting a
+    /// line no further indented than the skipped one also stops it.
+    fn synchronize(&mut self) {
+        let column = self.peek().map_or(0, |token| token.span.start.column);
+        let mut depth = 0usize;
+        self.advance(); // Always make progress
+        while let Some(token) = self.peek() {
+            let outdented = self.starts_line() && token.span.start.column <= column;
+            match token.token_type {
+                TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightBrace if depth == 1 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => depth = depth.saturating_sub(1),
+                _ if (depth == 0 || outdented) && (self.at_statement_start() || self.at_script_statement()) => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skips a component member that failed to parse. Brackets may be
+    /// unbalanced, so this goes by indentation: through the lines indented
+    /// past the member and a closing bracket level with it.
+    fn skip_member(&mut self) {
+        let column = self.peek().map_or(0, |token| token.span.start.column);
+        self.advance(); // Always make progress
+        while let Some(token) = self.peek() {
+            if self.starts_line() && token.span.start.column <= column {
+                let closing = matches!(token.token_type, TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket);
+                if !closing || token.span.start.column < column {
+                    return;
+                }
+            }
+            self.advance();
+        }
+    }
+
+    /// Whether the next token is the first on its line.
+    fn starts_line(&self) -> bool {
+        match (self.previous(), self.peek()) {
+            (Some(previous), Some(token)) => token.span.start.line > previous.span.end.line,
+            _ => true,
+        }
+    }
+
+    /// Builds the lossless syntax tree for the input parsed so far. Call
+    /// after `parse`.
+    pub fn syntax_tree(&self) -> SyntaxNode<'src> {
+        let mut stack = vec![SyntaxNode::new(SyntaxKind::Root)];
+        let mut next = 0;
+
+        for event in &self.events {
+            match *event {
+                Event::Open(kind, token) => {
+                    // Trivia before the node's first token stays in the parent
+                    let until = self.positions.get(token).copied().unwrap_or(self.all_tokens.len());
+                    self.flush_tokens(&mut stack, &mut next, until);
+                    stack.push(SyntaxNode::new(kind));
+                }
+                Event::Close(token) => {
+                    let until = match token.checked_sub(1).and_then(|last| self.positions.get(last)) {
+                        Some(&last) => last + 1,
+                        None => next,
+                    };
+                    self.flush_tokens(&mut stack, &mut next, until);
+                    if stack.len() > 1 {
+                        let node = stack.pop().unwrap();
+                        stack.last_mut().unwrap().children.push(SyntaxElement::Node(node));
+                    }
+                }
+            }
+        }
+
+        self.flush_tokens(&mut stack, &mut next, self.all_tokens.len());
+        while stack.len() > 1 {
+            let node = stack.pop().unwrap();
+            stack.last_mut().unwrap().children.push(SyntaxElement::Node(node));
+        }
+        stack.pop().unwrap()
+    }
+
+    fn flush_tokens(&self, stack: &mut [SyntaxNode<'src>], next: &mut usize, until: usize) {
+        let node = stack.last_mut().unwrap();
+        while *next < until {
+            node.children.push(SyntaxElement::Token(self.all_tokens[*next].clone()));
+            *next += 1;
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            event: self.events.len(),
+            token: self.current,
+        }
+    }
+
+    /// A checkpoint at the token just consumed, for nodes whose first token
+    /// was matched before their parse function was called.
+    fn checkpoint_previous(&self) -> Checkpoint {
+        Checkpoint {
+            event: self.events.len(),
+            token: self.current.saturating_sub(1),
+        }
+    }
+
+    /// Records a `kind` node from `checkpoint` through the last consumed token.
+    fn wrap(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        self.events.insert(checkpoint.event, Event::Open(kind, checkpoint.token));
+        self.events.push(Event::Close(self.current));
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current >= self.tokens.len() || self.tokens[self.current].token_type == TokenType::Eof
+    }
+
+    fn peek(&self) -> Option<&Token<'src>> {
+        if self.is_at_end() {
+            None
+        } else {
+            Some(&self.tokens[self.current])
+        }
+    }
+
+    fn previous(&self) -> Option<&Token<'src>> {
+        if self.current == 0 {
+            None
+        } else {
+            Some(&self.tokens[self.current - 1])
+        }
+    }
+
+    /// Span of the most recently consumed token, used as the start of a node
+    /// whose leading keyword has already been matched.
+    fn previous_span(&self) -> Span {
+        self.previous()
+            .or_else(|| self.tokens.get(self.current))
+            .map(|token| token.span.clone())
+            .unwrap_or_else(|| Span::new(Arc::from(""), Position::default(), Position::default()))
+    }
+
+    /// Span from `start` through the most recently consumed token.
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.previous_span())
+    }
+
+    fn advance(&mut self) -> Option<&Token<'src>> {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        if self.is_at_end() {
+            false
+        } else {
+            self.tokens[self.current].token_type == *token_type
+        }
+    }
+
+    /// Whether the next tokens are `token_type` followed by the tag name
+    /// `name`, as in `<page`.
+    fn check_tag(&self, token_type: &TokenType, name: &str) -> bool {
+        self.check(token_type)
+            && self.tokens.get(self.current + 1)
+                .is_some_and(|token| token.token_type == TokenType::Identifier && token.value == name)
+    }
+
+    fn check_async_function(&self) -> bool {
+        self.check(&TokenType::Async)
+            && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::Function)
+    }
+
+    fn match_tokens(&mut self, token_types: &[TokenType]) -> bool {
+        for token_type in token_types {
+            if self.check(token_type) {
+                self.advance();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn parse_statement(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let (kind, statement) = if self.match_tokens(&[TokenType::Page]) {
+            (SyntaxKind::Page, self.parse_page()?)
+        } else if self.match_tokens(&[TokenType::Component]) {
+            (SyntaxKind::Component, self.parse_component()?)
+        } else if self.match_tokens(&[TokenType::Style]) {
+            (SyntaxKind::Style, self.parse_style()?)
+        } else if self.match_tokens(&[TokenType::Event]) {
+            (SyntaxKind::Event, self.parse_event()?)
+        } else if self.check(&TokenType::State)
+            && self.tokens.get(self.current + 2).is_some_and(|token| token.token_type == TokenType::Colon)
+        {
+            // `state name: Type = initial;` outside a component
+            self.advance(); // Skip state
+            (SyntaxKind::StateVariable, self.parse_state_variable(self.previous_span())?)
+        } else if self.match_tokens(&[TokenType::State]) {
+            (SyntaxKind::State, self.parse_state()?)
+        } else if self.match_tokens(&[TokenType::Api]) {
+            (SyntaxKind::Api, self.parse_api()?)
+        } else if self.match_tokens(&[TokenType::Router]) {
+            (SyntaxKind::Router, self.parse_router()?)
+        } else if self.match_tokens(&[TokenType::Layout]) {
+            (SyntaxKind::Layout, self.parse_layout()?)
+        } else if self.match_tokens(&[TokenType::Animation]) {
+            (SyntaxKind::Animation, self.parse_animation()?)
+        } else if self.match_tokens(&[TokenType::Type]) {
+            (SyntaxKind::Type, self.parse_type()?)
+        } else if self.match_tokens(&[TokenType::Interface]) {
+            (SyntaxKind::Interface, self.parse_interface()?)
+        } else if self.match_tokens(&[TokenType::Enum]) {
+            (SyntaxKind::Enum, self.parse_enum()?)
+        } else if self.match_tokens(&[TokenType::Hook]) {
+            (SyntaxKind::Hook, self.parse_hook()?)
+        } else if self.match_tokens(&[TokenType::Plugin]) {
+            (SyntaxKind::Plugin, self.parse_plugin()?)
+        } else if self.match_tokens(&[TokenType::Config]) {
+            (SyntaxKind::Config, self.parse_config()?)
+        } else if self.match_tokens(&[TokenType::Import]) {
+            (SyntaxKind::Import, self.parse_import()?)
+        } else if self.match_tokens(&[TokenType::Export]) {
+            (SyntaxKind::Export, self.parse_export()?)
+        } else if self.check(&TokenType::Function) || self.check_async_function() {
+            (SyntaxKind::Method, self.parse_method()?)
+        } else if self.check(&TokenType::Const) || self.check(&TokenType::Let) || self.check(&TokenType::Var) {
+            let start = self.tokens[self.current].span.clone();
+            let statement = self.nested(Self::parse_stmt)?;
+            (SyntaxKind::Variable, ASTNode::Variable { statement, span: self.span_from(&start) })
+        } else if self.check_tag(&TokenType::OpeningTagStart, "component") {
+            self.advance(); // Skip <
+            (SyntaxKind::Component, self.parse_component_element()?)
+        } else if self.check_tag(&TokenType::OpeningTagStart, "page") {
+            self.advance(); // Skip <
+            (SyntaxKind::Page, self.parse_page_element()?)
+        } else if self.check_tag(&TokenType::OpeningTagStart, "router") {
+            self.advance(); // Skip <
+            (SyntaxKind::Router, self.parse_router_element()?)
+        } else if self.match_tokens(&[TokenType::OpeningTagStart]) {
+            // parse_element records its own node
+            return self.parse_element();
+        } else if self.check(&TokenType::At) {
+            // A top-level `@media` block; parse_at_rule records its own node
+            return self.parse_at_rule();
+        } else {
+            return Err(self.unexpected("a declaration"));
+        };
+
+        self.wrap(checkpoint, kind);
+        Ok(statement)
+    }
+
+    fn parse_page(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+        let mut attributes = Vec::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        // Parse attributes
+        while !self.check(&TokenType::LeftBrace) && !self.is_at_end() {
+            if self.peek().is_some_and(|token| is_word(&token.token_type)) {
+                attributes.push(self.parse_attribute()?);
+            } else {
+                return Err(self.unexpected("a page attribute or `{`"));
+            }
+        }
+
+        let mut body = Vec::new();
+        if self.check(&TokenType::LeftBrace) {
+            self.advance(); // Skip {
+            body = self.parse_block()?;
+            if self.check(&TokenType::RightBrace) {
+                self.advance(); // Skip }
+            }
+        }
+
+        Ok(ASTNode::Page {
+            name,
+            attributes,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses the `<page title="...">...</page>` form after its `<`. The
+    /// page's children, usually `<head>` and `<body>`, become its body.
+    fn parse_page_element(&mut self) -> Result<ASTNode, Diagnostic> {
+        match self.parse_tag()? {
+            ASTNode::Element { attributes, children, span, .. } => Ok(ASTNode::Page {
+                name: String::new(),
+                attributes,
+                body: children,
+                span,
+            }),
+            other => Ok(other),
+        }
+    }
+
+    fn parse_component(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+        let mut type_params = Vec::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        if self.match_tokens(&[TokenType::Less]) {
+            type_params = self.parse_type_parameters()?;
+        }
+
+        let mut members = ComponentMembers::default();
+        if self.check(&TokenType::LeftBrace) {
+            self.advance(); // Skip {
+            members = self.parse_component_members();
+            if self.check(&TokenType::RightBrace) {
+                self.advance(); // Skip }
+            }
+        }
+
+        Ok(ASTNode::Component {
+            name,
+            type_params,
+            props: members.props,
+            events: members.events,
+            state: members.state,
+            methods: members.methods,
+            computed: members.computed,
+            watchers: members.watchers,
+            lifecycle: members.lifecycle,
+            render: members.render,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses the `<component Name>` form after its `<`. The header is a tag
+    /// but the members are script, closed by `}` or `</component>`.
+    fn parse_component_element(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        self.advance(); // Skip component
+
+        let mut name = String::new();
+        if self.check(&TokenType::Identifier) {
+            name = self.advance().unwrap().value.to_string();
+        }
+        if !self.check(&TokenType::TagEnd) {
+            let error = self.unexpected("`>`");
+            self.report(error);
+            while !self.check(&TokenType::TagEnd) && !self.is_at_end() {
+                self.advance(); // Skip the rest of the header
+            }
+        }
+        self.match_tokens(&[TokenType::TagEnd]);
+
+        let members = self.parse_component_members();
+        if self.match_tokens(&[TokenType::ClosingTagStart]) {
+            while !self.check(&TokenType::TagEnd) && !self.is_at_end() {
+                self.advance(); // Skip the closing tag name
+            }
+            self.match_tokens(&[TokenType::TagEnd]);
+        } else {
+            self.match_tokens(&[TokenType::RightBrace]);
+        }
+
+        Ok(ASTNode::Component {
+            name,
+            type_params: Vec::new(),
+            props: members.props,
+            events: members.events,
+            state: members.state,
+            methods: members.methods,
+            computed: members.computed,
+            watchers: members.watchers,
+            lifecycle: members.lifecycle,
+            render: members.render,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses component members up to the closing `}` or `</component>`.
+    fn parse_component_members(&mut self) -> ComponentMembers {
+        let mut members = ComponentMembers::default();
+
+        while !self.check(&TokenType::RightBrace) && !self.check(&TokenType::ClosingTagStart) && !self.is_at_end() {
+            let checkpoint = self.checkpoint();
+            if let Err(error) = self.parse_component_member(&mut members) {
+                // Drop the member and carry on with the next one
+                self.report(error);
+                self.rewind(checkpoint);
+                self.skip_member();
+                self.wrap(checkpoint, SyntaxKind::Error);
+            }
+        }
+
+        members
+    }
+
+    /// Parses one component member into `members`.
+    fn parse_component_member(&mut self, members: &mut ComponentMembers) -> Result<(), Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let next_is_colon = self.tokens.get(self.current + 2)
+            .is_some_and(|token| token.token_type == TokenType::Colon);
+
+        if self.check(&TokenType::Identifier) && self.tokens[self.current].value == "prop" {
+            self.advance(); // Skip prop
+            let prop = self.parse_prop()?;
+            self.wrap(checkpoint, SyntaxKind::Prop);
+            members.props.push(prop);
+        } else if self.check(&TokenType::Event) && next_is_colon {
+            self.advance(); // Skip event
+            let event = self.parse_event_declaration()?;
+            self.wrap(checkpoint, SyntaxKind::EventDeclaration);
+            members.events.push(event);
+        } else if self.check(&TokenType::State) && next_is_colon {
+            self.advance(); // Skip state
+            let state = self.parse_state_variable(self.previous_span())?;
+            self.wrap(checkpoint, SyntaxKind::StateVariable);
+            members.state.push(state);
+        } else if self.check(&TokenType::Function) || self.check_async_function() {
+            let method = self.parse_method()?;
+            self.wrap(checkpoint, SyntaxKind::Method);
+            members.methods.push(method);
+        } else if self.match_tokens(&[TokenType::Computed]) {
+            let computed = self.parse_computed()?;
+            self.wrap(checkpoint, SyntaxKind::Computed);
+            members.computed.push(computed);
+        } else if self.match_tokens(&[TokenType::Watch]) {
+            let watch = self.parse_watch()?;
+            self.wrap(checkpoint, SyntaxKind::Watch);
+            members.watchers.push(watch);
+        } else if self.check(&TokenType::Mounted) || self.check(&TokenType::Unmounted) {
+            let lifecycle = self.parse_lifecycle()?;
+            self.wrap(checkpoint, SyntaxKind::Lifecycle);
+            members.lifecycle.push(lifecycle);
+        } else if self.match_tokens(&[TokenType::Render]) {
+            let render = self.parse_render()?;
+            self.wrap(checkpoint, SyntaxKind::Render);
+            members.render.extend(render);
+        } else {
+            return Err(self.unexpected("a component member"));
+        }
+
+        Ok(())
+    }
+
+    /// Parses `name: Type = default;` or `name?: Type;` after `prop`. A
+    /// prop without a type is `any`.
+    fn parse_prop(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.source_before(&[TokenType::Question, TokenType::Colon, TokenType::Assign, TokenType::Semicolon]);
+        let optional = self.match_tokens(&[TokenType::Question]);
+
+        let mut prop_type = TypeExpr::Primitive("any".to_string());
+        if self.match_tokens(&[TokenType::Colon]) {
+            prop_type = self.parse_type_expr();
+            self.source_before(&[TokenType::Assign, TokenType::Semicolon]); // Skip what the type grammar left
+        }
+        if optional {
+            prop_type = TypeExpr::Optional(Box::new(prop_type));
+        }
+
+        let mut default = None;
+        if self.match_tokens(&[TokenType::Assign]) {
+            default = Some(self.parse_value(&[TokenType::Semicolon]));
+        }
+        self.match_tokens(&[TokenType::Semicolon]);
+
+        Ok(ASTNode::Prop {
+            name,
+            prop_type,
+            default,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses a type: a `|` union of primaries, each optionally followed by
+    /// `[]` or `?`.
+    /// A type nested too deeply is reported and read as `any`.
+    fn parse_type_expr(&mut self) -> TypeExpr {
+        let parsed = self.nested(|parser| {
+            parser.match_tokens(&[TokenType::Pipe]); // Leading | of a multi-line union
+            let mut members = vec![parser.parse_postfix_type()];
+            while parser.match_tokens(&[TokenType::Pipe]) {
+                members.push(parser.parse_postfix_type());
+            }
+
+            if members.len() == 1 {
+                Ok(members.pop().unwrap())
+            } else {
+                Ok(TypeExpr::Union(members))
+            }
+        });
+        parsed.unwrap_or_else(|_| TypeExpr::Primitive("any".to_string()))
+    }
+
+    fn parse_postfix_type(&mut self) -> TypeExpr {
+        let mut type_expr = self.parse_primary_type();
+        loop {
+            if self.check(&TokenType::LeftBracket)
+                && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::RightBracket)
+            {
+                self.advance(); // Skip [
+                self.advance(); // Skip ]
+                type_expr = TypeExpr::Array(Box::new(type_expr));
+            } else if self.match_tokens(&[TokenType::Question]) {
+                type_expr = TypeExpr::Optional(Box::new(type_expr));
+            } else {
+                return type_expr;
+            }
+        }
+    }
+
+    fn parse_primary_type(&mut self) -> TypeExpr {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return TypeExpr::Primitive("any".to_string()),
+        };
+
+        match token.token_type {
+            TokenType::String => {
+                self.advance();
+                TypeExpr::Literal(Value::String(unescape_string(token.value)))
+            }
+            TokenType::Number => {
+                self.advance();
+                match parse_number_value(token.value) {
+                    Some(number) => TypeExpr::Literal(Value::Number(number)),
+                    None => TypeExpr::Primitive("number".to_string()),
+                }
+            }
+            TokenType::True | TokenType::False => {
+                self.advance();
+                TypeExpr::Literal(Value::Boolean(token.token_type == TokenType::True))
+            }
+            TokenType::Null | TokenType::Undefined => {
+                self.advance();
+                TypeExpr::Primitive(token.value.to_string())
+            }
+            TokenType::Identifier => {
+                self.advance();
+                let mut name = token.value.to_string();
+                // Qualified names: `Emadocs.Component`
+                while self.check(&TokenType::Dot)
+                    && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::Identifier)
+                {
+                    self.advance(); // Skip .
+                    name.push('.');
+                    name.push_str(self.advance().unwrap().value);
+                }
+
+                if self.match_tokens(&[TokenType::Less]) {
+                    let mut arguments = Vec::new();
+                    while !self.check(&TokenType::Greater) && !self.is_at_end() {
+                        let before = self.current;
+                        arguments.push(self.parse_type_expr());
+                        if !self.match_tokens(&[TokenType::Comma]) && self.current == before {
+                            self.advance(); // Skip a token no type starts with
+                        }
+                    }
+                    self.match_tokens(&[TokenType::Greater]);
+                    TypeExpr::Generic { name, arguments }
+                } else if PRIMITIVE_TYPES.contains(&name.as_str()) {
+                    TypeExpr::Primitive(name)
+                } else {
+                    TypeExpr::Named(name)
+                }
+            }
+            TokenType::LeftParen if self.at_arrow_parameters(self.current) => {
+                self.advance(); // Skip (
+                let parameters = self.parse_type_parameter_list();
+                self.match_tokens(&[TokenType::Arrow]);
+                TypeExpr::Function {
+                    parameters,
+                    return_type: Box::new(self.parse_type_expr()),
+                }
+            }
+            TokenType::LeftBrace => {
+                self.advance(); // Skip {
+                TypeExpr::Object(self.parse_type_fields())
+            }
+            TokenType::LeftParen => {
+                self.advance(); // Skip (
+                let inner = self.parse_type_expr();
+                self.source_before(&[TokenType::RightParen]);
+                self.match_tokens(&[TokenType::RightParen]);
+                inner
+            }
+            _ => {
+                // Not in the type grammar; keep its source as a name
+                let source = self.source_before(&[
+                    TokenType::Pipe, TokenType::Assign, TokenType::Semicolon,
+                    TokenType::Comma, TokenType::Greater,
+                ]);
+                if source.is_empty() {
+                    TypeExpr::Primitive("any".to_string())
+                } else {
+                    TypeExpr::Named(source)
+                }
+            }
+        }
+    }
+
+    /// Parses the parameters of a function type after its `(`, through the
+    /// `)`.
+    fn parse_type_parameter_list(&mut self) -> Vec<(String, TypeExpr)> {
+        let mut parameters = Vec::new();
+        while !self.check(&TokenType::RightParen) && !self.is_at_end() {
+            let before = self.current;
+            let name = self.source_before(&[TokenType::Question, TokenType::Colon, TokenType::Comma, TokenType::RightParen]);
+            let optional = self.match_tokens(&[TokenType::Question]);
+            let mut parameter_type = TypeExpr::Primitive("any".to_string());
+            if self.match_tokens(&[TokenType::Colon]) {
+                parameter_type = self.parse_type_expr();
+            }
+            if optional {
+                parameter_type = TypeExpr::Optional(Box::new(parameter_type));
+            }
+            parameters.push((name, parameter_type));
+            if !self.match_tokens(&[TokenType::Comma]) {
+                self.source_before(&[TokenType::RightParen]);
+            }
+            if self.current == before {
+                break; // An unmatched bracket; leave it to the caller
+            }
+        }
+        self.match_tokens(&[TokenType::RightParen]);
+        parameters
+    }
+
+    /// Parses the fields of an object type or interface after its `{`,
+    /// through the `}`: `name: Type`, `name?: Type`, `[key: string]: Type`
+    /// and methods like `save(todo: Todo): void`, separated by `;` or `,`.
+    fn parse_type_fields(&mut self) -> Vec<(String, TypeExpr)> {
+        let mut fields = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                continue;
+            }
+            let before = self.current;
+
+            let name = if self.match_tokens(&[TokenType::LeftBracket]) {
+                let key = self.source_before(&[]);
+                self.match_tokens(&[TokenType::RightBracket]);
+                format!("[{}]", key)
+            } else {
+                self.source_before(&[
+                    TokenType::Question, TokenType::Colon, TokenType::LeftParen,
+                    TokenType::Semicolon, TokenType::Comma,
+                ])
+            };
+            let optional = self.match_tokens(&[TokenType::Question]);
+
+            let mut field_type = TypeExpr::Primitive("any".to_string());
+            if self.match_tokens(&[TokenType::LeftParen]) {
+                let parameters = self.parse_type_parameter_list();
+                let mut return_type = TypeExpr::Primitive("void".to_string());
+                if self.match_tokens(&[TokenType::Colon]) {
+                    return_type = self.parse_type_expr();
+                }
+                field_type = TypeExpr::Function { parameters, return_type: Box::new(return_type) };
+            } else if self.match_tokens(&[TokenType::Colon]) {
+                field_type = self.parse_type_expr();
+            }
+            if optional {
+                field_type = TypeExpr::Optional(Box::new(field_type));
+            }
+            fields.push((name, field_type));
+
+            if self.current == before {
+                break; // An unmatched bracket; leave it to the caller
+            }
+        }
+        self.match_tokens(&[TokenType::RightBrace]);
+        fields
+    }
+
+    /// Parses the type parameters of a declaration after its `<`, through
+    /// the `>`.
+    fn parse_type_parameters(&mut self) -> Result<Vec<TypeParameter>, Diagnostic> {
+        let mut parameters = Vec::new();
+        while !self.match_tokens(&[TokenType::Greater]) {
+            let name = self.expect_word("a type parameter")?;
+            let mut constraint = None;
+            if self.match_tokens(&[TokenType::Extends]) {
+                constraint = Some(self.parse_type_expr());
+            }
+            let mut default = None;
+            if self.match_tokens(&[TokenType::Assign]) {
+                default = Some(self.parse_type_expr());
+            }
+            parameters.push(TypeParameter { name, constraint, default });
+            if !self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::Greater) {
+                return Err(self.unexpected("`,` or `>`"));
+            }
+        }
+        Ok(parameters)
+    }
+
+    /// Whether the `(` at token `start` opens the parameters of a function
+    /// type or an arrow function, that is, its `)` is followed by `=>`.
+    fn at_arrow_parameters(&self, start: usize) -> bool {
+        let mut depth = 0usize;
+        for (index, token) in self.tokens[start..].iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.tokens.get(start + index + 1)
+                            .is_some_and(|token| token.token_type == TokenType::Arrow);
+                    }
+                }
+                TokenType::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Parses a value up to one of `closers`. Literals, arrays and objects
+    /// of literals become structured values; anything else is kept as an
+    /// expression.
+    fn parse_value(&mut self, closers: &[TokenType]) -> Value {
+        let start = self.current;
+        if let Some(value) = self.parse_literal_value() {
+            if self.is_at_end() || closers.iter().any(|closer| self.check(closer)) {
+                return value;
+            }
+        }
+
+        // Not a plain literal; take the whole expression instead
+        self.current = start;
+        Value::Expression(self.source_before(closers))
+    }
+
+    /// `parse_literal_value` one level deeper; a value nested too deeply
+    /// isn't taken as a literal.
+    fn parse_nested_literal_value(&mut self) -> Option<Value> {
+        self.nested(|parser| Ok(parser.parse_literal_value())).ok().flatten()
+    }
+
+    fn parse_literal_value(&mut self) -> Option<Value> {
+        let token = self.peek()?.clone();
+        let value = match token.token_type {
+            TokenType::String => Value::String(unescape_string(token.value)),
+            TokenType::Number => Value::Number(parse_number_value(token.value)?),
+            TokenType::Minus => {
+                self.advance(); // Skip -
+                return match self.parse_nested_literal_value()? {
+                    Value::Number(number) => Some(Value::Number(-number)),
+                    _ => None,
+                };
+            }
+            TokenType::True => Value::Boolean(true),
+            TokenType::False => Value::Boolean(false),
+            TokenType::Null => Value::Null,
+            TokenType::Undefined => Value::Undefined,
+            TokenType::Identifier => {
+                self.advance();
+                let mut name = token.value.to_string();
+                while self.check(&TokenType::Dot)
+                    && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::Identifier)
+                {
+                    self.advance(); // Skip .
+                    name.push('.');
+                    name.push_str(self.advance().unwrap().value);
+                }
+                return Some(Value::Reference(name));
+            }
+            TokenType::LeftBracket => {
+                self.advance(); // Skip [
+                let mut items = Vec::new();
+                while !self.match_tokens(&[TokenType::RightBracket]) {
+                    items.push(self.parse_nested_literal_value()?);
+                    if !self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::RightBracket) {
+                        return None;
+                    }
+                }
+                return Some(Value::Array(items));
+            }
+            TokenType::LeftBrace => {
+                self.advance(); // Skip {
+                let mut entries = Vec::new();
+                while !self.match_tokens(&[TokenType::RightBrace]) {
+                    let key = self.advance()?.clone();
+                    let key = match key.token_type {
+                        TokenType::RightBrace | TokenType::Colon | TokenType::Eof => return None,
+                        _ => key.value.to_string(),
+                    };
+                    if !self.match_tokens(&[TokenType::Colon]) {
+                        return None;
+                    }
+                    entries.push((key, self.parse_nested_literal_value()?));
+                    if !self.match_tokens(&[TokenType::Comma, TokenType::Semicolon]) && !self.check(&TokenType::RightBrace) {
+                        return None;
+                    }
+                }
+                return Some(Value::Object(entries));
+            }
+            _ => return None,
+        };
+        self.advance();
+        Some(value)
+    }
+
+    /// Parses `name: (args) => void;` after `event`.
+    fn parse_event_declaration(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.source_before(&[TokenType::Colon, TokenType::Semicolon]);
+
+        let mut signature = TypeExpr::Primitive("any".to_string());
+        if self.match_tokens(&[TokenType::Colon]) {
+            signature = self.parse_type_expr();
+            self.source_before(&[TokenType::Semicolon]); // Skip what the type grammar left
+        }
+        self.match_tokens(&[TokenType::Semicolon]);
+
+        Ok(ASTNode::EventDeclaration {
+            name,
+            signature,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses `name: Type = initial;` after `state`.
+    fn parse_state_variable(&mut self, start: Span) -> Result<ASTNode, Diagnostic> {
+        let name = self.source_before(&[TokenType::Colon, TokenType::Assign, TokenType::Semicolon]);
+        if name.is_empty() {
+            return Err(self.unexpected("a state variable name"));
+        }
+
+        let mut type_annotation = None;
+        if self.match_tokens(&[TokenType::Colon]) {
+            type_annotation = Some(self.parse_type_expr());
+            self.source_before(&[TokenType::Assign, TokenType::Semicolon]); // Skip what the type grammar left
+        }
+        let mut initial = None;
+        if self.match_tokens(&[TokenType::Assign]) {
+            initial = Some(self.parse_expression_before(&[TokenType::Semicolon]));
+        }
+        self.match_tokens(&[TokenType::Semicolon]);
+
+        Ok(ASTNode::StateVariable {
+            name,
+            type_annotation,
+            initial,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses `[async] function name(parameters) { ... }`.
+    fn parse_method(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.tokens[self.current].span.clone();
+        let is_async = self.match_tokens(&[TokenType::Async]);
+        self.match_tokens(&[TokenType::Function]); // Absent for `init() { ... }` in a plugin
+
+        let mut name = String::new();
+        if self.check(&TokenType::Identifier) {
+            name = self.advance().unwrap().value.to_string();
+        }
+        if self.match_tokens(&[TokenType::Less]) {
+            self.parse_type_parameters()?; // Types aren't emitted
+        }
+
+        let mut parameters = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            parameters = self.parse_parameters()?;
+            self.match_tokens(&[TokenType::RightParen]);
+        }
+        if self.match_tokens(&[TokenType::Colon]) {
+            self.source_before(&[TokenType::LeftBrace]); // Skip the return type
+        }
+
+        let mut body = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            body = self.parse_body()?;
+        }
+
+        Ok(ASTNode::Method {
+            name,
+            is_async,
+            parameters,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses `name() { ... }` after `computed`. The parentheses and a
+    /// return type are optional.
+    fn parse_computed(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.expect_word("a computed name")?;
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            self.expect(TokenType::RightParen, "`)`")?;
+        }
+        if self.match_tokens(&[TokenType::Colon]) {
+            self.source_before(&[TokenType::LeftBrace]); // Skip the return type
+        }
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let body = self.parse_body()?;
+
+        Ok(ASTNode::Computed {
+            name,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses `name(value, previous) { ... }` after `watch`, where `name`
+    /// is a state variable of the component.
+    fn parse_watch(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let target = self.expect_word("a state name")?;
+
+        let mut parameters = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            parameters = self.parse_parameters()?;
+            self.expect(TokenType::RightParen, "`)`")?;
+        }
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let body = self.parse_body()?;
+
+        Ok(ASTNode::Watch {
+            target,
+            parameters,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses `mounted() { ... }` or `unmounted() { ... }`; the parentheses
+    /// are optional.
+    fn parse_lifecycle(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.tokens[self.current].span.clone();
+        let hook = match self.advance().unwrap().token_type {
+            TokenType::Mounted => LifecycleHook::Mounted,
+            _ => LifecycleHook::Unmounted,
+        };
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            self.expect(TokenType::RightParen, "`)`")?;
+        }
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let body = self.parse_body()?;
+
+        Ok(ASTNode::Lifecycle {
+            hook,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Parses `{ <markup /> }` or `() { <markup /> }` after `render` and
+    /// returns its root element.
+    /// Parses the root elements of a `render { ... }` or `render() { ... }`
+    /// block.
+    fn parse_render(&mut self) -> Result<Vec<ASTNode>, Diagnostic> {
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            self.source_before(&[]);
+            self.match_tokens(&[TokenType::RightParen]);
+        }
+
+        let mut roots = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                if self.match_tokens(&[TokenType::OpeningTagStart]) {
+                    roots.push(self.parse_element()?);
+                } else {
+                    let error = self.unexpected("markup");
+                    self.report(error);
+                    self.advance();
+                }
+            }
+            self.match_tokens(&[TokenType::RightBrace]);
+        }
+
+        Ok(roots)
+    }
+
+    fn parse_style(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        // `style @media (...) { ... }` is a top-level at-rule
+        if self.check(&TokenType::At) {
+            return self.parse_at_rule();
+        }
+        let selectors = self.parse_selector_list();
+
+        let mut rules = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            rules = self.parse_css_block()?;
+        }
+
+        Ok(ASTNode::Style { selectors, rules, span: self.span_from(&start) })
+    }
+
+    fn parse_event(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut event_type = String::new();
+        let mut target = Vec::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                event_type = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier && token.value == "on" {
+                self.advance(); // Skip "on"
+                target = self.parse_selector_list();
+            }
+        }
+
+        let mut parameters = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            parameters = self.parse_parameters()?;
+            self.match_tokens(&[TokenType::RightParen]);
+        }
+
+        let mut body = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            body = self.parse_body()?;
+        }
+
+        Ok(ASTNode::Event {
+            event_type,
+            target,
+            parameters,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_state(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        let mut properties = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            while !self.match_tokens(&[TokenType::RightBrace]) {
+                if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                    continue;
+                }
+                let checkpoint = self.checkpoint();
+                let start = self.tokens[self.current].span.clone();
+                properties.push(self.parse_state_variable(start)?);
+                self.wrap(checkpoint, SyntaxKind::StateVariable);
+            }
+        }
+
+        Ok(ASTNode::State { name, properties, span: self.span_from(&start) })
+    }
+
+    fn parse_api(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        let mut properties = Vec::new();
+        let mut methods = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            while !self.match_tokens(&[TokenType::RightBrace]) {
+                if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                    continue;
+                }
+                let checkpoint = self.checkpoint();
+                let method_at = if self.check(&TokenType::Async) { self.current + 2 } else { self.current + 1 };
+                if self.check(&TokenType::Function)
+                    || self.tokens.get(method_at).is_some_and(|token| token.token_type == TokenType::LeftParen)
+                {
+                    methods.push(self.parse_method()?);
+                    self.wrap(checkpoint, SyntaxKind::Method);
+                } else {
+                    properties.push(self.parse_property()?);
+                }
+            }
+        }
+
+        Ok(ASTNode::Api {
+            name,
+            properties,
+            methods,
+            span: self.span_from(&start),
+        })
+    }
 
-    fn match_tokens(&mut self, token_types: &[TokenType]) -> bool {
-        for token_type in token_types {
-            if self.check(token_type) {
+    fn parse_router(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut routes = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                    continue;
+                }
+                routes.push(self.parse_route()?);
+            }
+            self.match_tokens(&[TokenType::RightBrace]);
+        }
+
+        Ok(ASTNode::Router { routes, span: self.span_from(&start) })
+    }
+
+    /// Parses `route "/path" -> Component;`, optionally followed by
+    /// `{ guard: requireAuth, layout: "main" }`.
+    fn parse_route(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens[self.current].span.clone();
+        self.expect(TokenType::Route, "`route`")?;
+
+        let path = match self.peek() {
+            Some(token) if token.token_type == TokenType::String => token.value.to_string(),
+            _ => return Err(self.unexpected("a route path")),
+        };
+        self.advance();
+
+        self.expect(TokenType::Minus, "`->`")?;
+        self.expect(TokenType::Greater, "`->`")?;
+        let component = match self.peek() {
+            Some(token) if matches!(token.token_type, TokenType::String | TokenType::Identifier) => token.value.to_string(),
+            _ => return Err(self.unexpected("a component")),
+        };
+        self.advance();
+
+        let mut options = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                    continue;
+                }
+                let name = self.expect_word("an option name")?;
+                self.expect(TokenType::Colon, "`:`")?;
+                let value = self.parse_expression_before(&[TokenType::Comma, TokenType::Semicolon, TokenType::RightBrace]);
+                options.push((name, value));
+            }
+            self.expect(TokenType::RightBrace, "`}`")?;
+        }
+        self.match_tokens(&[TokenType::Semicolon]);
+        self.wrap(checkpoint, SyntaxKind::Route);
+
+        let params = route_params(&path);
+        Ok(ASTNode::Route { path, params, component, options, span: self.span_from(&start) })
+    }
+
+    /// Parses `<router>` markup: each `<route>` child becomes a route.
+    fn parse_router_element(&mut self) -> Result<ASTNode, Diagnostic> {
+        let (children, span) = match self.parse_tag()? {
+            ASTNode::Element { children, span, .. } => (children, span),
+            other => return Ok(other),
+        };
+
+        let mut routes = Vec::new();
+        for child in children {
+            match child {
+                ASTNode::Element { tag_name, attributes, span, .. } if tag_name == "route" => {
+                    routes.push(route_from_attributes(attributes, span)?);
+                }
+                ASTNode::Element { tag_name, span, .. } => {
+                    return Err(Diagnostic::new(codes::INVALID_ROUTE, format!("expected `<route>`, found `<{}>`", tag_name), span));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ASTNode::Router { routes, span })
+    }
+
+    fn parse_layout(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        let mut render = Vec::new();
+        if self.check(&TokenType::LeftBrace) {
+            self.advance(); // Skip {
+            render = self.parse_component_members().render;
+            if self.check(&TokenType::RightBrace) {
+                self.advance(); // Skip }
+            }
+        }
+
+        Ok(ASTNode::Layout { name, render, span: self.span_from(&start) })
+    }
+
+    fn parse_animation(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        let mut keyframes = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            keyframes = self.parse_keyframes()?;
+        }
+
+        Ok(ASTNode::Animation { name, keyframes, span: self.span_from(&start) })
+    }
+
+    fn parse_type(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.expect_word("a type name")?;
+
+        let mut type_params = Vec::new();
+        if self.match_tokens(&[TokenType::Less]) {
+            type_params = self.parse_type_parameters()?;
+        }
+
+        // `type User { ... }` declares an object type without the `=`
+        let definition = if self.match_tokens(&[TokenType::Assign]) || self.check(&TokenType::LeftBrace) {
+            self.parse_type_expr()
+        } else {
+            return Err(self.unexpected("`=`"));
+        };
+        self.match_tokens(&[TokenType::Semicolon]);
+
+        Ok(ASTNode::Type { name, type_params, definition, span: self.span_from(&start) })
+    }
+
+    fn parse_interface(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.expect_word("an interface name")?;
+
+        let mut type_params = Vec::new();
+        if self.match_tokens(&[TokenType::Less]) {
+            type_params = self.parse_type_parameters()?;
+        }
+
+        let mut extends = Vec::new();
+        if self.match_tokens(&[TokenType::Extends]) {
+            loop {
+                extends.push(self.parse_postfix_type());
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let fields = self.parse_type_fields();
+
+        Ok(ASTNode::Interface { name, type_params, extends, fields, span: self.span_from(&start) })
+    }
+
+    fn parse_enum(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.expect_word("an enum name")?;
+        self.expect(TokenType::LeftBrace, "`{`")?;
+
+        let mut members: Vec<EnumMember> = Vec::new();
+        while !self.match_tokens(&[TokenType::RightBrace]) {
+            let member = match self.peek() {
+                Some(token) if token.token_type == TokenType::String => unescape_string(token.value),
+                Some(token) if is_word(&token.token_type) => token.value.to_string(),
+                _ => return Err(self.unexpected("an enum member")),
+            };
+            let span = self.advance().unwrap().span.clone();
+            if members.iter().any(|previous| previous.name == member) {
+                self.report(Diagnostic::new(
+                    codes::DUPLICATE_ENUM_MEMBER,
+                    format!("enum member `{}` is declared more than once", member),
+                    span.clone(),
+                ));
+            }
+
+            let mut value = None;
+            if self.match_tokens(&[TokenType::Assign]) {
+                value = Some(self.parse_expression_before(&[TokenType::Comma, TokenType::RightBrace]));
+            }
+            members.push(EnumMember { name: member, value, span });
+
+            if !self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::RightBrace) {
+                return Err(self.unexpected("`,` or `}`"));
+            }
+        }
+
+        Ok(ASTNode::Enum { name, members, span: self.span_from(&start) })
+    }
+
+    fn parse_hook(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut name = String::new();
+
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Identifier {
+                name = self.advance().unwrap().value.to_string();
+            }
+        }
+
+        let mut parameters = Vec::new();
+        if self.check(&TokenType::LeftParen) {
+            self.advance(); // Skip (
+            parameters = self.parse_parameters()?;
+            if self.check(&TokenType::RightParen) {
+                self.advance(); // Skip )
+            }
+        }
+
+        let mut body = Vec::new();
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            if self.at_hook_member() {
+                self.parse_hook_members(&mut parameters, &mut body)?;
+            } else {
+                body = self.parse_body()?;
+            }
+        }
+
+        Ok(ASTNode::Hook {
+            name,
+            parameters,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Whether a hook is written as `parameters: [...]; body: { ... }`
+    /// members rather than a body.
+    fn at_hook_member(&self) -> bool {
+        self.peek().is_some_and(|token| matches!(token.value, "parameters" | "body"))
+            && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::Colon)
+    }
+
+    /// Parses `parameters: [...];` and `body: { ... }` through the hook's `}`.
+    fn parse_hook_members(&mut self, parameters: &mut Vec<ASTNode>, body: &mut Vec<Stmt>) -> Result<(), Diagnostic> {
+        while !self.match_tokens(&[TokenType::RightBrace]) && !self.is_at_end() {
+            if !self.at_hook_member() {
+                self.advance(); // Skip ; and anything unknown
+                continue;
+            }
+            let member = self.advance().unwrap().value;
+            self.advance(); // Skip :
+            if member == "parameters" && self.match_tokens(&[TokenType::LeftBracket]) {
+                *parameters = self.parse_parameters()?;
+                self.match_tokens(&[TokenType::RightBracket]);
+            } else if member == "body" && self.match_tokens(&[TokenType::LeftBrace]) {
+                *body = self.parse_body()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_plugin(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let name = self.expect_word("a plugin name")?;
+        self.expect(TokenType::LeftBrace, "`{`")?;
+
+        let mut properties = Vec::new();
+        let mut signatures = Vec::new();
+        let mut methods = Vec::new();
+        while !self.match_tokens(&[TokenType::RightBrace]) {
+            if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                continue;
+            }
+
+            let checkpoint = self.checkpoint();
+            let method_at = if self.check(&TokenType::Async) { self.current + 2 } else { self.current + 1 };
+            let is_method = self.check(&TokenType::Function)
+                || self.tokens.get(method_at).is_some_and(|token| token.token_type == TokenType::LeftParen);
+            if is_method {
+                methods.push(self.parse_method()?);
+                self.wrap(checkpoint, SyntaxKind::Method);
+            } else if self.peek().is_some_and(|token| token.value == "methods")
+                && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::Colon)
+                && self.tokens.get(self.current + 2).is_some_and(|token| token.token_type == TokenType::LeftBrace)
+            {
+                self.advance(); // Skip methods
+                self.advance(); // Skip :
+                self.advance(); // Skip {
+                signatures.extend(self.parse_type_fields());
+            } else {
+                properties.push(self.parse_property()?);
+            }
+        }
+
+        Ok(ASTNode::Plugin { name, properties, signatures, methods, span: self.span_from(&start) })
+    }
+
+    fn parse_config(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        self.expect(TokenType::LeftBrace, "`{`")?;
+
+        let mut properties = Vec::new();
+        while !self.match_tokens(&[TokenType::RightBrace]) {
+            if self.match_tokens(&[TokenType::Semicolon, TokenType::Comma]) {
+                continue;
+            }
+            properties.push(self.parse_property()?);
+        }
+
+        Ok(ASTNode::Config { properties, span: self.span_from(&start) })
+    }
+
+    /// Parses `name: value` in a config or plugin block.
+    fn parse_property(&mut self) -> Result<(String, Value), Diagnostic> {
+        let name = match self.peek() {
+            Some(token) if token.token_type == TokenType::String => unescape_string(token.value),
+            Some(token) if is_word(&token.token_type) => token.value.to_string(),
+            _ => return Err(self.unexpected("a property name")),
+        };
+        self.advance();
+        self.expect(TokenType::Colon, "`:`")?;
+
+        let value = self.parse_value(&[TokenType::Semicolon, TokenType::Comma, TokenType::RightBrace]);
+        if let Value::Expression(source) = &value {
+            if source.is_empty() {
+                return Err(self.unexpected("a value"));
+            }
+        }
+        Ok((name, value))
+    }
+
+    fn parse_import(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut default = None;
+        let mut namespace = None;
+        let mut specifiers = Vec::new();
+
+        // `import "./theme.css";` has no bindings
+        if !self.check(&TokenType::String) {
+            if !self.check(&TokenType::LeftBrace) && !self.check(&TokenType::Multiply) {
+                default = Some(self.expect_word("an import binding")?);
+            }
+            if default.is_none() || self.match_tokens(&[TokenType::Comma]) {
+                if self.match_tokens(&[TokenType::Multiply]) {
+                    self.expect(TokenType::As, "`as`")?;
+                    namespace = Some(self.expect_word("a namespace name")?);
+                } else {
+                    let brace = self.tokens[self.current].span.clone();
+                    self.expect(TokenType::LeftBrace, "`{` or `*`")?;
+                    specifiers = self.parse_module_specifiers()?;
+                    // A string names an export, but a binding needs a name
+                    if let Some(specifier) = specifiers.iter().find(|specifier| specifier.alias.is_none() && !is_identifier_name(&specifier.name)) {
+                        let message = format!("import of \"{}\" needs `as` and a name", specifier.name);
+                        let span = self.span_from(&brace);
+                        self.report(Diagnostic::new(codes::UNEXPECTED_TOKEN, message, span));
+                    }
+                }
+            }
+            self.expect(TokenType::From, "`from`")?;
+        }
+
+        let source = self.parse_module_source()?;
+        self.match_tokens(&[TokenType::Semicolon]);
+
+        Ok(ASTNode::Import { default, namespace, specifiers, source, span: self.span_from(&start) })
+    }
+
+    fn parse_export(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let is_default = self.peek().is_some_and(|token| token.token_type == TokenType::Identifier && token.value == "default");
+        if is_default {
+            self.advance(); // Skip default
+        }
+
+        let mut source = String::new();
+        let clause = if !is_default && self.match_tokens(&[TokenType::Multiply]) {
+            let mut namespace = None;
+            if self.match_tokens(&[TokenType::As]) {
+                namespace = Some(self.expect_word("a namespace name")?);
+            }
+            self.expect(TokenType::From, "`from`")?;
+            source = self.parse_module_source()?;
+            ExportClause::All(namespace)
+        } else if !is_default && self.match_tokens(&[TokenType::LeftBrace]) {
+            let specifiers = self.parse_module_specifiers()?;
+            if self.match_tokens(&[TokenType::From]) {
+                source = self.parse_module_source()?;
+            }
+            ExportClause::Named(specifiers)
+        } else if !is_default && (self.check(&TokenType::Const) || self.check(&TokenType::Let) || self.check(&TokenType::Var)) {
+            return Ok(ASTNode::Export {
+                clause: ExportClause::Variable(self.nested(Self::parse_stmt)?),
+                source,
+                is_default,
+                span: self.span_from(&start),
+            });
+        } else if self.at_exported_declaration() {
+            ExportClause::Declaration(Box::new(self.parse_statement()?))
+        } else if is_default {
+            ExportClause::Expression(self.parse_expression_before(&[TokenType::Semicolon]))
+        } else {
+            return Err(self.unexpected("`{`, `*` or a declaration"));
+        };
+        self.match_tokens(&[TokenType::Semicolon]);
+
+        Ok(ASTNode::Export { clause, source, is_default, span: self.span_from(&start) })
+    }
+
+    /// Whether a declaration `parse_statement` knows follows `export`.
+    fn at_exported_declaration(&self) -> bool {
+        matches!(
+            self.peek().map(|token| &token.token_type),
+            Some(TokenType::Function | TokenType::Component | TokenType::Type | TokenType::Interface
+                | TokenType::Enum | TokenType::Hook | TokenType::State | TokenType::Plugin
+                | TokenType::Layout | TokenType::Event | TokenType::Animation)
+        ) || self.check_async_function()
+    }
+
+    /// Parses `a, b as c }` after the `{` of an import or export list.
+    fn parse_module_specifiers(&mut self) -> Result<Vec<ModuleSpecifier>, Diagnostic> {
+        let mut specifiers = Vec::new();
+        while !self.match_tokens(&[TokenType::RightBrace]) {
+            let name = self.expect_module_name("a name")?;
+            let mut alias = None;
+            if self.match_tokens(&[TokenType::As]) {
+                alias = Some(self.expect_module_name("an alias")?);
+            }
+            specifiers.push(ModuleSpecifier { name, alias });
+            if !self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::RightBrace) {
+                return Err(self.unexpected("`,` or `}`"));
+            }
+        }
+        Ok(specifiers)
+    }
+
+    /// A name in an import or export list: a word, or a string for a name
+    /// that isn't an identifier.
+    fn expect_module_name(&mut self, expected: &str) -> Result<String, Diagnostic> {
+        match self.peek() {
+            Some(token) if token.token_type == TokenType::String => {
+                let name = unescape_string(token.value);
+                self.advance();
+                Ok(name)
+            }
+            _ => self.expect_word(expected),
+        }
+    }
+
+    /// The module path after `from`, or after `import` in a side-effect
+    /// import.
+    fn parse_module_source(&mut self) -> Result<String, Diagnostic> {
+        match self.peek() {
+            Some(token) if token.token_type == TokenType::String => {
+                let source = unescape_string(token.value);
+                self.advance();
+                Ok(source)
+            }
+            _ => Err(self.unexpected("a module path")),
+        }
+    }
+
+    /// Parses an element whose `<` has already been matched: its attributes
+    /// and, unless it is void or self-closing, its children up to `</...>`.
+    fn parse_element(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint_previous();
+        let element = self.nested(Self::parse_tag)?;
+        self.wrap(checkpoint, SyntaxKind::Element);
+        Ok(element)
+    }
+
+    /// The element itself, without recording a syntax node for it.
+    fn parse_tag(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let mut tag_name = String::new();
+
+        if self.check(&TokenType::Identifier) {
+            tag_name = self.advance().unwrap().value.to_string();
+        } else if self.match_tokens(&[TokenType::InterpolationStart]) {
+            // Dynamic tag: <[[type]]>
+            tag_name = format!("[[{}]]", self.source_until(&TokenType::InterpolationEnd));
+        }
+
+        let mut attributes = Vec::new();
+        while !self.check(&TokenType::TagEnd) && !self.check(&TokenType::SelfClosingTagEnd) && !self.is_at_end() {
+            if self.check(&TokenType::Identifier) {
+                attributes.push(self.parse_attribute()?);
+            } else {
+                let error = self.unexpected("an attribute");
+                self.report(error);
+                self.advance();
+            }
+        }
+
+        let self_closing = self.match_tokens(&[TokenType::SelfClosingTagEnd]);
+        let mut children = Vec::new();
+        if !self_closing {
+            self.match_tokens(&[TokenType::TagEnd]);
+            if !is_void_tag(&tag_name) {
+                self.open_elements.push(tag_name.clone());
+                let parsed = self.parse_markup_children();
+                self.open_elements.pop();
+                children = parsed?;
+                self.parse_closing_tag(&tag_name, &start);
+            }
+        }
+
+        Ok(ASTNode::Element {
+            tag_name,
+            attributes,
+            children,
+            self_closing,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Consumes the `</name>` closing the element `tag_name` opened at
+    /// `start`, or reports the element as never closed when the input ends
+    /// or an enclosing element's closing tag comes first.
+    fn parse_closing_tag(&mut self, tag_name: &str, start: &Span) {
+        if self.closing_tag_name().is_some_and(|closing| closing.eq_ignore_ascii_case(tag_name)) {
+            while !self.check(&TokenType::TagEnd) && !self.is_at_end() {
+                self.advance(); // Skip the closing tag name
+            }
+            self.match_tokens(&[TokenType::TagEnd]);
+        } else {
+            let message = format!("`<{}>` is never closed", tag_name);
+            self.report(Diagnostic::new(codes::MISMATCHED_TAG, message, start.clone()));
+        }
+    }
+
+    /// The name in the `</name>` here, if a closing tag starts here.
+    fn closing_tag_name(&self) -> Option<String> {
+        if !self.check(&TokenType::ClosingTagStart) {
+            return None;
+        }
+        let from = self.current + 1;
+        let to = self.tokens[from..]
+            .iter()
+            .position(|token| matches!(token.token_type, TokenType::TagEnd | TokenType::Eof))
+            .map_or(self.tokens.len(), |offset| from + offset);
+        Some(tokens_to_source(&self.tokens[from..to]))
+    }
+
+    /// Skips a closing tag that closes none of the open elements, reporting
+    /// it. Returns false for any other token.
+    fn skip_stray_closing_tag(&mut self) -> bool {
+        let closing = match self.closing_tag_name() {
+            Some(closing) if !self.open_elements.is_empty() => closing,
+            _ => return false,
+        };
+        if self.open_elements.iter().any(|open| open.eq_ignore_ascii_case(&closing)) {
+            return false;
+        }
+
+        let span = self.tokens[self.current].span.clone();
+        let expected = self.open_elements.last().cloned().unwrap_or_default();
+        let message = format!("expected `</{}>`, found `</{}>`", expected, closing);
+        self.report(Diagnostic::new(codes::MISMATCHED_TAG, message, span));
+        while !self.check(&TokenType::TagEnd) && !self.is_at_end() {
+            self.advance();
+        }
+        self.match_tokens(&[TokenType::TagEnd]);
+        true
+    }
+
+    fn parse_markup_children(&mut self) -> Result<Vec<ASTNode>, Diagnostic> {
+        let mut children: Vec<ASTNode> = Vec::new();
+
+        while !self.is_at_end() {
+            if self.skip_stray_closing_tag() {
+                continue;
+            }
+            if self.check(&TokenType::ClosingTagStart) {
+                break;
+            }
+            let start = self.tokens[self.current].span.clone();
+            let child = if self.match_tokens(&[TokenType::Text]) {
+                ASTNode::Text { value: self.previous().map(|token| token.value.to_string()).unwrap_or_default(), span: start.clone() }
+            } else if self.match_tokens(&[TokenType::InterpolationStart]) {
+                self.parse_interpolation()?
+            } else if self.match_tokens(&[TokenType::OpeningTagStart]) {
+                self.parse_element()?
+            } else if self.match_tokens(&[TokenType::LeftBrace]) {
+                let checkpoint = self.checkpoint_previous();
+                let expression = self.parse_expression_before(&[TokenType::RightBrace]);
+                self.match_tokens(&[TokenType::RightBrace]);
+                self.wrap(checkpoint, SyntaxKind::Expression);
+                ASTNode::Expression { expression, span: self.span_from(&start) }
+            } else {
+                let error = self.unexpected("markup");
+                self.report(error);
+                self.advance();
+                continue;
+            };
+
+            // Whitespace went with the trivia; keep one space where it
+            // separated two pieces of inline content.
+            if let Some(previous) = children.last() {
+                let gap = previous.span().end.offset < start.start.offset;
+                if gap && is_inline_markup(previous) && is_inline_markup(&child) {
+                    let span = Span::new(start.file.clone(), previous.span().end, start.start);
+                    children.push(ASTNode::Text { value: " ".to_string(), span });
+                }
+            }
+            children.push(child);
+        }
+
+        Ok(children)
+    }
+
+    /// Parses `[[expression]]` after its `[[`.
+    fn parse_interpolation(&mut self) -> Result<ASTNode, Diagnostic> {
+        let start = self.previous_span();
+        let checkpoint = self.checkpoint_previous();
+        let expression = self.parse_expression_before(&[TokenType::InterpolationEnd]);
+        self.match_tokens(&[TokenType::InterpolationEnd]);
+        self.wrap(checkpoint, SyntaxKind::Interpolation);
+        Ok(ASTNode::Interpolation { expression, span: self.span_from(&start) })
+    }
+
+    /// Parses `name`, `name="text [[expr]]"`, `name={expr}` or
+    /// `name=[[expr]]`.
+    fn parse_attribute(&mut self) -> Result<Attribute, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let name_token = self.advance().cloned().unwrap();
+        let mut value = AttributeValue::Boolean;
+
+        if self.match_tokens(&[TokenType::Assign]) {
+            if self.check(&TokenType::String) {
+                let token = self.advance().cloned().unwrap();
+                value = split_interpolations(&token);
+            } else if self.match_tokens(&[TokenType::LeftBrace]) {
+                value = AttributeValue::Expression(self.parse_expression_before(&[TokenType::RightBrace]));
+                self.match_tokens(&[TokenType::RightBrace]);
+            } else if self.match_tokens(&[TokenType::InterpolationStart]) {
+                value = AttributeValue::Interpolated(vec![self.parse_interpolation()?]);
+            }
+        }
+
+        self.wrap(checkpoint, SyntaxKind::Attribute);
+        Ok(Attribute {
+            name: name_token.value.to_string(),
+            value,
+            span: self.span_from(&name_token.span),
+        })
+    }
+
+    /// Consumes tokens up to and including `closer` and returns their source
+    /// text. Braces nest, so `{ {a: 1} }` ends at the outer `}`.
+    fn source_until(&mut self, closer: &TokenType) -> String {
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+
+        while !self.is_at_end() {
+            let token = &self.tokens[self.current];
+            if depth == 0 && token.token_type == *closer {
                 self.advance();
-                return true;
+                break;
             }
+            match token.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            tokens.push(token.clone());
+            self.advance();
         }
-        false
+
+        tokens_to_source(&tokens)
     }
 
-    fn parse_statement(&mut self) -> Result<Option<ASTNode>, String> {
-        if self.match_tokens(&[TokenType::Page]) {
-            Ok(Some(self.parse_page()?))
-        } else if self.match_tokens(&[TokenType::Component]) {
-            Ok(Some(self.parse_component()?))
-        } else if self.match_tokens(&[TokenType::Style]) {
-            Ok(Some(self.parse_style()?))
-        } else if self.match_tokens(&[TokenType::Event]) {
-            Ok(Some(self.parse_event()?))
-        } else if self.match_tokens(&[TokenType::State]) {
-            Ok(Some(self.parse_state()?))
-        } else if self.match_tokens(&[TokenType::Api]) {
-            Ok(Some(self.parse_api()?))
-        } else if self.match_tokens(&[TokenType::Router]) {
-            Ok(Some(self.parse_router()?))
-        } else if self.match_tokens(&[TokenType::Layout]) {
-            Ok(Some(self.parse_layout()?))
-        } else if self.match_tokens(&[TokenType::Animation]) {
-            Ok(Some(self.parse_animation()?))
-        } else if self.match_tokens(&[TokenType::Type]) {
-            Ok(Some(self.parse_type()?))
-        } else if self.match_tokens(&[TokenType::Hook]) {
-            Ok(Some(self.parse_hook()?))
-        } else if self.match_tokens(&[TokenType::Plugin]) {
-            Ok(Some(self.parse_plugin()?))
-        } else if self.match_tokens(&[TokenType::Config]) {
-            Ok(Some(self.parse_config()?))
-        } else if self.match_tokens(&[TokenType::Import]) {
-            Ok(Some(self.parse_import()?))
-        } else if self.match_tokens(&[TokenType::Export]) {
-            Ok(Some(self.parse_export()?))
+    // ---- expressions ----
+
+    /// Parses an expression by precedence climbing: assignment and arrows,
+    /// then `?:`, binary operators, unary operators, and calls and member
+    /// access on primaries.
+    pub fn parse_expression(&mut self) -> Result<Expr, Diagnostic> {
+        self.nested(Self::parse_assignment)
+    }
+
+    /// Parses an expression that should end at one of `closers`. When the
+    /// grammar can't parse it, its source is kept as `Expr::Raw`.
+    fn parse_expression_before(&mut self, closers: &[TokenType]) -> Expr {
+        let checkpoint = self.checkpoint();
+        if let Ok(expression) = self.parse_expression() {
+            let ended = match self.peek() {
+                None => true,
+                Some(token) => closers.contains(&token.token_type)
+                    || matches!(token.token_type, TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket),
+            };
+            if ended {
+                return expression;
+            }
+        }
+
+        self.rewind(checkpoint);
+        Expr::Raw(self.source_before(closers))
+    }
+
+    /// Goes back to `checkpoint`, dropping the syntax nodes recorded since.
+    fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.events.truncate(checkpoint.event);
+        self.current = checkpoint.token;
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expr, Diagnostic> {
+        if self.at_arrow() {
+            return self.parse_arrow();
+        }
+
+        let target = self.parse_conditional()?;
+        let operator = match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::Assign) => "=",
+            Some(TokenType::PlusAssign) => "+=",
+            Some(TokenType::MinusAssign) => "-=",
+            Some(TokenType::MultiplyAssign) => "*=",
+            Some(TokenType::DivideAssign) => "/=",
+            Some(TokenType::ModuloAssign) => "%=",
+            Some(TokenType::PowerAssign) => "**=",
+            Some(TokenType::ShiftLeftAssign) => "<<=",
+            Some(TokenType::AmpersandAssign) => "&=",
+            Some(TokenType::PipeAssign) => "|=",
+            Some(TokenType::CaretAssign) => "^=",
+            Some(TokenType::AndAssign) => "&&=",
+            Some(TokenType::OrAssign) => "||=",
+            Some(TokenType::NullishAssign) => "??=",
+            Some(TokenType::Greater) => match self.greater_run() {
+                (2, true) => ">>=",
+                (3, true) => ">>>=",
+                _ => return Ok(target),
+            },
+            _ => return Ok(target),
+        };
+        for _ in 0..operator.matches('>').count().max(1) {
+            self.advance();
+        }
+
+        Ok(Expr::Assign {
+            operator: operator.to_string(),
+            target: Box::new(target),
+            value: Box::new(self.nested(Self::parse_assignment)?),
+        })
+    }
+
+    /// Whether an arrow function starts here: `x =>`, `(a, b) =>` or either
+    /// after `async`.
+    fn at_arrow(&self) -> bool {
+        let mut index = self.current;
+        if self.check(&TokenType::Async) {
+            index += 1;
+        }
+        match self.tokens.get(index) {
+            Some(token) if token.token_type == TokenType::LeftParen => self.at_arrow_parameters(index),
+            Some(token) if is_word(&token.token_type) => {
+                self.tokens.get(index + 1).is_some_and(|token| token.token_type == TokenType::Arrow)
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_arrow(&mut self) -> Result<Expr, Diagnostic> {
+        let is_async = self.match_tokens(&[TokenType::Async]);
+
+        let mut parameters = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            parameters = self.parse_parameters()?;
+            self.expect(TokenType::RightParen, "`)`")?;
         } else {
-            self.advance(); // Skip unknown token
-            Ok(None)
+            let checkpoint = self.checkpoint();
+            let token = self.advance().cloned().unwrap();
+            self.wrap(checkpoint, SyntaxKind::Parameter);
+            parameters.push(ASTNode::Parameter {
+                name: token.value.to_string(),
+                type_annotation: None,
+                default: None,
+                span: token.span.clone(),
+            });
         }
+        self.expect(TokenType::Arrow, "`=>`")?;
+
+        let body = if self.match_tokens(&[TokenType::LeftBrace]) {
+            ArrowBody::Block(self.parse_body()?)
+        } else {
+            ArrowBody::Expression(Box::new(self.nested(Self::parse_assignment)?))
+        };
+
+        Ok(Expr::Arrow { parameters, body, is_async })
     }
 
-    fn parse_page(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
-        let mut attributes = HashMap::new();
+    fn parse_conditional(&mut self) -> Result<Expr, Diagnostic> {
+        let test = self.parse_binary(3)?;
+        if !self.match_tokens(&[TokenType::Question]) {
+            return Ok(test);
+        }
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+        let consequent = self.nested(Self::parse_assignment)?;
+        self.expect(TokenType::Colon, "`:` in conditional expression")?;
+        let alternate = self.nested(Self::parse_assignment)?;
+        Ok(Expr::Conditional {
+            test: Box::new(test),
+            consequent: Box::new(consequent),
+            alternate: Box::new(alternate),
+        })
+    }
+
+    /// Parses binary operators binding at least as tightly as `min`.
+    fn parse_binary(&mut self, min: u8) -> Result<Expr, Diagnostic> {
+        // `-a ** 2` is ambiguous, so JavaScript rejects it unparenthesized
+        let unary = self.peek().is_some_and(|token| {
+            matches!(token.token_type, TokenType::Not | TokenType::Minus | TokenType::Plus | TokenType::Tilde | TokenType::Await)
+                || (token.token_type == TokenType::Identifier && matches!(token.value, "typeof" | "void" | "delete"))
+        });
+        let mut left = self.parse_unary()?;
+
+        loop {
+            // `value as Type` only informs the type checker; it binds like `<`
+            if self.check(&TokenType::As) && binary_precedence("<") >= min {
+                self.advance(); // Skip as
+                self.parse_postfix_type();
+                continue;
+            }
+            let operator = match self.peek().map(|token| &token.token_type) {
+                Some(TokenType::Greater) => match self.greater_run() {
+                    (_, true) => break, // `>=`, `>>=` or `>>>=`
+                    (1, false) => ">",
+                    (2, false) => ">>",
+                    _ => ">>>",
+                },
+                _ => match self.peek().and_then(binary_operator) {
+                    Some(operator) => operator,
+                    None => break,
+                },
+            };
+            let precedence = binary_precedence(operator);
+            if precedence < min {
+                break;
+            }
+            if operator == "**" && unary && matches!(left, Expr::Unary { .. } | Expr::Await(_)) {
+                let span = self.peek().map_or_else(|| self.previous_span(), |token| token.span.clone());
+                return Err(Diagnostic::new(
+                    codes::UNEXPECTED_TOKEN,
+                    "`**` after a unary operator needs parentheses, as in `(-a) ** 2`".to_string(),
+                    span,
+                ));
+            }
+            for _ in 0..operator.matches('>').count().max(1) {
+                self.advance();
             }
+            // `**` is right-associative; everything else groups left
+            let next = if operator == "**" { precedence } else { precedence + 1 };
+            let right = self.nested(|parser| parser.parse_binary(next))?;
+            left = Expr::Binary {
+                operator: operator.to_string(),
+                left: Box::new(left),
+                right: Box::new(right),
+            };
         }
 
-        // Parse attributes
-        while !self.check(&TokenType::LeftBrace) && !self.is_at_end() {
-            if let Some(token) = self.peek() {
-                if token.token_type == TokenType::Identifier {
-                    let key = self.advance().unwrap().value.clone();
-                    if self.check(&TokenType::Assign) {
-                        self.advance(); // Skip =
-                        if let Some(token) = self.peek() {
-                            if token.token_type == TokenType::String {
-                                let value = self.advance().unwrap().value.clone();
-                                attributes.insert(key, value);
-                            }
-                        }
-                    }
+        Ok(left)
+    }
+
+    /// The `>` tokens written together from the current one, up to three,
+    /// and whether a `>=` ends them, as in `>>=`.
+    fn greater_run(&self) -> (usize, bool) {
+        let mut count = 0;
+        let mut end = None;
+        while let Some(token) = self.tokens.get(self.current + count) {
+            if end.is_some_and(|end| token.span.start.offset != end) {
+                break;
+            }
+            match token.token_type {
+                TokenType::Greater if count < 3 => {}
+                TokenType::GreaterEqual if count > 0 && count < 3 => return (count + 1, true),
+                _ => break,
+            }
+            end = Some(token.span.end.offset);
+            count += 1;
+        }
+        (count, false)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, Diagnostic> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return self.parse_postfix(),
+        };
+
+        let operator = match token.token_type {
+            TokenType::Not => "!",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Tilde => "~",
+            TokenType::Identifier if matches!(token.value, "typeof" | "void" | "delete") && self.operand_follows() => token.value,
+            TokenType::Increment | TokenType::Decrement => {
+                self.advance();
+                return Ok(Expr::Update {
+                    operator: token.value.to_string(),
+                    prefix: true,
+                    operand: Box::new(self.nested(Self::parse_unary)?),
+                });
+            }
+            TokenType::Await => {
+                self.advance();
+                return Ok(Expr::Await(Box::new(self.nested(Self::parse_unary)?)));
+            }
+            _ => return self.parse_postfix(),
+        };
+        self.advance();
+
+        Ok(Expr::Unary {
+            operator: operator.to_string(),
+            operand: Box::new(self.nested(Self::parse_unary)?),
+        })
+    }
+
+    /// Whether the token after the current one can start an operand, so a
+    /// word like `typeof` is an operator rather than a name.
+    fn operand_follows(&self) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|token| {
+            is_word(&token.token_type)
+                || matches!(
+                    token.token_type,
+                    TokenType::String | TokenType::Number | TokenType::LeftParen | TokenType::LeftBracket
+                        | TokenType::Not | TokenType::Minus | TokenType::TemplateStart
+                )
+        })
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, Diagnostic> {
+        let operand = self.parse_call_member()?;
+        if self.check(&TokenType::Increment) || self.check(&TokenType::Decrement) {
+            let operator = self.advance().unwrap().value.to_string();
+            return Ok(Expr::Update { operator, prefix: false, operand: Box::new(operand) });
+        }
+        Ok(operand)
+    }
+
+    /// Parses a primary followed by any chain of `.name`, `[index]`,
+    /// `(arguments)` and their `?.` forms.
+    fn parse_call_member(&mut self) -> Result<Expr, Diagnostic> {
+        let mut expression = if self.check(&TokenType::Identifier) && self.tokens[self.current].value == "new" {
+            self.parse_new()?
+        } else {
+            self.parse_primary()?
+        };
+
+        loop {
+            if self.match_tokens(&[TokenType::Dot]) {
+                let property = self.expect_word("a property name after `.`")?;
+                expression = Expr::Member { object: Box::new(expression), property, optional: false };
+            } else if self.match_tokens(&[TokenType::OptionalChain]) {
+                if self.match_tokens(&[TokenType::LeftParen]) {
+                    let arguments = self.parse_arguments(TokenType::RightParen)?;
+                    expression = Expr::Call { callee: Box::new(expression), arguments, optional: true };
+                } else if self.match_tokens(&[TokenType::LeftBracket]) {
+                    let index = self.parse_expression()?;
+                    self.expect(TokenType::RightBracket, "`]`")?;
+                    expression = Expr::Index { object: Box::new(expression), index: Box::new(index), optional: true };
                 } else {
-                    self.advance();
+                    let property = self.expect_word("a property name after `?.`")?;
+                    expression = Expr::Member { object: Box::new(expression), property, optional: true };
                 }
+            } else if self.match_tokens(&[TokenType::LeftBracket]) {
+                let index = self.parse_expression()?;
+                self.expect(TokenType::RightBracket, "`]`")?;
+                expression = Expr::Index { object: Box::new(expression), index: Box::new(index), optional: false };
+            } else if self.match_tokens(&[TokenType::LeftParen]) {
+                let arguments = self.parse_arguments(TokenType::RightParen)?;
+                expression = Expr::Call { callee: Box::new(expression), arguments, optional: false };
+            } else if self.at_type_arguments() {
+                // `useState<User | null>(null)`: the types don't reach the JavaScript
+                self.source_before(&[TokenType::LeftParen]);
+            } else {
+                return Ok(expression);
             }
         }
+    }
 
-        let mut body = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            body = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+    /// Whether a `<` under the cursor opens type arguments before a call's
+    /// `(`, rather than a comparison.
+    fn at_type_arguments(&self) -> bool {
+        if !self.check(&TokenType::Less) {
+            return false;
+        }
+        let mut depth = 0usize;
+        for (index, token) in self.tokens[self.current..].iter().enumerate() {
+            match token.token_type {
+                TokenType::Less => depth += 1,
+                TokenType::Greater => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.tokens.get(self.current + index + 1)
+                            .is_some_and(|token| token.token_type == TokenType::LeftParen);
+                    }
+                }
+                TokenType::Pipe | TokenType::Ampersand | TokenType::Comma | TokenType::Dot
+                | TokenType::LeftBracket | TokenType::RightBracket | TokenType::String | TokenType::Number => {}
+                ref token_type if is_word(token_type) => {}
+                _ => return false,
             }
         }
+        false
+    }
 
-        Ok(ASTNode::Page {
-            name,
-            attributes,
-            body,
-        })
+    /// Parses `new Callee(arguments)`; the callee takes member access but
+    /// not calls.
+    fn parse_new(&mut self) -> Result<Expr, Diagnostic> {
+        self.advance(); // Skip new
+        let mut callee = self.parse_primary()?;
+        while self.match_tokens(&[TokenType::Dot]) {
+            let property = self.expect_word("a property name after `.`")?;
+            callee = Expr::Member { object: Box::new(callee), property, optional: false };
+        }
+
+        if self.at_type_arguments() {
+            // `new Map<string, Array<number>>()`
+            self.source_before(&[TokenType::LeftParen]);
+        }
+        let mut arguments = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            arguments = self.parse_arguments(TokenType::RightParen)?;
+        }
+        Ok(Expr::New { callee: Box::new(callee), arguments })
     }
 
-    fn parse_component(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
-        let mut type_params = Vec::new();
+    /// Parses comma-separated expressions, spreads allowed, through `closer`.
+    fn parse_arguments(&mut self, closer: TokenType) -> Result<Vec<Expr>, Diagnostic> {
+        let mut arguments = Vec::new();
+        while !self.match_tokens(std::slice::from_ref(&closer)) {
+            if self.is_at_end() {
+                return Err(self.unexpected("a closing bracket"));
+            }
+            if self.match_tokens(&[TokenType::Comma]) {
+                continue; // An array hole, or a trailing comma
+            }
+            if self.match_tokens(&[TokenType::Spread]) {
+                arguments.push(Expr::Spread(Box::new(self.nested(Self::parse_assignment)?)));
+            } else {
+                arguments.push(self.nested(Self::parse_assignment)?);
+            }
+            if !self.check(&closer) {
+                self.expect(TokenType::Comma, "`,`")?;
+            }
+        }
+        Ok(arguments)
+    }
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+    fn parse_primary(&mut self) -> Result<Expr, Diagnostic> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected("an expression")),
+        };
+
+        let literal = match token.token_type {
+            TokenType::String => Some(Value::String(unescape_string(token.value))),
+            TokenType::Number => parse_number_value(token.value).map(Value::Number),
+            TokenType::True => Some(Value::Boolean(true)),
+            TokenType::False => Some(Value::Boolean(false)),
+            TokenType::Null => Some(Value::Null),
+            TokenType::Undefined => Some(Value::Undefined),
+            _ => None,
+        };
+        if let Some(value) = literal {
+            self.advance();
+            return Ok(Expr::Literal(value));
+        }
+
+        match token.token_type {
+            TokenType::Number | TokenType::BigInt => {
+                self.advance();
+                Ok(Expr::Raw(token.value.to_string()))
+            }
+            TokenType::Regex => {
+                self.advance();
+                Ok(Expr::Regex(token.value.to_string()))
+            }
+            TokenType::TemplateStart => self.parse_template(),
+            TokenType::LeftParen => {
+                self.advance(); // Skip (
+                let expression = self.parse_expression()?;
+                self.expect(TokenType::RightParen, "`)`")?;
+                Ok(expression)
+            }
+            TokenType::LeftBracket => {
+                self.advance(); // Skip [
+                Ok(Expr::Array(self.parse_arguments(TokenType::RightBracket)?))
+            }
+            TokenType::LeftBrace => self.parse_object(),
+            TokenType::OpeningTagStart => {
+                self.advance(); // Skip <
+                Ok(Expr::Markup(Box::new(self.parse_element()?)))
+            }
+            TokenType::Function => self.parse_function_expression(),
+            TokenType::Async if self.check_async_function() => self.parse_function_expression(),
+            ref token_type if is_word(token_type) => {
+                self.advance();
+                Ok(Expr::Identifier(token.value.to_string()))
             }
+            _ => Err(self.unexpected("an expression")),
         }
+    }
 
-        // Parse type parameters
-        if self.check(&TokenType::Less) {
-            self.advance(); // Skip <
-            while !self.check(&TokenType::Greater) && !self.is_at_end() {
-                if let Some(token) = self.peek() {
-                    if token.token_type == TokenType::Identifier {
-                        type_params.push(self.advance().unwrap().value.clone());
-                    }
+    /// Parses a template literal from its opening backtick.
+    fn parse_template(&mut self) -> Result<Expr, Diagnostic> {
+        self.advance(); // Skip `
+        let mut quasis = vec![String::new()];
+        let mut expressions = Vec::new();
+
+        loop {
+            let token = match self.peek() {
+                Some(token) => token.clone(),
+                None => return Err(self.unexpected("the end of the template literal")),
+            };
+            self.advance();
+            match token.token_type {
+                TokenType::TemplateChunk => quasis.last_mut().unwrap().push_str(token.value),
+                TokenType::TemplateExprStart => {
+                    expressions.push(self.parse_expression()?);
+                    self.expect(TokenType::TemplateExprEnd, "`}` closing the template expression")?;
+                    quasis.push(String::new());
                 }
-                if self.check(&TokenType::Comma) {
-                    self.advance(); // Skip ,
+                TokenType::TemplateEnd => return Ok(Expr::Template { quasis, expressions }),
+                _ => return Err(Diagnostic::new(codes::UNEXPECTED_TOKEN, format!("unexpected `{}` in template literal", token.value), token.span)),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Expr, Diagnostic> {
+        self.advance(); // Skip {
+        let mut properties = Vec::new();
+
+        while !self.match_tokens(&[TokenType::RightBrace]) {
+            if self.is_at_end() {
+                return Err(self.unexpected("`}` closing the object"));
+            }
+
+            if self.match_tokens(&[TokenType::Spread]) {
+                properties.push(Property::Spread(self.nested(Self::parse_assignment)?));
+            } else if self.match_tokens(&[TokenType::LeftBracket]) {
+                let key = self.nested(Self::parse_assignment)?;
+                self.expect(TokenType::RightBracket, "`]`")?;
+                self.expect(TokenType::Colon, "`:`")?;
+                properties.push(Property::Computed { key, value: self.nested(Self::parse_assignment)? });
+            } else {
+                // `get total() { ... }`, `set total(value) { ... }` and
+                // `async save() { ... }`, unless `get` is itself the key
+                let modifier = match (self.peek(), self.tokens.get(self.current + 1)) {
+                    (Some(token), Some(next)) if matches!(token.value, "get" | "set" | "async")
+                        && (is_word(&next.token_type) || matches!(next.token_type, TokenType::String | TokenType::Number)) =>
+                    {
+                        Some(self.advance().unwrap().value.to_string())
+                    }
+                    _ => None,
+                };
+                let token = self.advance().cloned().unwrap();
+                let key = match token.token_type {
+                    TokenType::String => unescape_string(token.value),
+                    TokenType::Number => token.value.to_string(),
+                    ref token_type if is_word(token_type) => token.value.to_string(),
+                    _ => return Err(Diagnostic::new(codes::UNEXPECTED_TOKEN, format!("expected a property name, found `{}`", token.value), token.span)),
+                };
+
+                if modifier.is_none() && self.match_tokens(&[TokenType::Colon]) {
+                    properties.push(Property::KeyValue { key, value: self.nested(Self::parse_assignment)? });
+                } else if modifier.is_some() || self.check(&TokenType::LeftParen) {
+                    // Method shorthand: `save(item) { ... }`
+                    self.expect(TokenType::LeftParen, "`(`")?;
+                    let parameters = self.parse_parameters()?;
+                    self.expect(TokenType::RightParen, "`)`")?;
+                    self.expect(TokenType::LeftBrace, "`{`")?;
+                    let body = self.parse_body()?;
+                    match modifier {
+                        Some(kind) if kind != "async" => properties.push(Property::Accessor { kind, key, parameters, body }),
+                        modifier => {
+                            let value = Expr::Function { name: None, parameters, body, is_async: modifier.is_some() };
+                            properties.push(Property::KeyValue { key, value });
+                        }
+                    }
+                } else {
+                    properties.push(Property::Shorthand(key));
                 }
             }
-            if self.check(&TokenType::Greater) {
-                self.advance(); // Skip >
+
+            if !self.check(&TokenType::RightBrace) {
+                self.expect(TokenType::Comma, "`,` between properties")?;
             }
         }
 
-        let mut body = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            body = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
-            }
+        Ok(Expr::Object(properties))
+    }
+
+    /// Parses `[async] function [name](parameters) { ... }` as a value.
+    fn parse_function_expression(&mut self) -> Result<Expr, Diagnostic> {
+        let is_async = self.match_tokens(&[TokenType::Async]);
+        self.advance(); // Skip function
+
+        let mut name = None;
+        if self.check(&TokenType::Identifier) {
+            name = Some(self.advance().unwrap().value.to_string());
         }
+        self.expect(TokenType::LeftParen, "`(`")?;
+        let parameters = self.parse_parameters()?;
+        self.expect(TokenType::RightParen, "`)`")?;
+        if self.match_tokens(&[TokenType::Colon]) {
+            self.source_before(&[TokenType::LeftBrace]); // Skip the return type
+        }
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let body = self.parse_body()?;
 
-        Ok(ASTNode::Component {
-            name,
-            type_params,
-            props: Vec::new(),
-            events: Vec::new(),
-            state: Vec::new(),
-            methods: Vec::new(),
-            render: None,
-        })
+        Ok(Expr::Function { name, parameters, body, is_async })
     }
 
-    fn parse_style(&mut self) -> Result<ASTNode, String> {
-        let mut selector = String::new();
+    fn expect(&mut self, token_type: TokenType, what: &str) -> Result<(), Diagnostic> {
+        if self.match_tokens(&[token_type]) {
+            Ok(())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                selector = self.advance().unwrap().value.clone();
+    /// Consumes a name, keywords included, as after `.`.
+    fn expect_word(&mut self, what: &str) -> Result<String, Diagnostic> {
+        match self.peek() {
+            Some(token) if is_word(&token.token_type) => Ok(self.advance().unwrap().value.to_string()),
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    /// Runs `parse` one level of nesting deeper. Past `MAX_NESTING_DEPTH`
+    /// levels it fails instead, before the recursion can overflow the
+    /// stack. The error is also reported here, as callers that fall back
+    /// to raw source drop it.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, Diagnostic>) -> Result<T, Diagnostic> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            let span = self.peek().map_or_else(|| self.previous_span(), |token| token.span.clone());
+            let error = Diagnostic::new(
+                codes::NESTING_TOO_DEEP,
+                format!("nesting deeper than {} levels", MAX_NESTING_DEPTH),
+                span,
+            );
+            self.report(error.clone());
+            return Err(error);
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Records `error` unless it has been already.
+    fn report(&mut self, error: Diagnostic) {
+        if !self.diagnostics.contains(&error) {
+            self.diagnostics.push(error);
+        }
+    }
+
+    fn unexpected(&self, what: &str) -> Diagnostic {
+        match self.peek() {
+            Some(token) => Diagnostic::new(codes::UNEXPECTED_TOKEN, format!("expected {}, found `{}`", what, token.value), token.span.clone()),
+            None => Diagnostic::new(codes::UNEXPECTED_TOKEN, format!("expected {}, found end of input", what), self.previous_span()),
+        }
+    }
+
+    // ---- styles ----
+
+    /// Parses the rules, declarations and at-rules of a style block after
+    /// its `{`, through the `}`.
+    fn parse_css_block(&mut self) -> Result<Vec<ASTNode>, Diagnostic> {
+        let mut items = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_tokens(&[TokenType::Semicolon]) {
+                continue;
             }
+            let item = if self.check(&TokenType::At) {
+                self.nested(Self::parse_at_rule)?
+            } else if self.at_css_rule() {
+                self.nested(Self::parse_css_rule)?
+            } else {
+                self.parse_declaration()?
+            };
+            items.push(item);
         }
+        self.match_tokens(&[TokenType::RightBrace]);
 
-        let mut rules = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            rules = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+        Ok(items)
+    }
+
+    /// Whether a `{` comes before the next `;` or `}`, so what follows is a
+    /// nested rule rather than a declaration.
+    fn at_css_rule(&self) -> bool {
+        let mut depth = 0usize;
+        for token in &self.tokens[self.current..] {
+            match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket => depth = depth.saturating_sub(1),
+                TokenType::LeftBrace if depth == 0 => return true,
+                TokenType::Semicolon | TokenType::RightBrace if depth == 0 => return false,
+                _ => {}
             }
         }
+        false
+    }
+
+    fn parse_css_rule(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens[self.current].span.clone();
+        let selectors = self.parse_selector_list();
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let children = self.parse_css_block()?;
+        self.wrap(checkpoint, SyntaxKind::Rule);
 
-        Ok(ASTNode::Style { selector, rules })
+        Ok(ASTNode::Rule { selectors, children, span: self.span_from(&start) })
     }
 
-    fn parse_event(&mut self) -> Result<ASTNode, String> {
-        let mut event_type = String::new();
-        let mut target = String::new();
+    /// Parses `property: value;`. The value is kept as written, with
+    /// whitespace and comments reduced to single spaces.
+    fn parse_declaration(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens[self.current].span.clone();
+        let property = self.source_before(&[TokenType::Colon, TokenType::Semicolon]);
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                event_type = self.advance().unwrap().value.clone();
+        let mut value = String::new();
+        if self.match_tokens(&[TokenType::Colon]) {
+            value = self.source_before(&[TokenType::Semicolon]);
+        }
+        if value.is_empty() && property.is_empty() {
+            self.advance(); // Skip a stray token
+        }
+
+        let mut important = false;
+        if let Some(rest) = value.strip_suffix("important").and_then(|rest| rest.trim_end().strip_suffix('!')) {
+            value = rest.trim_end().to_string();
+            important = true;
+        }
+        self.match_tokens(&[TokenType::Semicolon]);
+        self.wrap(checkpoint, SyntaxKind::Declaration);
+
+        Ok(ASTNode::Declaration { property, value, important, span: self.span_from(&start) })
+    }
+
+    /// Parses `@name prelude { ... }` or `@name prelude;`.
+    fn parse_at_rule(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens[self.current].span.clone();
+        let name = self.advance().unwrap().value.trim_start_matches('@').to_string();
+        let prelude = self.source_before(&[TokenType::LeftBrace, TokenType::Semicolon]);
+
+        let mut block = None;
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            block = Some(if name.ends_with("keyframes") {
+                self.parse_keyframes()?
+            } else {
+                self.parse_css_block()?
+            });
+        } else {
+            self.match_tokens(&[TokenType::Semicolon]);
+        }
+        self.wrap(checkpoint, SyntaxKind::AtRule);
+
+        Ok(ASTNode::AtRule { name, prelude, block, span: self.span_from(&start) })
+    }
+
+    /// Parses the keyframes of an animation or `@keyframes` rule after its
+    /// `{`, through the `}`.
+    fn parse_keyframes(&mut self) -> Result<Vec<ASTNode>, Diagnostic> {
+        let mut keyframes = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_tokens(&[TokenType::Semicolon]) {
+                continue;
             }
+            keyframes.push(self.parse_keyframe()?);
         }
+        self.match_tokens(&[TokenType::RightBrace]);
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier && token.value == "on" {
-                self.advance(); // Skip "on"
-                if let Some(token) = self.peek() {
-                    if token.token_type == TokenType::Identifier {
-                        target = self.advance().unwrap().value.clone();
+        Ok(keyframes)
+    }
+
+    /// Parses `from { ... }`, `50% { ... }` or `0%, 100% { ... }`.
+    fn parse_keyframe(&mut self) -> Result<ASTNode, Diagnostic> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens[self.current].span.clone();
+
+        let mut offsets = Vec::new();
+        loop {
+            let offset = match self.peek() {
+                Some(token) if token.token_type == TokenType::Identifier && token.value.eq_ignore_ascii_case("from") => {
+                    KeyframeOffset::From
+                }
+                Some(token) if token.token_type == TokenType::Identifier && token.value.eq_ignore_ascii_case("to") => {
+                    KeyframeOffset::To
+                }
+                Some(token) if token.token_type == TokenType::Percentage => {
+                    match token.value.trim_end_matches('%').parse::<f64>() {
+                        Ok(percentage) if (0.0..=100.0).contains(&percentage) => KeyframeOffset::Percentage(percentage),
+                        _ => return Err(Diagnostic::new(codes::INVALID_KEYFRAME, format!("keyframe `{}` is not between 0% and 100%", token.value), token.span.clone())),
                     }
                 }
+                _ => return Err(self.unexpected("`from`, `to` or a percentage")),
+            };
+            self.advance();
+            offsets.push(offset);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
             }
         }
 
-        let mut body = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            body = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
-            }
-        }
+        self.expect(TokenType::LeftBrace, "`{`")?;
+        let declarations = self.parse_css_block()?;
+        self.wrap(checkpoint, SyntaxKind::Keyframe);
 
-        Ok(ASTNode::Event {
-            event_type,
-            target,
-            body,
-        })
+        Ok(ASTNode::Keyframe { offsets, declarations, span: self.span_from(&start) })
     }
 
-    fn parse_state(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
-
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+    /// Parses comma-separated selectors up to the `{` of their block.
+    /// Whitespace between simple selectors is the descendant combinator.
+    fn parse_selector_list(&mut self) -> Vec<Selector> {
+        let mut selectors = Vec::new();
+        let mut compounds = Vec::new();
+        let mut parts = Vec::new();
+        let mut combinator = None;
+
+        fn finish(compounds: &mut Vec<CompoundSelector>, parts: &mut Vec<SimpleSelector>, combinator: &mut Option<Combinator>) {
+            if !parts.is_empty() {
+                compounds.push(CompoundSelector { combinator: combinator.take(), parts: std::mem::take(parts) });
             }
         }
 
-        let mut properties = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            properties = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+        while let Some(token) = self.peek().cloned() {
+            let explicit = match token.token_type {
+                // `(` opens the parameters of `event click on button(event)`
+                TokenType::LeftBrace | TokenType::RightBrace | TokenType::Semicolon | TokenType::LeftParen => break,
+                TokenType::Comma => {
+                    self.advance();
+                    finish(&mut compounds, &mut parts, &mut combinator);
+                    if !compounds.is_empty() {
+                        selectors.push(Selector { compounds: std::mem::take(&mut compounds) });
+                    }
+                    combinator = None;
+                    continue;
+                }
+                TokenType::Greater => Some(Combinator::Child),
+                TokenType::Plus => Some(Combinator::NextSibling),
+                TokenType::Tilde => Some(Combinator::SubsequentSibling),
+                _ => None,
+            };
+            if let Some(explicit) = explicit {
+                self.advance();
+                finish(&mut compounds, &mut parts, &mut combinator);
+                combinator = Some(explicit);
+                continue;
+            }
+
+            let gap = self.previous().is_some_and(|previous| previous.span.end.offset < token.span.start.offset);
+            if gap && !parts.is_empty() {
+                finish(&mut compounds, &mut parts, &mut combinator);
+                combinator = Some(Combinator::Descendant);
             }
+            let after_nesting = parts.last() == Some(&SimpleSelector::Nesting);
+            parts.push(self.parse_simple_selector(after_nesting));
         }
 
-        Ok(ASTNode::State { name, properties })
+        finish(&mut compounds, &mut parts, &mut combinator);
+        if !compounds.is_empty() {
+            selectors.push(Selector { compounds });
+        }
+        selectors
     }
 
-    fn parse_api(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
-
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+    fn parse_simple_selector(&mut self, after_nesting: bool) -> SimpleSelector {
+        let token = self.advance().cloned().unwrap();
+        let adjacent = |parser: &Self| parser.peek()
+            .is_some_and(|next| next.span.start.offset == token.span.end.offset);
+
+        match token.token_type {
+            TokenType::Ampersand => SimpleSelector::Nesting,
+            TokenType::Multiply => SimpleSelector::Universal,
+            TokenType::Identifier if after_nesting => SimpleSelector::Suffix(token.value.to_string()),
+            // Keywords name elements too, as in `event load on page`
+            ref token_type if is_word(token_type) => SimpleSelector::Type(self.css_name(token.value.to_string())),
+            TokenType::Hash if token.value.len() > 1 => SimpleSelector::Id(token.value[1..].to_string()),
+            // Script mode splits `#save` into `#` and a word
+            TokenType::Hash if self.peek().is_some_and(|next| is_word(&next.token_type)) && adjacent(self) => {
+                let name = self.advance().unwrap().value.to_string();
+                SimpleSelector::Id(self.css_name(name))
+            }
+            TokenType::Dot if self.peek().is_some_and(|next| is_word(&next.token_type)) && adjacent(self) => {
+                let name = self.advance().unwrap().value.to_string();
+                SimpleSelector::Class(self.css_name(name))
+            }
+            TokenType::LeftBracket => {
+                let text = self.source_before(&[]);
+                self.match_tokens(&[TokenType::RightBracket]);
+                SimpleSelector::Attribute(text)
+            }
+            TokenType::Colon if self.check(&TokenType::Colon) && adjacent(self) => {
+                self.advance(); // Skip the second :
+                let name = self.advance().map(|token| token.value.to_string()).unwrap_or_default();
+                SimpleSelector::PseudoElement(name)
+            }
+            TokenType::Colon if self.check(&TokenType::Identifier) && adjacent(self) => {
+                let name = self.advance().unwrap().value.to_string();
+                let mut argument = None;
+                if self.check(&TokenType::LeftParen) && self.previous().is_some_and(|name| {
+                    self.tokens[self.current].span.start.offset == name.span.end.offset
+                }) {
+                    self.advance(); // Skip (
+                    argument = Some(self.source_before(&[]));
+                    self.match_tokens(&[TokenType::RightParen]);
+                }
+                SimpleSelector::PseudoClass { name, argument }
             }
+            _ => SimpleSelector::Raw(token.value.to_string()),
         }
+    }
 
-        let mut methods = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            methods = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+    /// Extends `name` with adjacent `-part`s, which script mode lexes as
+    /// separate tokens, as in `.btn-primary`.
+    fn css_name(&mut self, mut name: String) -> String {
+        while self.check(&TokenType::Minus) {
+            let touching = |first: &Token, second: &Token| first.span.end.offset == second.span.start.offset;
+            let joined = match (self.tokens.get(self.current - 1), self.tokens.get(self.current), self.tokens.get(self.current + 1)) {
+                (Some(name), Some(minus), Some(part)) => {
+                    touching(name, minus) && touching(minus, part)
+                        && (is_word(&part.token_type) || part.token_type == TokenType::Number)
+                }
+                _ => false,
+            };
+            if !joined {
+                break;
             }
+            self.advance(); // Skip -
+            name.push('-');
+            name.push_str(self.advance().unwrap().value);
         }
-
-        Ok(ASTNode::Api {
-            name,
-            properties: HashMap::new(),
-            methods,
-        })
+        name
     }
 
-    fn parse_router(&mut self) -> Result<ASTNode, String> {
-        let mut routes = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            routes = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+    // ---- statements ----
+
+    /// Parses the statements of a body after its `{`, through the `}`.
+    fn parse_body(&mut self) -> Result<Vec<Stmt>, Diagnostic> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_tokens(&[TokenType::Semicolon]) {
+                continue;
             }
+            statements.push(self.parse_stmt_or_raw());
         }
-
-        Ok(ASTNode::Router { routes })
+        self.match_tokens(&[TokenType::RightBrace]);
+        Ok(statements)
     }
 
-    fn parse_layout(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
-
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+    /// Parses a statement, keeping its source as `Stmt::Raw` and reporting
+    /// the error when the grammar can't parse it.
+    fn parse_stmt_or_raw(&mut self) -> Stmt {
+        let checkpoint = self.checkpoint();
+        match self.nested(Self::parse_stmt) {
+            Ok(statement) => statement,
+            Err(error) => {
+                self.report(error);
+                self.rewind(checkpoint);
+                Stmt::Raw(self.skip_statement())
             }
         }
+    }
 
-        let mut render = None;
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            let body = self.parse_block()?;
-            if !body.is_empty() {
-                render = Some(Box::new(body[0].clone()));
+    /// Skips to the end of a statement that didn't parse: its `;`, or the
+    /// end of its line once brackets are balanced, or the `}` closing the
+    /// enclosing block even if parentheses are left open. Returns its
+    /// source.
+    fn skip_statement(&mut self) -> String {
+        let from = self.current;
+        let mut braces = 0usize;
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            let new_line = self.current > from && token.span.start.line > self.tokens[self.current - 1].span.end.line;
+            if braces == 0 && depth == 0 && (token.token_type == TokenType::Semicolon || new_line) {
+                break;
             }
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+            match token.token_type {
+                TokenType::LeftBrace => braces += 1,
+                TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightBrace if braces == 0 => {
+                    if self.current == from {
+                        self.advance(); // A stray closing brace
+                    }
+                    break;
+                }
+                TokenType::RightParen | TokenType::RightBracket if depth == 0 => {
+                    if self.current == from {
+                        self.advance(); // A stray closing bracket
+                    }
+                    break;
+                }
+                TokenType::RightBrace => braces -= 1,
+                TokenType::RightParen | TokenType::RightBracket => depth -= 1,
+                _ => {}
             }
+            self.advance();
         }
 
-        Ok(ASTNode::Layout { name, render })
+        let source = tokens_to_source(&self.tokens[from..self.current]);
+        self.match_tokens(&[TokenType::Semicolon]);
+        source
     }
 
-    fn parse_animation(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
+    pub fn parse_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected("a statement")),
+        };
+        let next = self.tokens.get(self.current + 1).map(|token| token.token_type.clone());
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+        match token.token_type {
+            TokenType::LeftBrace => {
+                self.advance(); // Skip {
+                Ok(Stmt::Block(self.parse_body()?))
             }
-        }
-
-        let mut keyframes = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            keyframes = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+            TokenType::Const | TokenType::Let | TokenType::Var => {
+                let statement = self.parse_variable()?;
+                self.end_statement()?;
+                Ok(statement)
+            }
+            TokenType::Function => self.parse_function_declaration(),
+            TokenType::Async if self.check_async_function() => self.parse_function_declaration(),
+            TokenType::If => self.parse_if(),
+            TokenType::For => self.parse_for(),
+            TokenType::While => {
+                self.advance(); // Skip while
+                let test = self.parse_condition()?;
+                let body = Box::new(self.nested(Self::parse_stmt)?);
+                Ok(Stmt::While { test, body })
+            }
+            TokenType::Return => {
+                self.advance(); // Skip return
+                let argument = if self.at_statement_end(&token) { None } else { Some(self.parse_expression()?) };
+                self.end_statement()?;
+                Ok(Stmt::Return(argument))
             }
+            TokenType::Identifier => match token.value {
+                "switch" if next == Some(TokenType::LeftParen) => self.parse_switch(),
+                "try" if next == Some(TokenType::LeftBrace) => self.parse_try(),
+                "do" => {
+                    self.advance(); // Skip do
+                    let body = Box::new(self.nested(Self::parse_stmt)?);
+                    self.expect(TokenType::While, "`while`")?;
+                    let test = self.parse_condition()?;
+                    self.end_statement()?;
+                    Ok(Stmt::DoWhile { body, test })
+                }
+                "throw" => {
+                    self.advance(); // Skip throw
+                    let argument = self.parse_expression()?;
+                    self.end_statement()?;
+                    Ok(Stmt::Throw(argument))
+                }
+                "break" | "continue" => {
+                    self.advance();
+                    let mut label = None;
+                    if self.check(&TokenType::Identifier) && !self.at_statement_end(&token) {
+                        label = Some(self.advance().unwrap().value.to_string());
+                    }
+                    self.end_statement()?;
+                    Ok(if token.value == "break" { Stmt::Break(label) } else { Stmt::Continue(label) })
+                }
+                _ => self.parse_expression_statement(),
+            },
+            _ => self.parse_expression_statement(),
         }
-
-        Ok(ASTNode::Animation { name, keyframes })
     }
 
-    fn parse_type(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
+    fn parse_expression_statement(&mut self) -> Result<Stmt, Diagnostic> {
+        let expression = self.parse_expression()?;
+        self.end_statement()?;
+        Ok(Stmt::Expression(expression))
+    }
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
-            }
+    /// Whether the statement begun by `start` can end here: at `;`, `}`, the
+    /// end of input or a line break.
+    fn at_statement_end(&self, start: &Token) -> bool {
+        match self.peek() {
+            None => true,
+            Some(token) => matches!(token.token_type, TokenType::Semicolon | TokenType::RightBrace)
+                || token.span.start.line > self.previous().unwrap_or(start).span.end.line,
         }
+    }
 
-        let mut definition = None;
-        if self.check(&TokenType::Assign) {
-            self.advance(); // Skip =
-            // Parse type definition
+    /// Consumes a statement's `;`, which a line break or `}` can stand in for.
+    fn end_statement(&mut self) -> Result<(), Diagnostic> {
+        if self.match_tokens(&[TokenType::Semicolon]) {
+            return Ok(());
         }
-
-        if self.check(&TokenType::Semicolon) {
-            self.advance(); // Skip ;
+        match (self.peek(), self.previous()) {
+            (None, _) => Ok(()),
+            (Some(token), _) if token.token_type == TokenType::RightBrace => Ok(()),
+            (Some(token), Some(previous)) if token.span.start.line > previous.span.end.line => Ok(()),
+            _ => Err(self.unexpected("`;`")),
         }
-
-        Ok(ASTNode::Type { name, definition })
     }
 
-    fn parse_hook(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
+    /// Parses `const`, `let` or `var` and its declarations, without the `;`.
+    fn parse_variable(&mut self) -> Result<Stmt, Diagnostic> {
+        let kind = self.advance().unwrap().value.to_string();
+        let mut declarations = Vec::new();
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+        loop {
+            let binding = self.parse_binding()?;
+            if self.match_tokens(&[TokenType::Colon]) {
+                self.source_before(&[TokenType::Assign, TokenType::Comma, TokenType::Semicolon]); // Skip the type
             }
-        }
+            let mut init = None;
+            if self.match_tokens(&[TokenType::Assign]) {
+                init = Some(self.nested(Self::parse_assignment)?);
+            }
+            declarations.push((binding, init));
 
-        let mut parameters = Vec::new();
-        if self.check(&TokenType::LeftParen) {
-            self.advance(); // Skip (
-            parameters = self.parse_parameters()?;
-            if self.check(&TokenType::RightParen) {
-                self.advance(); // Skip )
+            if !self.match_tokens(&[TokenType::Comma]) {
+                return Ok(Stmt::Variable { kind, declarations });
             }
         }
+    }
 
-        let mut body = Vec::new();
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            body = self.parse_block()?;
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
+    /// A variable name, or a destructuring pattern as written.
+    fn parse_binding(&mut self) -> Result<String, Diagnostic> {
+        let closer = match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::LeftBracket) => TokenType::RightBracket,
+            Some(TokenType::LeftBrace) => TokenType::RightBrace,
+            _ => return self.expect_word("a variable name"),
+        };
+
+        let from = self.current;
+        self.advance();
+        self.source_before(&[]);
+        self.expect(closer, "the end of the pattern")?;
+        Ok(tokens_to_source(&self.tokens[from..self.current]))
+    }
+
+    fn parse_function_declaration(&mut self) -> Result<Stmt, Diagnostic> {
+        match self.parse_function_expression()? {
+            Expr::Function { name: Some(name), parameters, body, is_async } => {
+                Ok(Stmt::Function { name, parameters, body, is_async })
             }
+            _ => Err(self.unexpected("a function name")),
         }
+    }
 
-        Ok(ASTNode::Hook {
-            name,
-            parameters,
-            body,
-        })
+    /// Parses the parenthesized condition of `if`, `while` or `switch`.
+    fn parse_condition(&mut self) -> Result<Expr, Diagnostic> {
+        self.expect(TokenType::LeftParen, "`(`")?;
+        let test = self.parse_expression()?;
+        self.expect(TokenType::RightParen, "`)`")?;
+        Ok(test)
     }
 
-    fn parse_plugin(&mut self) -> Result<ASTNode, String> {
-        let mut name = String::new();
+    fn parse_if(&mut self) -> Result<Stmt, Diagnostic> {
+        self.advance(); // Skip if
+        let test = self.parse_condition()?;
+        let consequent = Box::new(self.nested(Self::parse_stmt)?);
+        let mut alternate = None;
+        if self.match_tokens(&[TokenType::Else]) {
+            alternate = Some(Box::new(self.nested(Self::parse_stmt)?));
+        }
+        Ok(Stmt::If { test, consequent, alternate })
+    }
 
-        if let Some(token) = self.peek() {
-            if token.token_type == TokenType::Identifier {
-                name = self.advance().unwrap().value.clone();
+    /// Parses `for (init; test; update)`, `for (x of xs)` or `for (k in o)`
+    /// and the loop body.
+    fn parse_for(&mut self) -> Result<Stmt, Diagnostic> {
+        self.advance(); // Skip for
+        self.expect(TokenType::LeftParen, "`(`")?;
+
+        let mut init = None;
+        if matches!(self.peek().map(|token| &token.token_type), Some(TokenType::Const | TokenType::Let | TokenType::Var)) {
+            let checkpoint = self.checkpoint();
+            let kind = self.advance().unwrap().value.to_string();
+            let binding = self.parse_binding()?;
+            if let Some(of) = self.match_for_in() {
+                return self.finish_for_in(Some(kind), binding, of);
+            }
+            self.rewind(checkpoint);
+            init = Some(Box::new(self.parse_variable()?));
+        } else if !self.check(&TokenType::Semicolon) {
+            let expression = self.parse_expression()?;
+            if let Some(of) = self.match_for_in() {
+                return self.finish_for_in(None, expression.to_string(), of);
             }
+            // `for (key in object)` parses as an `in` comparison
+            if let Expr::Binary { operator, left, right } = &expression {
+                if operator == "in" && self.check(&TokenType::RightParen) {
+                    self.advance(); // Skip )
+                    let body = Box::new(self.nested(Self::parse_stmt)?);
+                    return Ok(Stmt::ForIn { kind: None, binding: left.to_string(), of: false, object: (**right).clone(), body });
+                }
+            }
+            init = Some(Box::new(Stmt::Expression(expression)));
         }
+        self.expect(TokenType::Semicolon, "`;` in `for`")?;
 
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            // Parse properties
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
-            }
+        let mut test = None;
+        if !self.check(&TokenType::Semicolon) {
+            test = Some(self.parse_expression()?);
+        }
+        self.expect(TokenType::Semicolon, "`;` in `for`")?;
+        let mut update = None;
+        if !self.check(&TokenType::RightParen) {
+            update = Some(self.parse_expression()?);
         }
+        self.expect(TokenType::RightParen, "`)`")?;
 
-        Ok(ASTNode::Plugin {
-            name,
-            properties: HashMap::new(),
-        })
+        let body = Box::new(self.nested(Self::parse_stmt)?);
+        Ok(Stmt::For { init, test, update, body })
     }
 
-    fn parse_config(&mut self) -> Result<ASTNode, String> {
-        if self.check(&TokenType::LeftBrace) {
-            self.advance(); // Skip {
-            // Parse properties
-            if self.check(&TokenType::RightBrace) {
-                self.advance(); // Skip }
-            }
-        }
+    /// Consumes the `of` or `in` of a `for` head; `Some(true)` for `of`.
+    fn match_for_in(&mut self) -> Option<bool> {
+        let of = match self.peek() {
+            Some(token) if token.token_type == TokenType::Identifier && token.value == "of" => true,
+            Some(token) if token.token_type == TokenType::Identifier && token.value == "in" => false,
+            _ => return None,
+        };
+        self.advance();
+        Some(of)
+    }
 
-        Ok(ASTNode::Config {
-            properties: HashMap::new(),
-        })
+    fn finish_for_in(&mut self, kind: Option<String>, binding: String, of: bool) -> Result<Stmt, Diagnostic> {
+        let object = self.parse_expression()?;
+        self.expect(TokenType::RightParen, "`)`")?;
+        let body = Box::new(self.nested(Self::parse_stmt)?);
+        Ok(Stmt::ForIn { kind, binding, of, object, body })
     }
 
-    fn parse_import(&mut self) -> Result<ASTNode, String> {
-        let mut specifiers = Vec::new();
+    fn parse_switch(&mut self) -> Result<Stmt, Diagnostic> {
+        self.advance(); // Skip switch
+        let discriminant = self.parse_condition()?;
+        self.expect(TokenType::LeftBrace, "`{`")?;
 
-        // Parse specifiers
-        while !self.check(&TokenType::From) && !self.is_at_end() {
-            if let Some(token) = self.peek() {
-                if token.token_type == TokenType::Identifier {
-                    specifiers.push(self.advance().unwrap().value.clone());
-                }
+        let at_label = |parser: &Self| parser.peek()
+            .is_some_and(|token| token.token_type == TokenType::Identifier && matches!(token.value, "case" | "default"));
+
+        let mut cases = Vec::new();
+        while !self.match_tokens(&[TokenType::RightBrace]) {
+            if self.is_at_end() {
+                return Err(self.unexpected("`}` closing the switch"));
             }
-            if self.check(&TokenType::Comma) {
-                self.advance(); // Skip ,
+
+            let mut test = None;
+            if self.advance().is_some_and(|token| token.value == "case") {
+                test = Some(self.parse_expression()?);
+            } else if self.previous().is_none_or(|token| token.value != "default") {
+                return Err(Diagnostic::new(codes::UNEXPECTED_TOKEN, "expected `case` or `default`", self.previous_span()));
             }
-        }
+            self.expect(TokenType::Colon, "`:`")?;
 
-        let mut source = String::new();
-        if self.check(&TokenType::From) {
-            self.advance(); // Skip from
-            if let Some(token) = self.peek() {
-                if token.token_type == TokenType::String {
-                    source = self.advance().unwrap().value.clone();
+            let mut body = Vec::new();
+            while !self.check(&TokenType::RightBrace) && !self.is_at_end() && !at_label(self) {
+                if !self.match_tokens(&[TokenType::Semicolon]) {
+                    body.push(self.parse_stmt_or_raw());
                 }
             }
+            cases.push(SwitchCase { test, body });
         }
 
-        Ok(ASTNode::Import { specifiers, source })
+        Ok(Stmt::Switch { discriminant, cases })
     }
 
-    fn parse_export(&mut self) -> Result<ASTNode, String> {
-        let mut is_default = false;
-        if self.check(&TokenType::Identifier) {
-            if let Some(token) = self.peek() {
-                if token.value == "default" {
-                    self.advance(); // Skip default
-                    is_default = true;
+    fn parse_try(&mut self) -> Result<Stmt, Diagnostic> {
+        self.advance(); // Skip try
+        self.advance(); // Skip {
+        let block = self.parse_body()?;
+
+        let mut parameter = None;
+        let mut handler = None;
+        if self.check(&TokenType::Identifier) && self.tokens[self.current].value == "catch" {
+            self.advance();
+            if self.match_tokens(&[TokenType::LeftParen]) {
+                parameter = Some(self.parse_binding()?);
+                if self.match_tokens(&[TokenType::Colon]) {
+                    self.source_before(&[]); // Skip the type
                 }
+                self.expect(TokenType::RightParen, "`)`")?;
             }
+            self.expect(TokenType::LeftBrace, "`{`")?;
+            handler = Some(self.parse_body()?);
         }
 
-        let mut specifiers = Vec::new();
-        // Parse specifiers
-        while !self.check(&TokenType::From) && !self.is_at_end() {
-            if let Some(token) = self.peek() {
-                if token.token_type == TokenType::Identifier {
-                    specifiers.push(self.advance().unwrap().value.clone());
-                }
-            }
-            if self.check(&TokenType::Comma) {
-                self.advance(); // Skip ,
-            }
+        let mut finalizer = None;
+        if self.check(&TokenType::Identifier) && self.tokens[self.current].value == "finally" {
+            self.advance();
+            self.expect(TokenType::LeftBrace, "`{`")?;
+            finalizer = Some(self.parse_body()?);
         }
 
-        let mut source = String::new();
-        if self.check(&TokenType::From) {
-            self.advance(); // Skip from
-            if let Some(token) = self.peek() {
-                if token.token_type == TokenType::String {
-                    source = self.advance().unwrap().value.clone();
-                }
-            }
+        if handler.is_none() && finalizer.is_none() {
+            return Err(self.unexpected("`catch` or `finally`"));
         }
-
-        Ok(ASTNode::Export {
-            specifiers,
-            source,
-            is_default,
-        })
+        Ok(Stmt::Try { block, parameter, handler, finalizer })
     }
 
-    fn parse_block(&mut self) -> Result<Vec<ASTNode>, String> {
+    fn parse_block(&mut self) -> Result<Vec<ASTNode>, Diagnostic> {
         let mut statements = Vec::new();
 
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            if let Some(statement) = self.parse_statement()? {
-                statements.push(statement);
-            } else {
-                self.advance(); // Skip unknown token
-            }
+            statements.push(self.nested(Self::parse_statement)?);
         }
 
         Ok(statements)
     }
 
-    fn parse_parameters(&mut self) -> Result<Vec<ASTNode>, String> {
+    /// Parses a parameter list after its `(`, stopping at the `)`, or at the
+    /// `]` of a hook's `parameters: [...]`.
+    fn parse_parameters(&mut self) -> Result<Vec<ASTNode>, Diagnostic> {
         let mut parameters = Vec::new();
 
-        while !self.check(&TokenType::RightParen) && !self.is_at_end() {
-            if let Some(token) = self.peek() {
-                if token.token_type == TokenType::Identifier {
-                    // Parse parameter
-                    self.advance();
-                }
+        while !self.check(&TokenType::RightParen) && !self.check(&TokenType::RightBracket) && !self.is_at_end() {
+            let checkpoint = self.checkpoint();
+            let start = self.tokens[self.current].span.clone();
+            // Names may be keywords (`event`), rest (`...args`) or patterns
+            let name = self.source_before(&[TokenType::Colon, TokenType::Assign, TokenType::Comma, TokenType::RightParen]);
+
+            let mut type_annotation = None;
+            if self.match_tokens(&[TokenType::Colon]) {
+                type_annotation = Some(self.parse_type_expr());
+                self.source_before(&[TokenType::Assign, TokenType::Comma, TokenType::RightParen]); // Skip what the type grammar left
+            }
+            let mut default = None;
+            if self.match_tokens(&[TokenType::Assign]) {
+                default = Some(self.parse_expression_before(&[TokenType::Comma, TokenType::RightParen]));
             }
-            if self.check(&TokenType::Comma) {
-                self.advance(); // Skip ,
+
+            if name.is_empty() && type_annotation.is_none() && default.is_none() {
+                if !self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::RightParen) {
+                    self.advance(); // Skip a stray token
+                }
+                continue;
             }
+
+            self.wrap(checkpoint, SyntaxKind::Parameter);
+            parameters.push(ASTNode::Parameter {
+                name,
+                type_annotation,
+                default,
+                span: self.span_from(&start),
+            });
+            self.match_tokens(&[TokenType::Comma]);
         }
 
         Ok(parameters)
     }
+
+    /// Consumes tokens up to, not including, the first of `closers` outside
+    /// brackets, or an unmatched closing bracket, and returns their source.
+    fn source_before(&mut self, closers: &[TokenType]) -> String {
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+
+        while !self.is_at_end() {
+            let token = &self.tokens[self.current];
+            if depth == 0 && closers.contains(&token.token_type) {
+                break;
+            }
+            match token.token_type {
+                TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+            tokens.push(token.clone());
+            self.advance();
+        }
+
+        tokens_to_source(&tokens)
+    }
+}
+
+/// Whether `node` is content within a line: text, a value, or an inline
+/// element such as `<b>`.
+fn is_inline_markup(node: &ASTNode) -> bool {
+    match node {
+        ASTNode::Text { .. } | ASTNode::Interpolation { .. } | ASTNode::Expression { .. } => true,
+        ASTNode::Element { tag_name, .. } => INLINE_ELEMENTS.contains(&tag_name.as_str()),
+        _ => false,
+    }
+}
+
+/// Rebuilds source text from tokens, with a single space wherever the
+/// original had whitespace or comments between them.
+fn tokens_to_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    let mut previous_end: Option<usize> = None;
+
+    for token in tokens {
+        if previous_end.is_some_and(|end| token.span.start.offset > end) {
+            source.push(' ');
+        }
+        if token.token_type == TokenType::String {
+            let quote = if token.value.contains('"') { '\'' } else { '"' };
+            source.push(quote);
+            source.push_str(token.value);
+            source.push(quote);
+        } else {
+            source.push_str(token.value);
+        }
+        previous_end = Some(token.span.end.offset);
+    }
+
+    source
+}
+
+/// The value of a number literal: decimal, `0x`/`0o`/`0b` integers and `_`
+/// separators.
+fn parse_number_value(text: &str) -> Option<f64> {
+    let digits = text.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0o") | Some("0O") => 8,
+        Some("0b") | Some("0B") => 2,
+        _ => return digits.parse().ok(),
+    };
+    u64::from_str_radix(&digits[2..], radix).ok().map(|value| value as f64)
+}
+
+/// Splits a quoted attribute value on its `[[...]]` interpolations. Values
+/// without any stay `Static`.
+fn split_interpolations(token: &Token) -> AttributeValue {
+    let value = token.value;
+    if !value.contains("[[") {
+        return AttributeValue::Static(value.to_string());
+    }
+
+    // The value starts one character after the opening quote
+    let mut base = token.span.start;
+    base.offset += 1;
+    base.column += 1;
+    let span = |from: usize, to: usize| {
+        Span::new(token.span.file.clone(), offset_position(base, value, from), offset_position(base, value, to))
+    };
+
+    let mut parts = Vec::new();
+    let mut rest = 0;
+    while let Some(found) = value[rest..].find("[[") {
+        let open = rest + found;
+        let close = match find_interpolation_end(&value[open + 2..]) {
+            Some(index) => open + 2 + index,
+            None => break,
+        };
+        if open > rest {
+            parts.push(ASTNode::Text { value: value[rest..open].to_string(), span: span(rest, open) });
+        }
+        parts.push(ASTNode::Interpolation {
+            expression: parse_expression_source(&value[open + 2..close], &token.span.file),
+            span: span(open, close + 2),
+        });
+        rest = close + 2;
+    }
+    if rest < value.len() {
+        parts.push(ASTNode::Text { value: value[rest..].to_string(), span: span(rest, value.len()) });
+    }
+
+    AttributeValue::Interpolated(parts)
+}
+
+/// Parses an expression found inside another token, as in an attribute
+/// value, keeping its source as `Expr::Raw` when it doesn't parse.
+fn parse_expression_source(source: &str, file: &str) -> Expr {
+    let tokens = match Lexer::new(source, file).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return Expr::Raw(source.trim().to_string()),
+    };
+    let mut parser = Parser::new(tokens);
+    match parser.parse_expression() {
+        Ok(expression) if parser.is_at_end() => expression,
+        _ => Expr::Raw(source.trim().to_string()),
+    }
+}
+
+/// Finds the `]]` closing an interpolation, skipping brackets nested inside
+/// it as in `[[items[0]]]`.
+fn find_interpolation_end(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    for (index, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'[' => depth += 1,
+            b']' if depth == 0 && bytes.get(index + 1) == Some(&b']') => return Some(index),
+            b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The position `offset` bytes into `text`, which starts at `base`.
+fn offset_position(base: Position, text: &str, offset: usize) -> Position {
+    let mut position = base;
+    for c in text[..offset].chars() {
+        position.offset += c.len_utf8();
+        if c == '\n' {
+            position.line += 1;
+            position.column = 1;
+        } else {
+            position.column += 1;
+        }
+    }
+    position
 }
 
 // ===================================
@@ -1141,6 +6834,23 @@ pub struct CodeGenerator {
     components: HashMap<String, String>,
     styles: HashMap<String, String>,
     scripts: HashMap<String, String>,
+    // Static attributes of the page being generated, for `[[page.x]]`
+    page_attributes: HashMap<String, String>,
+    diagnostics: Vec<Diagnostic>,
+    // Whether `delegateEvent` has been emitted
+    event_runtime: bool,
+    // The page's values only known at runtime, by placeholder index
+    bindings: Vec<String>,
+    // Whether `escapeHtml` and `htmlAttribute` have been emitted
+    template_runtime: bool,
+    // Whether `readProp` has been emitted
+    prop_runtime: bool,
+}
+
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CodeGenerator {
@@ -1152,89 +6862,235 @@ impl CodeGenerator {
             components: HashMap::new(),
             styles: HashMap::new(),
             scripts: HashMap::new(),
+            page_attributes: HashMap::new(),
+            diagnostics: Vec::new(),
+            event_runtime: false,
+            bindings: Vec::new(),
+            template_runtime: false,
+            prop_runtime: false,
         }
     }
 
-    pub fn generate(&mut self, ast: &ASTNode) -> Result<(), String> {
+    pub fn generate(&mut self, ast: &ASTNode) -> Result<(), Diagnostic> {
+        self.check_animations(ast);
         self.visit_node(ast)?;
+        self.generate_bindings();
         Ok(())
     }
 
-    fn visit_node(&mut self, node: &ASTNode) -> Result<(), String> {
+    /// Warns about `animation="fadeIn 0.8s ease"` attributes naming an
+    /// animation that no `animation` block or `@keyframes` rule declares.
+    fn check_animations(&mut self, ast: &ASTNode) {
+        let mut declared = HashSet::new();
+        let mut used = Vec::new();
+        collect_animations(ast, &mut declared, &mut used);
+
+        for (name, span) in used {
+            if !declared.contains(name) {
+                self.diagnostics.push(Diagnostic::new(
+                    codes::UNKNOWN_ANIMATION,
+                    format!("animation `{}` is not declared", name),
+                    span.clone(),
+                ));
+            }
+        }
+    }
+
+    /// Fills in the page's `data-ema-bind` placeholders once its script has
+    /// run: text becomes the value, escaped, and attributes are set, or
+    /// left out when the value is false or missing.
+    fn generate_bindings(&mut self) {
+        if self.bindings.is_empty() {
+            return;
+        }
+        if !self.js_output.is_empty() && !self.js_output.ends_with("\n\n") {
+            self.js_output.push('\n');
+        }
+        self.js_output.push_str("const pageBindings = [\n");
+        for binding in &self.bindings {
+            self.js_output.push_str(&format!("  () => {},\n", binding));
+        }
+        self.js_output.push_str("];\n\n");
+        self.js_output.push_str("for (const element of document.querySelectorAll('[data-ema-bind]')) {\n");
+        self.js_output.push_str("  element.replaceWith(String(pageBindings[element.dataset.emaBind]() ?? ''));\n");
+        self.js_output.push_str("}\n");
+        self.js_output.push_str("for (const element of document.querySelectorAll('*')) {\n");
+        self.js_output.push_str("  for (const { name, value } of [...element.attributes]) {\n");
+        self.js_output.push_str("    if (!name.startsWith('data-ema-bind-')) {\n");
+        self.js_output.push_str("      continue;\n");
+        self.js_output.push_str("    }\n");
+        self.js_output.push_str("    element.removeAttribute(name);\n");
+        self.js_output.push_str("    const bound = pageBindings[value]();\n");
+        self.js_output.push_str("    if (bound !== false && bound !== null && bound !== undefined) {\n");
+        self.js_output.push_str("      element.setAttribute(name.slice('data-ema-bind-'.length), bound === true ? '' : String(bound));\n");
+        self.js_output.push_str("    }\n");
+        self.js_output.push_str("  }\n");
+        self.js_output.push_str("}\n");
+    }
+
+    fn visit_node(&mut self, node: &ASTNode) -> Result<(), Diagnostic> {
         match node {
-            ASTNode::Program(statements) => {
-                for statement in statements {
+            ASTNode::Program { body, .. } => {
+                for statement in body {
                     self.visit_node(statement)?;
                 }
             }
-            ASTNode::Page { name, attributes, body } => {
+            ASTNode::Page { name, attributes, body, .. } => {
                 self.generate_page(name, attributes, body)?;
             }
-            ASTNode::Component { name, type_params, .. } => {
-                self.generate_component(name, type_params)?;
+            ASTNode::Component { .. } => {
+                self.generate_component(node)?;
+            }
+            ASTNode::Style { selectors, rules, .. } => {
+                self.generate_style(selectors, rules)?;
             }
-            ASTNode::Style { selector, rules } => {
-                self.generate_style(selector, rules)?;
+            ASTNode::AtRule { .. } => {
+                self.css_output.push_str(&css_rules(&[], std::slice::from_ref(node), false, 0));
+                self.css_output.push('\n');
             }
-            ASTNode::Event { event_type, target, body } => {
-                self.generate_event(event_type, target, body)?;
+            ASTNode::Event { event_type, target, parameters, body, .. } => {
+                self.generate_event(event_type, target, parameters, body)?;
             }
-            ASTNode::State { name, properties } => {
+            ASTNode::State { name, properties, .. } => {
                 self.generate_state(name, properties)?;
             }
-            ASTNode::Api { name, methods } => {
+            ASTNode::Api { name, methods, .. } => {
                 self.generate_api(name, methods)?;
             }
-            ASTNode::Router { routes } => {
+            ASTNode::Router { routes, .. } => {
                 self.generate_router(routes)?;
             }
-            ASTNode::Layout { name, render } => {
+            ASTNode::Layout { name, render, .. } => {
                 self.generate_layout(name, render)?;
             }
-            ASTNode::Animation { name, keyframes } => {
+            ASTNode::Animation { name, keyframes, .. } => {
                 self.generate_animation(name, keyframes)?;
             }
-            ASTNode::Type { name, definition } => {
-                self.generate_type(name, definition)?;
+            ASTNode::Type { name, type_params, definition, .. } => {
+                self.generate_type(name, type_params, definition)?;
             }
-            ASTNode::Hook { name, parameters, body } => {
+            ASTNode::Interface { name, type_params, extends, fields, .. } => {
+                // An interface is an object type and its bases
+                let mut parts = extends.clone();
+                parts.push(TypeExpr::Object(fields.clone()));
+                let definition = parts.iter().map(|part| part.to_string()).collect::<Vec<_>>().join(" & ");
+                self.js_output.push_str(&format!("// Interface: {}\n", name));
+                self.js_output.push_str(&typedef_comment(name, type_params, &definition));
+            }
+            ASTNode::Enum { name, members, .. } => {
+                self.generate_enum(name, members)?;
+            }
+            ASTNode::Hook { name, parameters, body, .. } => {
                 self.generate_hook(name, parameters, body)?;
             }
-            ASTNode::Plugin { name, properties } => {
-                self.generate_plugin(name, properties)?;
+            ASTNode::Plugin { name, properties, signatures, methods, .. } => {
+                self.generate_plugin(name, properties, signatures, methods)?;
             }
-            ASTNode::Config { properties } => {
+            ASTNode::Config { properties, .. } => {
                 self.generate_config(properties)?;
             }
-            ASTNode::Import { specifiers, source } => {
-                self.generate_import(specifiers, source)?;
+            ASTNode::Import { default, namespace, specifiers, source, .. } => {
+                self.generate_import(default, namespace, specifiers, source)?;
+            }
+            ASTNode::Export { clause, source, is_default, .. } => {
+                self.generate_export(clause, source, *is_default)?;
+            }
+            ASTNode::Method { name, is_async, parameters, body, .. } => {
+                self.js_output.push_str(&format!(
+                    "{}function {}({}) {}\n\n",
+                    if *is_async { "async " } else { "" },
+                    name,
+                    parameters_source(parameters),
+                    block_js(body, 0)
+                ));
+            }
+            ASTNode::Variable { statement, .. } | ASTNode::Script { statement, .. } => {
+                self.js_output.push_str(&statement.to_js(0));
+            }
+            ASTNode::StateVariable { name, initial, .. } => {
+                let initial = initial.as_ref().map_or("undefined".to_string(), |initial| initial.to_string());
+                self.js_output.push_str(&format!("let {} = {};\n", name, initial));
+            }
+            ASTNode::Element { .. } | ASTNode::Text { .. } | ASTNode::Interpolation { .. } => {
+                let html = self.render_markup(node, &mut MarkupTarget::Page);
+                self.html_output.push_str(&html);
+                self.html_output.push('\n');
+            }
+            _ => {
+                // Handle other node types
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_page(&mut self, name: &str, attributes: &[Attribute], body: &[ASTNode]) -> Result<(), Diagnostic> {
+        self.page_attributes = attributes.iter()
+            .filter_map(|attribute| match &attribute.value {
+                AttributeValue::Static(value) => Some((attribute.name.clone(), value.clone())),
+                _ => None,
+            })
+            .collect();
+
+        // The tag form splits the page into <head> and <body>; anything else
+        // at the top level goes into the body.
+        let mut head: Vec<&ASTNode> = Vec::new();
+        let mut content: Vec<&ASTNode> = Vec::new();
+        let mut body_attributes: &[Attribute] = &[];
+        for child in body {
+            match child {
+                ASTNode::Element { tag_name, children, .. } if tag_name == "head" => head.extend(children),
+                ASTNode::Element { tag_name, attributes, children, .. } if tag_name == "body" => {
+                    body_attributes = attributes;
+                    content.extend(children);
+                }
+                _ => content.push(child),
             }
-            ASTNode::Export { specifiers, source, is_default } => {
-                self.generate_export(specifiers, source, *is_default)?;
+        }
+
+        let lang = self.page_attributes.get("lang").cloned().unwrap_or_else(|| "en".to_string());
+        self.html_output.push_str(&format!("<!DOCTYPE html>\n<html lang=\"{}\"", escape_html(&lang)));
+        if let Some(theme) = self.page_attributes.get("theme") {
+            self.html_output.push_str(&format!(" data-theme=\"{}\"", escape_html(theme)));
+        }
+        self.html_output.push_str(">\n<head>\n");
+
+        // Defaults the page's own <head> doesn't already provide
+        if !head.iter().any(|node| element_has(node, "meta", "charset", None)) {
+            self.html_output.push_str("  <meta charset=\"UTF-8\">\n");
+        }
+        if !head.iter().any(|node| element_has(node, "meta", "name", Some("viewport"))) {
+            self.html_output.push_str("  <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n");
+        }
+        for node in &head {
+            let html = self.render_markup(node, &mut MarkupTarget::Page);
+            self.html_output.push_str(&format!("  {}\n", html));
+        }
+        if !head.iter().any(|node| matches!(node, ASTNode::Element { tag_name, .. } if tag_name == "title")) {
+            let title = attributes.iter().find(|attribute| attribute.name == "title");
+            if let Some(title) = title.and_then(|attribute| self.static_text(&attribute.value)) {
+                self.html_output.push_str(&format!("  <title>{}</title>\n", title));
             }
-            _ => {
-                // Handle other node types
+        }
+        for href in ["css/theme.css", "css/emadocs.css", "styles.css"] {
+            if !head.iter().any(|node| element_has(node, "link", "href", Some(href))) {
+                self.html_output.push_str(&format!("  <link rel=\"stylesheet\" href=\"{}\">\n", href));
             }
         }
-        Ok(())
-    }
 
-    fn generate_page(&mut self, name: &str, attributes: &HashMap<String, String>, body: &[ASTNode]) -> Result<(), String> {
-        self.html_output.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
-        self.html_output.push_str("  <meta charset=\"UTF-8\">\n");
-        self.html_output.push_str("  <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n");
-        
-        if let Some(title) = attributes.get("title") {
-            self.html_output.push_str(&format!("  <title>{}</title>\n", title));
+        self.html_output.push_str("</head>\n<body");
+        for attribute in body_attributes {
+            let html = self.render_attribute(attribute, &mut MarkupTarget::Page);
+            self.html_output.push_str(&html);
         }
-        
-        self.html_output.push_str("  <link rel=\"stylesheet\" href=\"css/theme.css\">\n");
-        self.html_output.push_str("  <link rel=\"stylesheet\" href=\"css/emadocs.css\">\n");
-        self.html_output.push_str("  <link rel=\"stylesheet\" href=\"styles.css\">\n");
-        self.html_output.push_str("</head>\n<body>\n");
-        
-        for child in body {
-            self.visit_node(child)?;
+        self.html_output.push_str(">\n");
+
+        for child in content {
+            if is_markup(child) {
+                let html = self.render_markup(child, &mut MarkupTarget::Page);
+                self.html_output.push_str(&format!("  {}\n", html));
+            } else {
+                self.visit_node(child)?;
+            }
         }
         
         self.html_output.push_str("  <script src=\"js/emadocs.js\"></script>\n");
@@ -1244,7 +7100,14 @@ impl CodeGenerator {
         Ok(())
     }
 
-    fn generate_component(&mut self, name: &str, type_params: &[String]) -> Result<(), String> {
+    fn generate_component(&mut self, component: &ASTNode) -> Result<(), Diagnostic> {
+        let (name, props, state, methods, computed, watchers, lifecycle, render) = match component {
+            ASTNode::Component { name, props, state, methods, computed, watchers, lifecycle, render, .. } => {
+                (name, props, state, methods, computed, watchers, lifecycle, render)
+            }
+            _ => return Ok(()),
+        };
+
         // Generate component CSS
         self.css_output.push_str(&format!("/* {} Component */\n", name));
         self.css_output.push_str(&format!(".ema-{} {{\n", name.to_lowercase()));
@@ -1254,25 +7117,248 @@ impl CodeGenerator {
         self.css_output.push_str("}\n\n");
         
         // Generate component JavaScript
+        if !render.is_empty() && !self.template_runtime {
+            // Values a template interpolates, as escaped text, and as an
+            // attribute that is left out when false or missing, objects and
+            // arrays as JSON for the prop they set
+            self.template_runtime = true;
+            self.js_output.push_str("function escapeHtml(value) {\n");
+            self.js_output.push_str("  return String(value ?? '')\n");
+            self.js_output.push_str("    .replace(/&/g, '&amp;')\n");
+            self.js_output.push_str("    .replace(/</g, '&lt;')\n");
+            self.js_output.push_str("    .replace(/>/g, '&gt;')\n");
+            self.js_output.push_str("    .replace(/\"/g, '&quot;')\n");
+            self.js_output.push_str("    .replace(/'/g, '&#39;');\n");
+            self.js_output.push_str("}\n\n");
+            self.js_output.push_str("function htmlAttribute(name, value) {\n");
+            self.js_output.push_str("  if (value === false || value === null || value === undefined) {\n");
+            self.js_output.push_str("    return '';\n");
+            self.js_output.push_str("  }\n");
+            self.js_output.push_str("  if (value === true) {\n");
+            self.js_output.push_str("    return ` ${name}`;\n");
+            self.js_output.push_str("  }\n");
+            self.js_output.push_str("  const text = typeof value === 'object' ? JSON.stringify(value) : value;\n");
+            self.js_output.push_str("  return ` ${name}=\"${escapeHtml(text)}\"`;\n");
+            self.js_output.push_str("}\n\n");
+        }
+        if !props.is_empty() && !self.prop_runtime {
+            // A prop's value from its attribute, converted to the prop's
+            // type, or its default when the attribute is missing or can't be
+            // converted
+            self.prop_runtime = true;
+            self.js_output.push_str("function readProp(element, attribute, type, fallback) {\n");
+            self.js_output.push_str("  const value = element.getAttribute(attribute);\n");
+            self.js_output.push_str("  if (value === null) {\n");
+            self.js_output.push_str("    return fallback;\n");
+            self.js_output.push_str("  }\n");
+            self.js_output.push_str("  switch (type) {\n");
+            self.js_output.push_str("    case 'boolean':\n");
+            self.js_output.push_str("      return value !== 'false';\n");
+            self.js_output.push_str("    case 'number':\n");
+            self.js_output.push_str("      return value.trim() === '' || Number.isNaN(Number(value)) ? fallback : Number(value);\n");
+            self.js_output.push_str("    case 'object':\n");
+            self.js_output.push_str("    case 'array':\n");
+            self.js_output.push_str("      try {\n");
+            self.js_output.push_str("        return JSON.parse(value);\n");
+            self.js_output.push_str("      } catch {\n");
+            self.js_output.push_str("        return fallback;\n");
+            self.js_output.push_str("      }\n");
+            self.js_output.push_str("    case 'function':\n");
+            self.js_output.push_str("      return fallback;\n");
+            self.js_output.push_str("    default:\n");
+            self.js_output.push_str("      return value;\n");
+            self.js_output.push_str("  }\n");
+            self.js_output.push_str("}\n\n");
+        }
         self.js_output.push_str(&format!("class Ema{} extends HTMLElement {{\n", self.capitalize(name)));
         self.js_output.push_str("  constructor() {\n");
         self.js_output.push_str("    super();\n");
         self.js_output.push_str("    this.attachShadow({ mode: 'open' });\n");
+        let mut state_names = Vec::new();
+        if !state.is_empty() {
+            self.js_output.push_str("    this.state = {\n");
+            for variable in state {
+                if let ASTNode::StateVariable { name, initial, .. } = variable {
+                    let initial = initial.as_ref().map_or("undefined".to_string(), |initial| initial.to_string());
+                    self.js_output.push_str(&format!("      {}: {},\n", name, initial));
+                    state_names.push(name.as_str());
+                }
+            }
+            self.js_output.push_str("    };\n");
+        }
+        if !computed.is_empty() {
+            self.js_output.push_str("    this.computedCache = new Map();\n");
+        }
+        let members: Vec<&str> = props.iter()
+            .chain(computed)
+            .chain(methods)
+            .filter_map(|member| match member {
+                ASTNode::Prop { name, .. } | ASTNode::Computed { name, .. } | ASTNode::Method { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut instance = InstanceNames::new(&state_names, &members);
+        if !watchers.is_empty() {
+            // Watchers of the same state run in declaration order
+            let mut targets: Vec<&str> = Vec::new();
+            let mut handlers: HashMap<&str, Vec<String>> = HashMap::new();
+            for watcher in watchers {
+                if let ASTNode::Watch { target, parameters, body, span } = watcher {
+                    if !state_names.contains(&target.as_str()) {
+                        self.diagnostics.push(Diagnostic::new(
+                            codes::UNKNOWN_WATCH_TARGET,
+                            format!("`{}` is not a state variable of `{}`", target, name),
+                            span.clone(),
+                        ));
+                    }
+                    if !targets.contains(&target.as_str()) {
+                        targets.push(target);
+                    }
+                    let mut parameters = parameters.clone();
+                    let mut body = body.clone();
+                    instance.scoped(&mut parameters, |names| names.block(&mut body));
+                    handlers.entry(target).or_default().push(format!(
+                        "        ({}) => {},\n",
+                        parameters_source(&parameters),
+                        block_js(&body, 4)
+                    ));
+                }
+            }
+            self.js_output.push_str("    this.watchers = {\n");
+            for target in targets {
+                self.js_output.push_str(&format!("      {}: [\n", target));
+                for handler in &handlers[target] {
+                    self.js_output.push_str(handler);
+                }
+                self.js_output.push_str("      ],\n");
+            }
+            self.js_output.push_str("    };\n");
+        }
+        self.js_output.push_str("  }\n\n");
+        let mut observed = Vec::new();
+        let mut descriptors = Vec::new();
+        let mut accessors = Vec::new();
+        for prop in props {
+            if let ASTNode::Prop { name, prop_type, default, span } = prop {
+                if let Some(default) = default {
+                    if !prop_type.accepts(default) {
+                        self.diagnostics.push(Diagnostic::new(
+                            codes::PROP_DEFAULT_TYPE,
+                            format!("default {} of prop `{}` is not a `{}`", default.to_js(), name, prop_type),
+                            span.clone(),
+                        ));
+                    }
+                }
+                let attribute = attribute_name(name);
+                observed.push(quote_js_string(&attribute));
+                descriptors.push(format!("      '{}': {},\n", name, prop_descriptor(prop_type, default)));
+                let key = if is_identifier_name(name) { name.clone() } else { quote_js_string(name) };
+                accessors.push(format!(
+                    "  get {}() {{\n    return readProp(this, {}, '{}', {});\n  }}\n\n",
+                    key,
+                    quote_js_string(&attribute),
+                    prop_type.runtime_type(),
+                    default.as_ref().map_or("undefined".to_string(), Value::to_js)
+                ));
+            }
+        }
+        if !observed.is_empty() {
+            self.js_output.push_str("  static get observedAttributes() {\n");
+            self.js_output.push_str(&format!("    return [{}];\n", observed.join(", ")));
+            self.js_output.push_str("  }\n\n");
+            self.js_output.push_str("  static get props() {\n");
+            self.js_output.push_str("    return {\n");
+            for descriptor in &descriptors {
+                self.js_output.push_str(descriptor);
+            }
+            self.js_output.push_str("    };\n");
+            self.js_output.push_str("  }\n\n");
+        }
+        for accessor in &accessors {
+            self.js_output.push_str(accessor);
+        }
+        // A changed attribute changes a prop, so renders like a state change
+        self.js_output.push_str("  attributeChangedCallback() {\n");
+        if !computed.is_empty() {
+            self.js_output.push_str("    this.computedCache.clear();\n");
+        }
+        self.js_output.push_str("    if (this.isConnected) {\n");
+        self.js_output.push_str("      this.render();\n");
+        self.js_output.push_str("    }\n");
         self.js_output.push_str("  }\n\n");
         self.js_output.push_str("  connectedCallback() {\n");
         self.js_output.push_str("    this.render();\n");
         self.js_output.push_str("    this.setupEventListeners();\n");
+        self.js_output.push_str(&lifecycle_js(lifecycle, LifecycleHook::Mounted, &mut instance));
         self.js_output.push_str("  }\n\n");
+        if lifecycle.iter().any(|node| matches!(node, ASTNode::Lifecycle { hook: LifecycleHook::Unmounted, .. })) {
+            self.js_output.push_str("  disconnectedCallback() {\n");
+            self.js_output.push_str(&lifecycle_js(lifecycle, LifecycleHook::Unmounted, &mut instance));
+            self.js_output.push_str("  }\n\n");
+        }
+        if !state.is_empty() || !watchers.is_empty() {
+            // Changing state drops cached computed values, runs the watchers
+            // with the new and previous value, then renders again
+            self.js_output.push_str("  setState(name, value) {\n");
+            self.js_output.push_str("    const previous = this.state[name];\n");
+            self.js_output.push_str("    if (Object.is(previous, value)) {\n");
+            self.js_output.push_str("      return;\n");
+            self.js_output.push_str("    }\n");
+            self.js_output.push_str("    this.state[name] = value;\n");
+            if !computed.is_empty() {
+                self.js_output.push_str("    this.computedCache.clear();\n");
+            }
+            if !watchers.is_empty() {
+                self.js_output.push_str("    for (const watcher of this.watchers[name] || []) {\n");
+                self.js_output.push_str("      watcher(value, previous);\n");
+                self.js_output.push_str("    }\n");
+            }
+            self.js_output.push_str("    if (this.isConnected) {\n");
+            self.js_output.push_str("      this.render();\n");
+            self.js_output.push_str("    }\n");
+            self.js_output.push_str("  }\n\n");
+        }
+        for getter in computed {
+            if let ASTNode::Computed { name, body, .. } = getter {
+                self.js_output.push_str(&format!("  get {}() {{\n", name));
+                self.js_output.push_str(&format!("    if (!this.computedCache.has('{}')) {{\n", name));
+                let body = instance.resolve_body(body);
+                self.js_output.push_str(&format!("      this.computedCache.set('{}', (() => {})());\n", name, block_js(&body, 3)));
+                self.js_output.push_str("    }\n");
+                self.js_output.push_str(&format!("    return this.computedCache.get('{}');\n", name));
+                self.js_output.push_str("  }\n\n");
+            }
+        }
         self.js_output.push_str("  render() {\n");
         self.js_output.push_str("    this.shadowRoot.innerHTML = this.getTemplate();\n");
         self.js_output.push_str("  }\n\n");
         self.js_output.push_str("  getTemplate() {\n");
         self.js_output.push_str("    return `\n");
-        self.js_output.push_str(&format!("      <div class=\"ema-{}\">\n", name.to_lowercase()));
-        self.js_output.push_str("        <slot></slot>\n");
-        self.js_output.push_str("      </div>\n");
+        for root in render {
+            let html = self.render_markup(root, &mut MarkupTarget::Template(&mut instance));
+            self.js_output.push_str(&format!("      {}\n", html));
+        }
+        if render.is_empty() {
+            self.js_output.push_str(&format!("      <div class=\"ema-{}\">\n", name.to_lowercase()));
+            self.js_output.push_str("        <slot></slot>\n");
+            self.js_output.push_str("      </div>\n");
+        }
         self.js_output.push_str("    `;\n");
         self.js_output.push_str("  }\n\n");
+        for method in methods {
+            if let ASTNode::Method { name, is_async, parameters, body, .. } = method {
+                let mut parameters = parameters.clone();
+                let mut body = body.clone();
+                instance.scoped(&mut parameters, |names| names.block(&mut body));
+                self.js_output.push_str(&format!(
+                    "  {}{}({}) {}\n\n",
+                    if *is_async { "async " } else { "" },
+                    name,
+                    parameters_source(&parameters),
+                    block_js(&body, 1)
+                ));
+            }
+        }
         self.js_output.push_str("  setupEventListeners() {\n");
         self.js_output.push_str("    // Event listeners will be added here\n");
         self.js_output.push_str("  }\n");
@@ -1282,23 +7368,106 @@ impl CodeGenerator {
         Ok(())
     }
 
-    fn generate_style(&mut self, selector: &str, rules: &[ASTNode]) -> Result<(), String> {
-        self.css_output.push_str(&format!("/* {} Styles */\n", selector));
-        self.css_output.push_str(&format!(".{} {{\n", selector));
-        self.css_output.push_str("  /* Styles will be generated here */\n");
-        self.css_output.push_str("}\n\n");
+    fn generate_style(&mut self, selectors: &[Selector], rules: &[ASTNode]) -> Result<(), Diagnostic> {
+        let names: Vec<String> = selectors.iter().map(Selector::to_string).collect();
+        self.css_output.push_str(&format!("/* {} Styles */\n", names.join(", ")));
+
+        // A component's block scopes its own declarations to the class the
+        // component's element carries, while its nested rules name the
+        // classes its render uses
+        let detached = selectors.iter().any(|selector| selector.component_name().is_some());
+        let parents: Vec<String> = selectors
+            .iter()
+            .map(|selector| match selector.component_name() {
+                Some(name) => format!(".{}", element_name(name)),
+                None => selector.to_css(None),
+            })
+            .collect();
+
+        self.css_output.push_str(&css_rules(&parents, rules, detached, 0));
+        self.css_output.push('\n');
         Ok(())
     }
 
-    fn generate_event(&mut self, event_type: &str, target: &str, body: &[ASTNode]) -> Result<(), String> {
-        self.js_output.push_str(&format!("// Event: {} on {}\n", event_type, target));
-        self.js_output.push_str(&format!("document.addEventListener('{}', (event) => {{\n", event_type));
-        self.js_output.push_str("  // Event handler code\n");
-        self.js_output.push_str("});\n");
+    fn generate_event(&mut self, event_type: &str, target: &[Selector], parameters: &[ASTNode], body: &[Stmt]) -> Result<(), Diagnostic> {
+        if target.is_empty() {
+            // A named handler, as in `event onAddTodo(text) { ... }`; its
+            // body may await
+            self.js_output.push_str(&format!("// Event: {}\n", event_type));
+            self.js_output.push_str(&format!(
+                "async function {}({}) {}\n",
+                event_type,
+                parameters_source(parameters),
+                block_js(body, 0)
+            ));
+            return Ok(());
+        }
+
+        if !self.event_runtime {
+            // Runs a handler for events on `root`, or with a selector, for
+            // events inside a matching element, passing the event, the
+            // element it matched and the helpers. Events that don't bubble
+            // reach a matching element's ancestors in the capture phase only
+            self.event_runtime = true;
+            self.js_output.push_str("function delegateEvent(root, type, selector, handler, capture = false) {\n");
+            self.js_output.push_str("  root.addEventListener(type, (event) => {\n");
+            self.js_output.push_str("    let target = root;\n");
+            self.js_output.push_str("    if (selector) {\n");
+            self.js_output.push_str("      target = event.target instanceof Element ? event.target.closest(selector) : null;\n");
+            self.js_output.push_str("      if (!target) {\n");
+            self.js_output.push_str("        return;\n");
+            self.js_output.push_str("      }\n");
+            self.js_output.push_str("    }\n");
+            self.js_output.push_str("    handler(event, target, {\n");
+            for helper in EVENT_HELPERS {
+                self.js_output.push_str(&format!("      {}: () => event.{}(),\n", helper, helper));
+            }
+            self.js_output.push_str("    });\n");
+            self.js_output.push_str("  }, capture);\n");
+            self.js_output.push_str("}\n");
+            self.js_output.push('\n');
+        }
+
+        let selectors: Vec<String> = target.iter().map(Selector::to_string).collect();
+        let selector = selectors.join(", ");
+        // `page`, `window` and `document` listen on that object instead of
+        // matching elements
+        let (root, matching) = match selector.as_str() {
+            "page" | "window" => ("window", "null".to_string()),
+            "document" => ("document", "null".to_string()),
+            _ => ("document", quote_js_string(&selector)),
+        };
+        // Declared parameters rename `event` and `target`, in that order;
+        // the helpers always come third
+        let mut arguments: Vec<String> = parameters
+            .iter()
+            .map(|parameter| parameters_source(std::slice::from_ref(parameter)))
+            .filter(|argument| !argument.is_empty())
+            .collect();
+        let helpers = format!("{{ {} }}", EVENT_HELPERS.join(", "));
+        for (index, default) in ["event", "target"].into_iter().enumerate() {
+            if arguments.len() == index {
+                let taken = arguments.iter().any(|argument| argument == default);
+                arguments.push(if taken { format!("_{}", default) } else { default.to_string() });
+            }
+        }
+        arguments.insert(2, helpers);
+        let capture = if matching != "null" && NON_BUBBLING_EVENTS.contains(&event_type) { ", true" } else { "" };
+
+        self.js_output.push_str(&format!("// Event: {} on {}\n", event_type, selector));
+        self.js_output.push_str(&format!(
+            "delegateEvent({}, '{}', {}, async ({}) => {}{});\n",
+            root,
+            event_type,
+            matching,
+            arguments.join(", "),
+            block_js(body, 0),
+            capture
+        ));
         Ok(())
     }
 
-    fn generate_state(&mut self, name: &str, properties: &[ASTNode]) -> Result<(), String> {
+    fn generate_state(&mut self, name: &str, properties: &[ASTNode]) -> Result<(), Diagnostic> {
         self.js_output.push_str(&format!("// State: {}\n", name));
         self.js_output.push_str(&format!("const {} = {{\n", name));
         self.js_output.push_str("  // State properties will be generated here\n");
@@ -1306,7 +7475,7 @@ impl CodeGenerator {
         Ok(())
     }
 
-    fn generate_api(&mut self, name: &str, methods: &[ASTNode]) -> Result<(), String> {
+    fn generate_api(&mut self, name: &str, methods: &[ASTNode]) -> Result<(), Diagnostic> {
         self.js_output.push_str(&format!("// API: {}\n", name));
         self.js_output.push_str(&format!("class {} {{\n", name));
         self.js_output.push_str("  constructor() {\n");
@@ -1317,11 +7486,11 @@ impl CodeGenerator {
         Ok(())
     }
 
-    fn generate_router(&mut self, routes: &[ASTNode]) -> Result<(), String> {
+    fn generate_router(&mut self, routes: &[ASTNode]) -> Result<(), Diagnostic> {
         self.js_output.push_str("// Router\n");
         self.js_output.push_str("class EmadocsRouter {\n");
-        self.js_output.push_str("  constructor() {\n");
-        self.js_output.push_str("    this.routes = new Map();\n");
+        self.js_output.push_str("  constructor(routes) {\n");
+        self.js_output.push_str("    this.routes = routes;\n");
         self.js_output.push_str("    this.init();\n");
         self.js_output.push_str("  }\n\n");
         self.js_output.push_str("  init() {\n");
@@ -1330,87 +7499,391 @@ impl CodeGenerator {
         self.js_output.push_str("  }\n\n");
         self.js_output.push_str("  handleRoute() {\n");
         self.js_output.push_str("    const path = window.location.pathname;\n");
-        self.js_output.push_str("    // Route handling logic\n");
+        self.js_output.push_str("    for (const route of this.routes) {\n");
+        self.js_output.push_str("      const match = route.pattern.exec(path);\n");
+        self.js_output.push_str("      if (match) {\n");
+        self.js_output.push_str("        const params = {};\n");
+        self.js_output.push_str("        route.params.forEach((name, index) => {\n");
+        self.js_output.push_str("          params[name] = decodeURIComponent(match[index + 1] || '');\n");
+        self.js_output.push_str("        });\n");
+        self.js_output.push_str("        this.current = { route, params };\n");
+        self.js_output.push_str("        window.dispatchEvent(new CustomEvent('ema:route', { detail: this.current }));\n");
+        self.js_output.push_str("        return;\n");
+        self.js_output.push_str("      }\n");
+        self.js_output.push_str("    }\n");
         self.js_output.push_str("  }\n");
-        self.js_output.push_str("}\n");
+        self.js_output.push_str("}\n\n");
+
+        self.js_output.push_str("const router = new EmadocsRouter([\n");
+        for route in routes {
+            if let ASTNode::Route { path, params, component, options, .. } = route {
+                let params: Vec<String> = params.iter().map(|name| quote_js_string(name)).collect();
+                let options = Expr::Object(options
+                    .iter()
+                    .map(|(key, value)| Property::KeyValue { key: key.clone(), value: value.clone() })
+                    .collect());
+                self.js_output.push_str(&format!(
+                    "  {{ path: {}, pattern: {}, params: [{}], component: {}, options: {} }},\n",
+                    quote_js_string(path),
+                    route_pattern(path),
+                    params.join(", "),
+                    quote_js_string(&element_name(component)),
+                    options
+                ));
+            }
+        }
+        self.js_output.push_str("]);\n");
         Ok(())
     }
 
-    fn generate_layout(&mut self, name: &str, render: &Option<Box<ASTNode>>) -> Result<(), String> {
+    fn generate_layout(&mut self, name: &str, render: &[ASTNode]) -> Result<(), Diagnostic> {
         self.js_output.push_str(&format!("// Layout: {}\n", name));
         self.js_output.push_str("// Layout implementation will be generated here\n");
         Ok(())
     }
 
-    fn generate_animation(&mut self, name: &str, keyframes: &[ASTNode]) -> Result<(), String> {
+    fn generate_animation(&mut self, name: &str, keyframes: &[ASTNode]) -> Result<(), Diagnostic> {
         self.css_output.push_str(&format!("@keyframes {} {{\n", name));
-        self.css_output.push_str("  /* Keyframes will be generated here */\n");
+        self.css_output.push_str(&css_rules(&[], keyframes, false, 1));
         self.css_output.push_str("}\n\n");
         Ok(())
     }
 
-    fn generate_type(&mut self, name: &str, definition: &Option<Box<ASTNode>>) -> Result<(), String> {
+    fn generate_type(&mut self, name: &str, type_params: &[TypeParameter], definition: &TypeExpr) -> Result<(), Diagnostic> {
         self.js_output.push_str(&format!("// Type: {}\n", name));
-        self.js_output.push_str("// Type definition will be generated here\n");
+        self.js_output.push_str(&typedef_comment(name, type_params, &definition.to_string()));
         Ok(())
     }
 
-    fn generate_hook(&mut self, name: &str, parameters: &[ASTNode], body: &[ASTNode]) -> Result<(), String> {
-        self.js_output.push_str(&format!("function use{}() {{\n", self.capitalize(name)));
-        self.js_output.push_str("  // Hook implementation\n");
-        self.js_output.push_str("}\n");
+    /// Emits an enum as a frozen object. Members without a value continue
+    /// from the previous numeric member, starting at 0.
+    fn generate_enum(&mut self, name: &str, members: &[EnumMember]) -> Result<(), Diagnostic> {
+        let mut properties = Vec::new();
+        let mut next = Some(0.0);
+        for member in members {
+            let value = match &member.value {
+                Some(value) => value.clone(),
+                None => match next {
+                    Some(number) => Expr::Literal(Value::Number(number)),
+                    None => {
+                        let message = format!("enum member `{}` needs a value", member.name);
+                        return Err(Diagnostic::new(codes::MISSING_ENUM_VALUE, message, member.span.clone()));
+                    }
+                },
+            };
+            next = match &value {
+                Expr::Literal(Value::Number(number)) => Some(number + 1.0),
+                _ => None,
+            };
+            properties.push(Property::KeyValue { key: member.name.clone(), value });
+        }
+
+        self.js_output.push_str(&format!("// Enum: {}\n", name));
+        self.js_output.push_str(&format!("const {} = Object.freeze({});\n", name, Expr::Object(properties)));
+        Ok(())
+    }
+
+    fn generate_hook(&mut self, name: &str, parameters: &[ASTNode], body: &[Stmt]) -> Result<(), Diagnostic> {
+        let name = if name.starts_with("use") { name.to_string() } else { format!("use{}", self.capitalize(name)) };
+        self.js_output.push_str(&format!("function {}({}) {}\n", name, parameters_source(parameters), block_js(body, 0)));
         Ok(())
     }
 
-    fn generate_plugin(&mut self, name: &str, properties: &HashMap<String, String>) -> Result<(), String> {
+    fn generate_plugin(&mut self, name: &str, properties: &[(String, Value)], signatures: &[(String, TypeExpr)], methods: &[ASTNode]) -> Result<(), Diagnostic> {
+        if !signatures.is_empty() {
+            let signatures = TypeExpr::Object(signatures.to_vec()).to_string();
+            self.js_output.push_str(&typedef_comment(&format!("{}Methods", name), &[], &signatures));
+        }
+
         self.js_output.push_str(&format!("class {} {{\n", name));
         self.js_output.push_str("  constructor() {\n");
-        self.js_output.push_str("    // Plugin initialization\n");
+        for (key, value) in properties {
+            if is_identifier_name(key) {
+                self.js_output.push_str(&format!("    this.{} = {};\n", key, value.to_js()));
+            } else {
+                self.js_output.push_str(&format!("    this[{}] = {};\n", quote_js_string(key), value.to_js()));
+            }
+        }
         self.js_output.push_str("  }\n");
+        for method in methods {
+            if let ASTNode::Method { name, is_async, parameters, body, .. } = method {
+                self.js_output.push_str(&format!(
+                    "\n  {}{}({}) {}\n",
+                    if *is_async { "async " } else { "" },
+                    name,
+                    parameters_source(parameters),
+                    block_js(body, 1)
+                ));
+            }
+        }
         self.js_output.push_str("}\n");
         Ok(())
     }
 
-    fn generate_config(&mut self, properties: &HashMap<String, String>) -> Result<(), String> {
+    fn generate_config(&mut self, properties: &[(String, Value)]) -> Result<(), Diagnostic> {
         self.js_output.push_str("const config = {\n");
-        self.js_output.push_str("  // Configuration will be generated here\n");
+        for (key, value) in properties {
+            let key = if is_identifier_name(key) { key.clone() } else { quote_js_string(key) };
+            self.js_output.push_str(&format!("  {}: {},\n", key, value.to_js()));
+        }
         self.js_output.push_str("};\n");
         Ok(())
     }
 
-    fn generate_import(&mut self, specifiers: &[String], source: &str) -> Result<(), String> {
-        self.js_output.push_str("import ");
-        if specifiers.len() == 1 {
-            self.js_output.push_str(&specifiers[0]);
+    fn generate_import(&mut self, default: &Option<String>, namespace: &Option<String>, specifiers: &[ModuleSpecifier], source: &str) -> Result<(), Diagnostic> {
+        let mut bindings = Vec::new();
+        if let Some(default) = default {
+            bindings.push(default.clone());
+        }
+        if let Some(namespace) = namespace {
+            bindings.push(format!("* as {}", namespace));
+        }
+        if !specifiers.is_empty() {
+            bindings.push(format!("{{ {} }}", specifiers_js(specifiers)));
+        }
+
+        if bindings.is_empty() {
+            self.js_output.push_str(&format!("import {};\n", quote_js_string(source)));
         } else {
-            self.js_output.push_str("{ ");
-            self.js_output.push_str(&specifiers.join(", "));
-            self.js_output.push_str(" }");
+            self.js_output.push_str(&format!("import {} from {};\n", bindings.join(", "), quote_js_string(source)));
         }
-        if !source.is_empty() {
-            self.js_output.push_str(&format!(" from '{}'", source));
+        Ok(())
+    }
+
+    fn generate_export(&mut self, clause: &ExportClause, source: &str, is_default: bool) -> Result<(), Diagnostic> {
+        let from = if source.is_empty() { String::new() } else { format!(" from {}", quote_js_string(source)) };
+        match clause {
+            ExportClause::Named(specifiers) => {
+                self.js_output.push_str(&format!("export {{ {} }}{};\n", specifiers_js(specifiers), from));
+            }
+            ExportClause::All(Some(namespace)) => {
+                self.js_output.push_str(&format!("export * as {}{};\n", namespace, from));
+            }
+            ExportClause::All(None) => self.js_output.push_str(&format!("export *{};\n", from)),
+            ExportClause::Expression(expression) => {
+                self.js_output.push_str(&format!("export default {};\n", expression));
+            }
+            ExportClause::Variable(statement) => {
+                self.js_output.push_str(&format!("export {}", statement.to_js(0)));
+            }
+            ExportClause::Declaration(declaration) if is_default && matches!(&**declaration, ASTNode::Method { name, .. } if name.is_empty()) => {
+                // `export default function () {}` has no binding to export
+                self.js_output.push_str("export default ");
+                self.visit_node(declaration)?;
+            }
+            ExportClause::Declaration(declaration) => {
+                // Generate the declaration as usual, then export the binding
+                // it produced under its declared name
+                self.visit_node(declaration)?;
+                if let Some((binding, name)) = self.declared_binding(declaration) {
+                    if is_default {
+                        self.js_output.push_str(&format!("export default {};\n", binding));
+                    } else if binding == name {
+                        self.js_output.push_str(&format!("export {{ {} }};\n", binding));
+                    } else {
+                        self.js_output.push_str(&format!("export {{ {} as {} }};\n", binding, name));
+                    }
+                }
+            }
         }
-        self.js_output.push_str(";\n");
         Ok(())
     }
 
-    fn generate_export(&mut self, specifiers: &[String], source: &str, is_default: bool) -> Result<(), String> {
-        self.js_output.push_str("export ");
-        if is_default {
-            self.js_output.push_str("default ");
+    /// The JavaScript binding a declaration generates and the name it was
+    /// declared with, as in `EmaButton` for `component Button`. Types and
+    /// other declarations without a binding have none.
+    fn declared_binding(&self, declaration: &ASTNode) -> Option<(String, String)> {
+        match declaration {
+            ASTNode::Component { name, .. } => Some((format!("Ema{}", self.capitalize(name)), name.clone())),
+            ASTNode::Hook { name, .. } if !name.starts_with("use") => {
+                Some((format!("use{}", self.capitalize(name)), name.clone()))
+            }
+            ASTNode::Event { event_type, target, .. } if target.is_empty() => {
+                Some((event_type.clone(), event_type.clone()))
+            }
+            ASTNode::Method { name, .. }
+            | ASTNode::Hook { name, .. }
+            | ASTNode::State { name, .. }
+            | ASTNode::Plugin { name, .. }
+            | ASTNode::Enum { name, .. } if !name.is_empty() => Some((name.clone(), name.clone())),
+            _ => None,
         }
-        if specifiers.len() == 1 {
-            self.js_output.push_str(&specifiers[0]);
-        } else {
-            self.js_output.push_str("{ ");
-            self.js_output.push_str(&specifiers.join(", "));
-            self.js_output.push_str(" }");
+    }
+
+    /// Renders markup to HTML, with text and interpolated values escaped.
+    /// Page values only known at runtime become `data-ema-bind`
+    /// placeholders that the page's script fills in; in a component's
+    /// template they are evaluated on the element each render.
+    fn render_markup(&mut self, node: &ASTNode, target: &mut MarkupTarget) -> String {
+        match node {
+            ASTNode::Element { tag_name, attributes, children, .. } => {
+                // A template's `<[[type]]>` takes its name from the element
+                let tag_name = &match (tag_name.strip_prefix("[[").and_then(|name| name.strip_suffix("]]")), &mut *target) {
+                    (Some(name), MarkupTarget::Template(instance)) if is_identifier_name(name) => {
+                        format!("${{escapeHtml({})}}", instance.resolve_expr(&Expr::Identifier(name.to_string())))
+                    }
+                    _ => element_name(tag_name),
+                };
+                let mut html = format!("<{}", tag_name);
+                for attribute in attributes {
+                    html.push_str(&self.render_attribute(attribute, target));
+                }
+                html.push('>');
+                if is_void_tag(tag_name) {
+                    return html;
+                }
+                for child in children {
+                    html.push_str(&self.render_markup(child, target));
+                }
+                html.push_str(&format!("</{}>", tag_name));
+                html
+            }
+            ASTNode::Text { value, .. } => target.literal(&escape_text(value)),
+            ASTNode::Interpolation { expression, .. } => match self.resolve_static(expression) {
+                Some(value) => target.literal(&escape_html(&value)),
+                None => self.render_value(expression, target),
+            },
+            ASTNode::Expression { expression, .. } => self.render_value(expression, target),
+            _ => String::new(),
         }
-        if !source.is_empty() {
-            self.js_output.push_str(&format!(" from '{}'", source));
+    }
+
+    /// A value only known at runtime, as text.
+    fn render_value(&mut self, expression: &Expr, target: &mut MarkupTarget) -> String {
+        match target {
+            MarkupTarget::Page => format!("<span data-ema-bind=\"{}\"></span>", self.bind(expression.to_string())),
+            MarkupTarget::Template(instance) => format!("${{{}}}", self.template_value(expression, instance)),
+        }
+    }
+
+    /// A template value as HTML: markup it evaluates to renders as a nested
+    /// template literal the way the render's own children do, and any other
+    /// value is escaped. A list of markup, as `items.map(item => <li />)`
+    /// gives, is joined.
+    fn template_value(&mut self, expression: &Expr, instance: &mut InstanceNames) -> Expr {
+        let escaped = |instance: &mut InstanceNames| Expr::Call {
+            callee: Box::new(Expr::Identifier("escapeHtml".to_string())),
+            arguments: vec![instance.resolve_expr(expression)],
+            optional: false,
+        };
+        if !contains_markup(expression) {
+            return escaped(instance);
+        }
+        match expression {
+            Expr::Markup(node) => Expr::Template {
+                quasis: vec![self.render_markup(node, &mut MarkupTarget::Template(instance))],
+                expressions: Vec::new(),
+            },
+            Expr::Conditional { test, consequent, alternate } => Expr::Conditional {
+                test: Box::new(instance.resolve_expr(test)),
+                consequent: Box::new(self.template_value(consequent, instance)),
+                alternate: Box::new(self.template_value(alternate, instance)),
+            },
+            Expr::Binary { left, right, .. } => Expr::Conditional {
+                test: Box::new(instance.resolve_expr(left)),
+                consequent: Box::new(self.template_value(right, instance)),
+                alternate: Box::new(Expr::Literal(Value::String(String::new()))),
+            },
+            Expr::Call { callee, arguments, optional } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| match argument {
+                        Expr::Arrow { parameters, body: ArrowBody::Expression(body), is_async } => {
+                            let mut parameters = parameters.clone();
+                            let value = instance.scoped(&mut parameters, |names| self.template_value(body, names));
+                            Expr::Arrow {
+                                parameters,
+                                body: ArrowBody::Expression(Box::new(value)),
+                                is_async: *is_async,
+                            }
+                        }
+                        argument => instance.resolve_expr(argument),
+                    })
+                    .collect();
+                let list = Expr::Call { callee: Box::new(instance.resolve_expr(callee)), arguments, optional: *optional };
+                Expr::Call {
+                    callee: Box::new(Expr::Member { object: Box::new(list), property: "join".to_string(), optional: false }),
+                    arguments: vec![Expr::Literal(Value::String(String::new()))],
+                    optional: false,
+                }
+            }
+            _ => escaped(instance),
+        }
+    }
+
+    fn render_attribute(&mut self, attribute: &Attribute, target: &mut MarkupTarget) -> String {
+        let name = &attribute.name;
+        match &attribute.value {
+            AttributeValue::Boolean => format!(" {}", name),
+            AttributeValue::Expression(expression) if name.starts_with("on") => {
+                format!(" {}=\"{}\"", name, target.literal(&escape_html(&expression.to_string())))
+            }
+            AttributeValue::Expression(expression) => match target {
+                MarkupTarget::Page => format!(" data-ema-bind-{}=\"{}\"", name, self.bind(expression.to_string())),
+                MarkupTarget::Template(instance) => {
+                    format!("${{htmlAttribute('{}', {})}}", name, instance.resolve_expr(expression))
+                }
+            },
+            value => match (self.static_text(value), target) {
+                (Some(text), target) => format!(" {}=\"{}\"", name, target.literal(&text)),
+                (None, MarkupTarget::Page) => {
+                    let binding = template_literal(value);
+                    format!(" data-ema-bind-{}=\"{}\"", name, self.bind(binding))
+                }
+                (None, MarkupTarget::Template(instance)) => {
+                    let mut html = format!(" {}=\"", name);
+                    if let AttributeValue::Interpolated(parts) = value {
+                        for part in parts {
+                            match part {
+                                ASTNode::Text { value, .. } => html.push_str(&template_text(&escape_text(value).replace('"', "&quot;"))),
+                                ASTNode::Interpolation { expression, .. } => match self.resolve_static(expression) {
+                                    Some(value) => html.push_str(&template_text(&escape_html(&value))),
+                                    None => html.push_str(&format!("${{escapeHtml({})}}", instance.resolve_expr(expression))),
+                                },
+                                _ => {}
+                            }
+                        }
+                    }
+                    html.push('"');
+                    html
+                }
+            },
+        }
+    }
+
+    /// Adds a page binding computing `expression`, returning its index.
+    fn bind(&mut self, expression: String) -> usize {
+        self.bindings.push(expression);
+        self.bindings.len() - 1
+    }
+
+    /// The escaped text of an attribute value when it is known at compile
+    /// time.
+    fn static_text(&self, value: &AttributeValue) -> Option<String> {
+        match value {
+            AttributeValue::Static(text) => Some(escape_text(text).replace('"', "&quot;")),
+            AttributeValue::Interpolated(parts) => parts.iter()
+                .map(|part| match part {
+                    ASTNode::Text { value, .. } => Some(escape_text(value).replace('"', "&quot;")),
+                    ASTNode::Interpolation { expression, .. } => {
+                        self.resolve_static(expression).map(|value| escape_html(&value))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Resolves `page.<attribute>` against the current page.
+    fn resolve_static(&self, expression: &Expr) -> Option<String> {
+        match expression {
+            Expr::Member { object, property, optional: false } => match &**object {
+                Expr::Identifier(name) if name == "page" => self.page_attributes.get(property).cloned(),
+                _ => None,
+            },
+            _ => None,
         }
-        self.js_output.push_str(";\n");
-        Ok(())
     }
 
     fn capitalize(&self, s: &str) -> String {
@@ -1421,6 +7894,11 @@ impl CodeGenerator {
         }
     }
 
+    /// Problems found while generating that don't stop code generation.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn get_html(&self) -> &str {
         &self.html_output
     }
@@ -1434,6 +7912,390 @@ impl CodeGenerator {
     }
 }
 
+fn is_markup(node: &ASTNode) -> bool {
+    is_inline_markup(node) || matches!(node, ASTNode::Element { .. })
+}
+
+/// Flattens nested style rules into plain CSS under `parents`. Consecutive
+/// declarations share one rule; at-rules keep their block and nest the rules
+/// inside it. When `detached`, nested rules without `&` stand on their own
+/// instead of being the parents' descendants.
+fn css_rules(parents: &[String], items: &[ASTNode], detached: bool, indent: usize) -> String {
+    let pad = "  ".repeat(indent);
+    let mut css = String::new();
+    let mut declarations = String::new();
+
+    let flush = |css: &mut String, declarations: &mut String| {
+        if declarations.is_empty() {
+            return;
+        }
+        if parents.is_empty() {
+            css.push_str(declarations);
+        } else {
+            css.push_str(&format!("{}{} {{\n{}{}}}\n", pad, parents.join(", "), declarations, pad));
+        }
+        declarations.clear();
+    };
+
+    for item in items {
+        match item {
+            ASTNode::Declaration { property, value, important, .. } => {
+                let inner = if parents.is_empty() { pad.clone() } else { format!("{}  ", pad) };
+                let important = if *important { " !important" } else { "" };
+                declarations.push_str(&format!("{}{}: {}{};\n", inner, property, value, important));
+            }
+            ASTNode::Rule { selectors, children, .. } => {
+                flush(&mut css, &mut declarations);
+                let mut nested = Vec::new();
+                for selector in selectors {
+                    if parents.is_empty() || (detached && !selector.has_nesting()) {
+                        nested.push(selector.to_css(None));
+                    } else {
+                        nested.extend(parents.iter().map(|parent| selector.to_css(Some(parent))));
+                    }
+                }
+                css.push_str(&css_rules(&nested, children, false, indent));
+            }
+            ASTNode::AtRule { name, prelude, block, .. } => {
+                flush(&mut css, &mut declarations);
+                let head = if prelude.is_empty() { format!("@{}", name) } else { format!("@{} {}", name, prelude) };
+                match block {
+                    Some(block) => {
+                        css.push_str(&format!("{}{} {{\n", pad, head));
+                        css.push_str(&css_rules(parents, block, detached, indent + 1));
+                        css.push_str(&format!("{}}}\n", pad));
+                    }
+                    None => css.push_str(&format!("{}{};\n", pad, head)),
+                }
+            }
+            ASTNode::Keyframe { offsets, declarations: body, .. } => {
+                // Keyframes aren't nested under the enclosing rule
+                flush(&mut css, &mut declarations);
+                let offsets: Vec<String> = offsets.iter().map(KeyframeOffset::to_string).collect();
+                css.push_str(&css_rules(&[offsets.join(", ")], body, false, indent));
+            }
+            _ => {}
+        }
+    }
+    flush(&mut css, &mut declarations);
+
+    css
+}
+
+/// Collects the animations `node` declares and those its markup's
+/// `animation` attributes use.
+fn collect_animations<'a>(node: &'a ASTNode, declared: &mut HashSet<&'a str>, used: &mut Vec<(&'a str, &'a Span)>) {
+    match node {
+        ASTNode::Program { body, .. } | ASTNode::Page { body, .. } => {
+            for child in body {
+                collect_animations(child, declared, used);
+            }
+        }
+        ASTNode::Component { render, .. } | ASTNode::Layout { render, .. } => {
+            for root in render {
+                collect_animations(root, declared, used);
+            }
+        }
+        ASTNode::Element { attributes, children, .. } => {
+            for attribute in attributes {
+                if let (true, AttributeValue::Static(value)) = (attribute.name == "animation", &attribute.value) {
+                    used.extend(animation_names(value).into_iter().map(|name| (name, &attribute.span)));
+                }
+            }
+            for child in children {
+                collect_animations(child, declared, used);
+            }
+        }
+        ASTNode::Animation { name, .. } => {
+            declared.insert(name);
+        }
+        ASTNode::Style { rules: items, .. } | ASTNode::Rule { children: items, .. } => {
+            for item in items {
+                collect_animations(item, declared, used);
+            }
+        }
+        ASTNode::AtRule { name, prelude, block, .. } => {
+            if name.ends_with("keyframes") {
+                declared.insert(prelude.as_str());
+            }
+            for item in block.iter().flatten() {
+                collect_animations(item, declared, used);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The animation names in an `animation` shorthand: the first word of each
+/// comma-separated entry that isn't a time, number, function or keyword.
+fn animation_names(value: &str) -> Vec<&str> {
+    const KEYWORDS: &[&str] = &[
+        "none", "initial", "inherit", "unset", "infinite", "normal", "reverse", "alternate",
+        "alternate-reverse", "forwards", "backwards", "both", "running", "paused", "linear",
+        "ease", "ease-in", "ease-out", "ease-in-out", "step-start", "step-end",
+    ];
+
+    let mut names = Vec::new();
+    let mut named = false;
+    let mut depth = 0usize;
+    let mut word_start = None;
+    for (index, c) in value.char_indices().chain(std::iter::once((value.len(), ','))) {
+        match c {
+            '(' => {
+                // `cubic-bezier(...)` and `var(...)` aren't names
+                depth += 1;
+                word_start = None;
+            }
+            ')' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            ',' | ' ' | '\t' | '\n' => {
+                if let Some(start) = word_start.take() {
+                    let word = &value[start..index];
+                    let numeric = word.starts_with(|c: char| c.is_ascii_digit() || c == '.');
+                    if !named && !numeric && !KEYWORDS.contains(&word) {
+                        names.push(word);
+                        named = true;
+                    }
+                }
+                if c == ',' {
+                    named = false;
+                }
+            }
+            _ => {
+                word_start.get_or_insert(index);
+            }
+        }
+    }
+    names
+}
+
+/// The names of a route path's `:name` segments, with `*` for a wildcard
+/// segment.
+fn route_params(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter_map(|segment| match segment {
+            "*" => Some("*".to_string()),
+            _ => segment.strip_prefix(':').map(str::to_string),
+        })
+        .collect()
+}
+
+/// Functions an `event` block can call in place of the same methods of its
+/// event.
+const EVENT_HELPERS: &[&str] = &["preventDefault", "stopPropagation", "stopImmediatePropagation"];
+
+/// Events that don't bubble, so a delegated listener catches them in the
+/// capture phase.
+const NON_BUBBLING_EVENTS: &[&str] = &[
+    "focus", "blur", "load", "unload", "error", "abort", "scroll", "mouseenter", "mouseleave", "pointerenter",
+    "pointerleave", "invalid", "toggle", "play", "pause", "ended", "loadeddata", "loadedmetadata",
+];
+
+/// A JavaScript regular expression matching a route path, capturing its
+/// parameters in order; `*` matches the rest of the path.
+fn route_pattern(path: &str) -> String {
+    let mut pattern = String::from("/^");
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        if segment == "*" {
+            pattern.push_str("(?:\\/(.*))?");
+            continue;
+        }
+        pattern.push_str("\\/");
+        if segment.starts_with(':') {
+            pattern.push_str("([^/]+)");
+        } else {
+            for c in segment.chars() {
+                if "\\^$.|?*+()[]{}/".contains(c) {
+                    pattern.push('\\');
+                }
+                pattern.push(c);
+            }
+        }
+    }
+    pattern.push_str("\\/?$/");
+    pattern
+}
+
+/// Builds a route from a `<route>` element's attributes: `path` and
+/// `component`, with the rest as options.
+fn route_from_attributes(attributes: Vec<Attribute>, span: Span) -> Result<ASTNode, Diagnostic> {
+    let mut path = None;
+    let mut component = None;
+    let mut options = Vec::new();
+
+    for attribute in attributes {
+        let value = match attribute.value {
+            AttributeValue::Boolean => Expr::Literal(Value::Boolean(true)),
+            AttributeValue::Static(text) => Expr::Literal(Value::String(text)),
+            AttributeValue::Expression(expression) => expression,
+            value @ AttributeValue::Interpolated(_) => Expr::Raw(interpolated_source(&value)),
+        };
+        match (attribute.name.as_str(), value) {
+            ("path", Expr::Literal(Value::String(text))) => path = Some(text),
+            ("path", _) => return Err(Diagnostic::new(codes::INVALID_ROUTE, "a route `path` must be a string", attribute.span)),
+            ("component", Expr::Literal(Value::String(name)) | Expr::Identifier(name)) => component = Some(name),
+            ("component", _) => return Err(Diagnostic::new(codes::INVALID_ROUTE, "a route `component` must name a component", attribute.span)),
+            (_, value) => options.push((attribute.name, value)),
+        }
+    }
+
+    let path = path.ok_or_else(|| Diagnostic::new(codes::INVALID_ROUTE, "`<route>` needs a `path`", span.clone()))?;
+    let component = component.ok_or_else(|| Diagnostic::new(codes::INVALID_ROUTE, "`<route>` needs a `component`", span.clone()))?;
+    Ok(ASTNode::Route { params: route_params(&path), path, component, options, span })
+}
+
+/// A JSDoc `@typedef` for a declared type, with `@template` lines for its
+/// type parameters.
+fn typedef_comment(name: &str, type_params: &[TypeParameter], definition: &str) -> String {
+    if type_params.is_empty() {
+        return format!("/** @typedef {{{}}} {} */\n", definition, name);
+    }
+
+    let mut comment = String::from("/**\n");
+    for parameter in type_params {
+        match &parameter.constraint {
+            Some(constraint) => comment.push_str(&format!(" * @template {{{}}} {}\n", constraint, parameter.name)),
+            None => comment.push_str(&format!(" * @template {}\n", parameter.name)),
+        }
+    }
+    comment.push_str(&format!(" * @typedef {{{}}} {}\n", definition, name));
+    comment.push_str(" */\n");
+    comment
+}
+
+/// `a, b as c` for an import or export list.
+fn specifiers_js(specifiers: &[ModuleSpecifier]) -> String {
+    specifiers.iter().map(|specifier| specifier.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// The HTML tag for a markup tag name: capitalized names refer to components
+/// and become their custom elements, `<Card>` to `<ema-card>`.
+fn element_name(tag_name: &str) -> String {
+    if tag_name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        format!("ema-{}", tag_name.to_lowercase())
+    } else {
+        tag_name.to_string()
+    }
+}
+
+/// The attribute a prop is read from: HTML lowercases attribute names, so
+/// `onToggle` is set as `on-toggle`.
+fn attribute_name(prop: &str) -> String {
+    let mut attribute = String::with_capacity(prop.len() + 2);
+    for (index, c) in prop.char_indices() {
+        if c.is_ascii_uppercase() {
+            if index > 0 && !attribute.ends_with('-') {
+                attribute.push('-');
+            }
+            attribute.push(c.to_ascii_lowercase());
+        } else {
+            attribute.push(c);
+        }
+    }
+    attribute
+}
+
+/// Whether `node` is a `<tag>` element with attribute `name`, equal to
+/// `value` when given, as in `<meta name="viewport">`.
+fn element_has(node: &ASTNode, tag: &str, name: &str, value: Option<&str>) -> bool {
+    match node {
+        ASTNode::Element { tag_name, attributes, .. } if tag_name == tag => attributes.iter().any(|attribute| {
+            attribute.name == name
+                && value.is_none_or(|value| match &attribute.value {
+                    AttributeValue::Static(text) => text.eq_ignore_ascii_case(value),
+                    _ => false,
+                })
+        }),
+        _ => false,
+    }
+}
+
+/// The runtime description of a prop: its type, its allowed values when
+/// the type is a literal union, and its default.
+fn prop_descriptor(prop_type: &TypeExpr, default: &Option<Value>) -> String {
+    let mut fields = vec![format!("type: '{}'", prop_type.runtime_type())];
+    if let Some(values) = prop_type.literal_values() {
+        let values: Vec<String> = values.iter().map(|value| value.to_js()).collect();
+        fields.push(format!("values: [{}]", values.join(", ")));
+    }
+    if let TypeExpr::Optional(_) = prop_type {
+        fields.push("optional: true".to_string());
+    }
+    if let Some(default) = default {
+        fields.push(format!("default: {}", default.to_js()));
+    }
+    format!("{{ {} }}", fields.join(", "))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Markup text as written, with `<`, `>` and any `&` that doesn't start a
+/// character reference such as `&copy;` or `&#169;` escaped.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        match c {
+            '&' => {
+                let rest = &text[index + 1..];
+                let name = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '#').unwrap_or(rest.len());
+                if name > 0 && rest[name..].starts_with(';') {
+                    escaped.push('&');
+                } else {
+                    escaped.push_str("&amp;");
+                }
+            }
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `text` escaped for a JavaScript template literal.
+fn template_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('`', "\\`").replace("${", "\\${")
+}
+
+/// A template literal computing an attribute value, `[[...]]` parts
+/// substituted, for a page binding.
+fn template_literal(value: &AttributeValue) -> String {
+    let mut literal = String::from("`");
+    if let AttributeValue::Interpolated(parts) = value {
+        for part in parts {
+            match part {
+                ASTNode::Text { value, .. } => literal.push_str(&template_text(value)),
+                ASTNode::Interpolation { expression, .. } => literal.push_str(&format!("${{{}}}", expression)),
+                _ => {}
+            }
+        }
+    }
+    literal.push('`');
+    literal
+}
+
+/// Source form of an attribute value, `[[...]]` kept, for runtime binding.
+fn interpolated_source(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::Static(text) => text.clone(),
+        AttributeValue::Interpolated(parts) => parts.iter()
+            .map(|part| match part {
+                ASTNode::Text { value, .. } => value.clone(),
+                ASTNode::Interpolation { expression, .. } => format!("[[{}]]", expression),
+                _ => String::new(),
+            })
+            .collect(),
+        AttributeValue::Expression(expression) => expression.to_string(),
+        AttributeValue::Boolean => String::new(),
+    }
+}
+
 // ===================================
 // COMPILER MAIN CLASS
 // ===================================
@@ -1452,8 +8314,13 @@ pub struct CompileResult {
     pub html: String,
     pub css: String,
     pub js: String,
-    pub errors: Vec<String>,
-    pub warnings: Vec<String>,
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    /// The parsed `Program`, including every statement that parsed when
+    /// there are errors.
+    pub ast: Option<ASTNode>,
+    /// The lossless syntax tree, built even when there are errors.
+    pub syntax_tree: Option<SyntaxTree>,
     pub compile_time: f64,
 }
 
@@ -1477,20 +8344,13 @@ impl EmadocsCompiler {
             js: String::new(),
             errors: Vec::new(),
             warnings: Vec::new(),
+            ast: None,
+            syntax_tree: None,
             compile_time: 0.0,
         };
 
-        match self.compile_internal() {
-            Ok((html, css, js)) => {
-                result.success = true;
-                result.html = html;
-                result.css = css;
-                result.js = js;
-            }
-            Err(e) => {
-                result.errors.push(e);
-            }
-        }
+        self.compile_internal(&mut result);
+        result.success = result.errors.is_empty();
 
         let duration = start_time.elapsed();
         result.compile_time = duration.as_millis() as f64;
@@ -1498,24 +8358,32 @@ impl EmadocsCompiler {
         result
     }
 
-    fn compile_internal(&self) -> Result<(String, String, String), String> {
-        // Tokenize
-        let mut lexer = Lexer::new(self.input.clone());
-        let tokens = lexer.tokenize()?;
+    fn compile_internal(&self, result: &mut CompileResult) {
+        // Tokenize; the lexer recovers, so parsing still finds its errors
+        let mut lexer = Lexer::new(&self.input, &self.filename);
+        let tokens: Vec<Token> = lexer.by_ref().collect();
+        result.errors.extend_from_slice(lexer.diagnostics());
 
         // Parse
         let mut parser = Parser::new(tokens);
-        let ast = parser.parse()?;
+        let ast = result.ast.insert(parser.parse());
+        result.syntax_tree = Some(SyntaxTree::new(&parser.syntax_tree(), &self.input));
+        result.errors.extend_from_slice(parser.diagnostics());
+        if !result.errors.is_empty() {
+            return;
+        }
 
         // Generate code
         let mut generator = CodeGenerator::new();
-        generator.generate(&ast)?;
+        if let Err(error) = generator.generate(ast) {
+            result.errors.push(error);
+            return;
+        }
 
-        Ok((
-            generator.get_html().to_string(),
-            generator.get_css().to_string(),
-            generator.get_js().to_string(),
-        ))
+        result.html = generator.get_html().to_string();
+        result.css = generator.get_css().to_string();
+        result.js = generator.get_js().to_string();
+        result.warnings.extend_from_slice(generator.diagnostics());
     }
 
     pub fn set_minify(&mut self, value: bool) {
@@ -1591,6 +8459,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Err("Compilation failed".into());
     }
 
+    for warning in &result.warnings {
+        eprintln!("  Warning: {}", warning);
+    }
+
     // Create output directory
     fs::create_dir_all(output_dir)?;
     fs::create_dir_all(format!("{}/css", output_dir))?;
@@ -0,0 +1,781 @@
+// Generated HTML, CSS and JavaScript for each construct.
+//
+// Run with `cargo test --test codegen`.
+
+#[allow(dead_code)]
+#[path = "../emadocs-compiler.rs"]
+mod compiler;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use compiler::{codes, CompileResult, EmadocsCompiler};
+
+fn compile(source: &str) -> CompileResult {
+    let result = EmadocsCompiler::new(source.to_string(), "test.ema".to_string()).compile();
+    let errors: Vec<String> = result.errors.iter().map(ToString::to_string).collect();
+    assert!(result.success, "{}", errors.join("\n"));
+    result
+}
+
+/// What `script` prints after the generated `js`, run by node against a
+/// minimal stand-in for the DOM's custom elements, or `None` without node.
+fn run_in_node(js: &str, script: &str) -> Option<String> {
+    const DOM: &str = "\
+        class Element {}\n\
+        class HTMLElement extends Element {\n\
+          constructor() { super(); this.attributes = new Map(); this.isConnected = false; }\n\
+          attachShadow() { this.shadowRoot = { innerHTML: '' }; return this.shadowRoot; }\n\
+          getAttribute(name) { return this.attributes.has(name) ? this.attributes.get(name) : null; }\n\
+          setAttribute(name, value) {\n\
+            const previous = this.getAttribute(name);\n\
+            this.attributes.set(name, String(value));\n\
+            if (this.constructor.observedAttributes.includes(name)) {\n\
+              this.attributeChangedCallback(name, previous, String(value));\n\
+            }\n\
+          }\n\
+        }\n\
+        const listeners = [];\n\
+        const document = { addEventListener: (type, listener, capture) => listeners.push({ type, listener, capture }) };\n\
+        const elements = new Map();\n\
+        const customElements = { define: (name, element) => elements.set(name, element) };\n\
+        function mount(name) {\n\
+          const element = new (elements.get(name))();\n\
+          element.isConnected = true;\n\
+          element.connectedCallback();\n\
+          return element;\n\
+        }\n";
+    let mut node = match Command::new("node").stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+        Ok(node) => node,
+        Err(_) => {
+            eprintln!("node not found, skipping");
+            return None;
+        }
+    };
+    let program = format!("{}{}\n{}\n", DOM, js, script);
+    node.stdin.take().expect("stdin").write_all(program.as_bytes()).expect("write to node");
+    let output = node.wait_with_output().expect("run node");
+    assert!(output.status.success(), "{}\n{}", String::from_utf8_lossy(&output.stderr), program);
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn computed_and_watch_bodies_read_the_element() {
+    let result = compile(
+        "component List {\n\
+           prop limit: number = 3;\n\
+           state items: string[] = [];\n\
+           state filter: string = \"all\";\n\
+           computed visible() {\n\
+             const items = [];\n\
+             return items.concat(shown()).slice(0, limit);\n\
+           }\n\
+           computed shown() { return filter === \"all\" ? items : { items, filter }; }\n\
+           watch filter(value, filter) { console.log(value, filter, items.length); }\n\
+           function reset() { }\n\
+         }\n",
+    );
+    let js = &result.js;
+    // A local binding shadows the state variable
+    assert!(js.contains("const items = [];"), "{}", js);
+    assert!(js.contains("return items.concat(this.shown()).slice(0, this.limit);"), "{}", js);
+    assert!(
+        js.contains("return this.state.filter === 'all' ? this.state.items : { items: this.state.items, filter: this.state.filter };"),
+        "{}",
+        js
+    );
+    assert!(js.contains("console.log(value, filter, this.state.items.length);"), "{}", js);
+}
+
+#[test]
+fn page_interpolations_are_resolved_and_escaped() {
+    let result = compile(
+        "page Home title=\"Tips & tricks\" {\n\
+           <h1 title=\"[[page.title]]\">[[page.title]]</h1>\n\
+           <p>Fish &amp; chips & peas &copy; 2025</p>\n\
+         }\n",
+    );
+    let html = &result.html;
+    assert!(html.contains("<title>Tips &amp; tricks</title>"), "{}", html);
+    assert!(html.contains("<h1 title=\"Tips &amp; tricks\">Tips &amp; tricks</h1>"), "{}", html);
+    assert!(html.contains("<p>Fish &amp; chips &amp; peas &copy; 2025</p>"), "{}", html);
+    assert!(!html.contains("data-ema-bind"), "{}", html);
+}
+
+#[test]
+fn page_runtime_values_are_bound_by_the_script() {
+    let result = compile(
+        "state name: string = \"Ada\";\n\
+         page Home {\n\
+           <p title=\"a [[name]] b\" hidden={!name}>Hello, [[name]]!</p>\n\
+         }\n",
+    );
+    let html = &result.html;
+    assert!(html.contains("<p data-ema-bind-title=\"0\" data-ema-bind-hidden=\"1\">Hello, <span data-ema-bind=\"2\"></span>!</p>"), "{}", html);
+    let js = &result.js;
+    assert!(js.contains("const pageBindings = [\n  () => `a ${name} b`,\n  () => !name,\n  () => name,\n];"), "{}", js);
+    assert!(js.contains("element.setAttribute(name.slice('data-ema-bind-'.length)"), "{}", js);
+}
+
+#[test]
+fn component_templates_interpolate_escaped_values() {
+    let result = compile(
+        "component Card {\n\
+           prop title: string;\n\
+           prop disabled: boolean = false;\n\
+           state count: number = 0;\n\
+           render {\n\
+             <div class=\"card card--[[title]]\" disabled={disabled}>[[title]] `a\\b` [[count + 1]]</div>\n\
+           }\n\
+         }\n",
+    );
+    let js = &result.js;
+    assert!(js.contains("function escapeHtml(value) {"), "{}", js);
+    assert!(
+        js.contains("<div class=\"card card--${escapeHtml(this.title)}\"${htmlAttribute('disabled', this.disabled)}>${escapeHtml(this.title)} \\`a\\\\b\\` ${escapeHtml(this.state.count + 1)}</div>"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn page_markup_compiles_to_a_document() {
+    let result = compile(concat!(
+        "<page title=\"Home\" theme=\"dark\">\n",
+        "  <head>\n",
+        "    <meta charset=\"utf-8\">\n",
+        "    <link rel=\"stylesheet\" href=\"app.css\" />\n",
+        "  </head>\n",
+        "  <body>\n",
+        "    <h1 class=\"title\">Hello</h1>\n",
+        "    <input type=\"checkbox\" checked>\n",
+        "    <p>Text<br>more</p>\n",
+        "  </body>\n",
+        "</page>\n",
+    ));
+    let html = &result.html;
+    assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\" data-theme=\"dark\">\n<head>\n"), "{}", html);
+    // The page's own head elements come before the generated ones
+    assert!(
+        html.contains("  <meta charset=\"utf-8\">\n  <link rel=\"stylesheet\" href=\"app.css\">\n  <title>Home</title>\n"),
+        "{}",
+        html
+    );
+    // Void elements take no closing tag, and boolean attributes no value
+    assert!(
+        html.contains("<body>\n  <h1 class=\"title\">Hello</h1>\n  <input type=\"checkbox\" checked>\n  <p>Text<br>more</p>\n"),
+        "{}",
+        html
+    );
+    assert!(html.trim_end().ends_with("<script src=\"script.js\"></script>\n</body>\n</html>"), "{}", html);
+}
+
+#[test]
+fn whitespace_around_inline_content_is_kept() {
+    let result = compile(concat!(
+        "<page title=\"Home\">\n",
+        "  <body>\n",
+        "    <p>Hello <b>world</b> and <i>you</i>!</p>\n",
+        "    <p>[[title]] <em>sub</em> <a href=\"/\">home</a></p>\n",
+        "    <div>\n",
+        "      <h2>Block</h2>\n",
+        "      <p>Next</p>\n",
+        "    </div>\n",
+        "  </body>\n",
+        "</page>\n",
+    ));
+    let html = &result.html;
+    assert!(html.contains("<p>Hello <b>world</b> and <i>you</i>!</p>"), "{}", html);
+    assert!(html.contains("</span> <em>sub</em> <a href=\"/\">home</a></p>"), "{}", html);
+    // Between block elements the layout ignores it
+    assert!(html.contains("<div><h2>Block</h2><p>Next</p></div>"), "{}", html);
+}
+
+#[test]
+fn prop_defaults_are_checked_against_their_type() {
+    let result = compile(concat!(
+        "component Badge {\n",
+        "  prop variant: \"minimal\" | \"neo\" = \"bold\";\n",
+        "  prop count: number = 0;\n",
+        "}\n",
+    ));
+    assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+    assert_eq!(result.warnings[0].code, codes::PROP_DEFAULT_TYPE);
+    assert_eq!(result.warnings[0].span.start.line, 2);
+    assert!(result.warnings[0].message.contains("`variant`"), "{}", result.warnings[0]);
+}
+
+#[test]
+fn bodies_compile_statement_by_statement() {
+    let result = compile(concat!(
+        "function total(items, limit = 10) {\n",
+        "  let sum = 0;\n",
+        "  for (let i = 0; i < items.length; i++) {\n",
+        "    if (i >= limit) break;\n",
+        "    else if (!items[i]) continue;\n",
+        "    sum += items[i].price;\n",
+        "  }\n",
+        "  for (const item of items) { sum += item.tax; }\n",
+        "  while (sum > 100) { sum = sum / 2; }\n",
+        "  switch (sum) {\n",
+        "    case 0:\n",
+        "      return \"none\";\n",
+        "    default:\n",
+        "      break;\n",
+        "  }\n",
+        "  try {\n",
+        "    validate(sum);\n",
+        "  } catch (error) {\n",
+        "    console.error(error);\n",
+        "  } finally {\n",
+        "    done();\n",
+        "  }\n",
+        "  return sum;\n",
+        "}\n",
+        "event click on button#save {\n",
+        "  if (ready) save(); else throw new Error(\"no\");\n",
+        "}\n",
+    ));
+    let expected = concat!(
+        "function total(items, limit = 10) {\n",
+        "  let sum = 0;\n",
+        "  for (let i = 0; i < items.length; i++) {\n",
+        "    if (i >= limit) {\n",
+        "      break;\n",
+        "    } else if (!items[i]) {\n",
+        "      continue;\n",
+        "    }\n",
+        "    sum += items[i].price;\n",
+        "  }\n",
+        "  for (const item of items) {\n",
+        "    sum += item.tax;\n",
+        "  }\n",
+        "  while (sum > 100) {\n",
+        "    sum = sum / 2;\n",
+        "  }\n",
+        "  switch (sum) {\n",
+        "    case 0:\n",
+        "      return 'none';\n",
+        "    default:\n",
+        "      break;\n",
+        "  }\n",
+        "  try {\n",
+        "    validate(sum);\n",
+        "  } catch (error) {\n",
+        "    console.error(error);\n",
+        "  } finally {\n",
+        "    done();\n",
+        "  }\n",
+        "  return sum;\n",
+        "}\n",
+    );
+    assert!(result.js.contains(expected), "{}", result.js);
+    assert!(result.js.contains("  if (ready) {\n    save();\n  } else {\n    throw new Error('no');\n  }\n"), "{}", result.js);
+}
+
+
+#[test]
+fn style_blocks_compile_to_nested_css() {
+    let result = compile(concat!(
+        "style section#hero, .features-grid > .card {\n",
+        "  padding: 80px 40px;\n",
+        "  &:hover { color: var(--primary); }\n",
+        "  &--primary { background: red; }\n",
+        "  .title { font-weight: 700; }\n",
+        "  @media (max-width: 768px) {\n",
+        "    padding: 20px;\n",
+        "  }\n",
+        "}\n",
+        "style Button {\n",
+        "  border: none;\n",
+        "  &:focus { outline: 2px solid blue; }\n",
+        "}\n",
+    ));
+    // Each nested rule is flattened against every selector in the list
+    let expected = concat!(
+        "section#hero, .features-grid > .card {\n",
+        "  padding: 80px 40px;\n",
+        "}\n",
+        "section#hero:hover, .features-grid > .card:hover {\n",
+        "  color: var(--primary);\n",
+        "}\n",
+        "section#hero--primary, .features-grid > .card--primary {\n",
+        "  background: red;\n",
+        "}\n",
+        "section#hero .title, .features-grid > .card .title {\n",
+        "  font-weight: 700;\n",
+        "}\n",
+        "@media (max-width: 768px) {\n",
+        "  section#hero, .features-grid > .card {\n",
+        "    padding: 20px;\n",
+        "  }\n",
+        "}\n",
+    );
+    assert!(result.css.contains(expected), "{}", result.css);
+    // A component name selects its element class
+    assert!(
+        result.css.contains(".ema-button {\n  border: none;\n}\n.ema-button:focus {\n  outline: 2px solid blue;\n}\n"),
+        "{}",
+        result.css
+    );
+}
+
+#[test]
+fn animations_compile_to_keyframes() {
+    let result = compile(concat!(
+        "animation fadeIn {\n",
+        "  from { opacity: 0; }\n",
+        "  50% { opacity: 0.5; }\n",
+        "  to { opacity: 1; }\n",
+        "}\n",
+        "style .spinner {\n",
+        "  @keyframes spin {\n",
+        "    0%, 100% { transform: rotate(0deg); }\n",
+        "  }\n",
+        "}\n",
+        "<div animation=\"fadeIn 0.8s ease, spin 2s linear infinite\">Hi</div>\n",
+    ));
+    assert!(
+        result.css.contains("@keyframes fadeIn {\n  from {\n    opacity: 0;\n  }\n  50% {\n    opacity: 0.5;\n  }\n  to {\n    opacity: 1;\n  }\n}\n"),
+        "{}",
+        result.css
+    );
+    assert!(result.css.contains("  0%, 100% {\n    transform: rotate(0deg);\n  }\n"), "{}", result.css);
+    assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+}
+
+#[test]
+fn undeclared_animations_are_warnings() {
+    let result = compile(concat!(
+        "animation fadeIn {\n  to { opacity: 1; }\n}\n",
+        "<div animation=\"cubic-bezier(0, 0, 1, 1) 1s slideUp, fadeIn 2s\">Hi</div>\n",
+    ));
+    assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+    let warning = &result.warnings[0];
+    assert_eq!(warning.code, codes::UNKNOWN_ANIMATION);
+    assert_eq!((warning.span.start.line, warning.span.start.column), (4, 6));
+    assert_eq!(warning.message, "animation `slideUp` is not declared");
+}
+
+#[test]
+fn routes_compile_to_patterns_and_params() {
+    let result = compile(concat!(
+        "router {\n",
+        "  route \"/\" -> HomePage;\n",
+        "  route \"/users/:id\" -> UserDetailPage { guard: requireAuth };\n",
+        "  route \"/docs/v1.0/*\" -> DocsPage;\n",
+        "}\n",
+    ));
+    for route in [
+        "  { path: '/', pattern: /^\\/?$/, params: [], component: 'ema-homepage', options: {} },\n",
+        "  { path: '/users/:id', pattern: /^\\/users\\/([^/]+)\\/?$/, params: ['id'], component: 'ema-userdetailpage', options: { guard: requireAuth } },\n",
+        "  { path: '/docs/v1.0/*', pattern: /^\\/docs\\/v1\\.0(?:\\/(.*))?\\/?$/, params: ['*'], component: 'ema-docspage', options: {} },\n",
+    ] {
+        assert!(result.js.contains(route), "{}\n{}", route, result.js);
+    }
+}
+
+#[test]
+fn types_compile_to_jsdoc_and_enums_to_frozen_objects() {
+    let result = compile(concat!(
+        "type Status = \"active\" | \"archived\";\n",
+        "type Page<T extends object> = { items: T[]; next?: string };\n",
+        "interface Admin extends User { role: Role }\n",
+        "enum Role { Viewer, Editor = 5, Owner, \"Super User\" = \"root\" }\n",
+    ));
+    let js = &result.js;
+    assert!(js.contains("/** @typedef {\"active\" | \"archived\"} Status */\n"), "{}", js);
+    assert!(
+        js.contains("/**\n * @template {object} T\n * @typedef {{ items: T[]; next?: string }} Page\n */\n"),
+        "{}",
+        js
+    );
+    assert!(js.contains("// Interface: Admin\n/** @typedef {User & { role: Role }} Admin */\n"), "{}", js);
+    assert!(
+        js.contains("const Role = Object.freeze({ Viewer: 0, Editor: 5, Owner: 6, 'Super User': 'root' });\n"),
+        "{}",
+        js
+    );
+}
+
+#[test]
+fn enum_members_after_a_string_need_a_value() {
+    let result = EmadocsCompiler::new("enum Mode { Light = \"light\", Dark }\n".to_string(), "test.ema".to_string()).compile();
+    assert!(!result.success);
+    let error = result.errors.iter().find(|error| error.code == codes::MISSING_ENUM_VALUE).expect("a missing value error");
+    assert_eq!((error.span.start.column, error.span.end.column), (30, 34), "{:?}", error);
+}
+
+#[test]
+fn duplicate_enum_members_are_errors() {
+    let result = EmadocsCompiler::new("enum Mode { Light, Dark, \"Light\" = 3 }\n".to_string(), "test.ema".to_string()).compile();
+    assert!(!result.success);
+    let error = result.errors.iter().find(|error| error.code == codes::DUPLICATE_ENUM_MEMBER).expect("a duplicate member error");
+    assert_eq!((error.span.start.column, error.span.end.column), (26, 33), "{:?}", error);
+}
+
+#[test]
+fn string_literal_types_are_escaped_in_their_typedef() {
+    let result = compile("type Tricky = \"*/ alert(1) /*\" | 'say \"hi\"';\n");
+    assert!(result.js.contains(r#"/** @typedef {"*\/ alert(1) /*" | "say \"hi\""} Tricky */"#), "{}", result.js);
+}
+
+#[test]
+fn config_and_plugin_values_compile_to_literals() {
+    let result = compile(concat!(
+        "config {\n",
+        "  port: 3000;\n",
+        "  \"dev-server\": { hot: false, \"max-age\": 60, \"it's\": true };\n",
+        "  plugins: [EmadocsUI];\n",
+        "}\n",
+        "plugin Analytics {\n",
+        "  name: \"analytics\";\n",
+        "  \"data-key\": 'it\\'s';\n",
+        "  methods: { track: (event: string) => void; };\n",
+        "}\n",
+    ));
+    let js = &result.js;
+    assert!(js.contains("const config = {\n  port: 3000,\n  'dev-server': { hot: false, 'max-age': 60, 'it\\'s': true },\n  plugins: [EmadocsUI],\n};\n"), "{}", js);
+    assert!(js.contains("/** @typedef {{ track: (event: string) => void }} AnalyticsMethods */\n"), "{}", js);
+    assert!(js.contains("    this.name = 'analytics';\n    this['data-key'] = 'it\\'s';\n"), "{}", js);
+}
+
+#[test]
+fn imports_compile_clause_by_clause() {
+    let result = compile(concat!(
+        "import \"./theme.css\";\n",
+        "import Button from \"./button.ema\";\n",
+        "import * as utils from \"./utils.ema\";\n",
+        "import { a, b as c } from \"./m.ema\";\n",
+        "import Card, { CardBody } from \"./card.ema\";\n",
+        "import Modal, * as modal from \"./modal.ema\";\n",
+        "import { \"a-b\" as ab, \"plain\" as p } from \"./strings.js\";\n",
+    ));
+    let expected = concat!(
+        "import './theme.css';\n",
+        "import Button from './button.ema';\n",
+        "import * as utils from './utils.ema';\n",
+        "import { a, b as c } from './m.ema';\n",
+        "import Card, { CardBody } from './card.ema';\n",
+        "import Modal, * as modal from './modal.ema';\n",
+        "import { 'a-b' as ab, plain as p } from './strings.js';\n",
+    );
+    assert!(result.js.contains(expected), "{}", result.js);
+}
+
+#[test]
+fn exports_compile_clause_by_clause() {
+    let result = compile(concat!(
+        "export { a, b as c };\n",
+        "export { d } from \"./d.ema\";\n",
+        "export * from \"./all.ema\";\n",
+        "export * as ns from \"./ns.ema\";\n",
+        "export const answer = 42;\n",
+        "export function greet(name) { return name; }\n",
+        "export component Button { }\n",
+        "export default Button;\n",
+    ));
+    let js = &result.js;
+    for line in [
+        "export { a, b as c };\n",
+        "export { d } from './d.ema';\n",
+        "export * from './all.ema';\n",
+        "export * as ns from './ns.ema';\n",
+        "export const answer = 42;\n",
+        "export { greet };\n",
+        "export { EmaButton as Button };\n",
+        "export default Button;\n",
+    ] {
+        assert!(js.contains(line), "{}\n{}", line, js);
+    }
+}
+
+#[test]
+fn string_names_in_export_lists_are_quoted() {
+    let result = compile(concat!(
+        "export { ab as \"a-b\", c as default };\n",
+        "export { \"x-y\" as xy, \"z\" } from \"./m.js\";\n",
+    ));
+    assert!(result.js.contains("export { ab as 'a-b', c as default };\nexport { 'x-y' as xy, z } from './m.js';\n"), "{}", result.js);
+}
+
+#[test]
+fn default_exports_export_each_declaration_form() {
+    for (source, expected) in [
+        ("export default function () { return 1; }\n", "export default function () {\n  return 1;\n}\n"),
+        ("export default async function () { await load(); }\n", "export default async function () {\n  await load();\n}\n"),
+        ("export default function greet(name) { return name; }\n", "function greet(name) {\n  return name;\n}\n\nexport default greet;\n"),
+        ("export default component Button { }\n", "export default EmaButton;\n"),
+        ("export default { answer: 42 };\n", "export default { answer: 42 };\n"),
+    ] {
+        let js = compile(source).js;
+        assert!(js.contains(expected), "{}\n{}", source, js);
+    }
+}
+
+#[test]
+fn lifecycle_computed_and_watch_blocks_wire_into_the_element() {
+    let result = compile(concat!(
+        "component Counter {\n",
+        "  state count: number = 0;\n",
+        "  computed doubled() { return count * 2; }\n",
+        "  watch count(value, previous) { console.log(value, previous); }\n",
+        "  mounted { console.log(\"up\", count); }\n",
+        "  unmounted { console.log(\"down\"); }\n",
+        "  render { <p>[[doubled]]</p> }\n",
+        "}\n",
+    ));
+    let js = &result.js;
+    assert!(
+        js.contains("  connectedCallback() {\n    this.render();\n    this.setupEventListeners();\n    console.log('up', this.state.count);\n  }\n"),
+        "{}",
+        js
+    );
+    assert!(js.contains("  disconnectedCallback() {\n    console.log('down');\n  }\n"), "{}", js);
+
+    // Computed getters are memoized until state or an attribute changes
+    assert!(js.contains("this.computedCache = new Map();"), "{}", js);
+    assert!(
+        js.contains("  attributeChangedCallback() {\n    this.computedCache.clear();\n    if (this.isConnected) {\n      this.render();\n    }\n  }\n"),
+        "{}",
+        js
+    );
+    assert!(
+        js.contains(concat!(
+            "  get doubled() {\n",
+            "    if (!this.computedCache.has('doubled')) {\n",
+            "      this.computedCache.set('doubled', (() => {\n",
+            "        return this.state.count * 2;\n",
+            "      })());\n",
+            "    }\n",
+            "    return this.computedCache.get('doubled');\n",
+            "  }\n",
+        )),
+        "{}",
+        js
+    );
+
+    // setState drops the cache and runs the watchers before rendering
+    assert!(
+        js.contains(concat!(
+            "    this.watchers = {\n",
+            "      count: [\n",
+            "        (value, previous) => {\n",
+            "          console.log(value, previous);\n",
+            "        },\n",
+            "      ],\n",
+            "    };\n",
+        )),
+        "{}",
+        js
+    );
+    assert!(
+        js.contains(concat!(
+            "    this.state[name] = value;\n",
+            "    this.computedCache.clear();\n",
+            "    for (const watcher of this.watchers[name] || []) {\n",
+            "      watcher(value, previous);\n",
+            "    }\n",
+            "    if (this.isConnected) {\n",
+            "      this.render();\n",
+            "    }\n",
+        )),
+        "{}",
+        js
+    );
+    assert!(js.contains("<p>${escapeHtml(this.doubled)}</p>"), "{}", js);
+}
+
+
+#[test]
+fn watching_an_unknown_name_is_a_warning() {
+    let result = compile("component Counter {\n  state count: number = 0;\n  watch total(value) { }\n}\n");
+    assert!(result.warnings.iter().any(|warning| warning.code == codes::UNKNOWN_WATCH_TARGET), "{:?}", result.warnings);
+}
+
+#[test]
+fn targeted_events_are_delegated_by_selector() {
+    let result = compile(concat!(
+        "event click on .todo-list > li:not(.done), button[data-action=\"save\"] {\n",
+        "  preventDefault();\n",
+        "  target.classList.toggle(\"active\");\n",
+        "}\n",
+        "event resize on window {\n",
+        "  layout();\n",
+        "}\n",
+    ));
+    let js = &result.js;
+    assert_eq!(js.matches("function delegateEvent(root, type, selector, handler, capture = false) {\n").count(), 1, "{}", js);
+    assert!(js.contains("      target = event.target instanceof Element ? event.target.closest(selector) : null;\n"), "{}", js);
+    assert!(
+        js.contains(concat!(
+            "delegateEvent(document, 'click', '.todo-list > li:not(.done), button[data-action=\"save\"]', ",
+            "async (event, target, { preventDefault, stopPropagation, stopImmediatePropagation }) => {\n",
+            "  preventDefault();\n",
+            "  target.classList.toggle('active');\n",
+            "});\n",
+        )),
+        "{}",
+        js
+    );
+    assert!(js.contains("delegateEvent(window, 'resize', null, async (event, target, {"), "{}", js);
+}
+
+#[test]
+fn event_parameters_come_before_the_helpers() {
+    let result = compile(concat!(
+        "event input on input.search (e) {\n",
+        "  filter(e.target.value, target);\n",
+        "}\n",
+        "event focus on .field (event, field) {\n",
+        "  field.select();\n",
+        "}\n",
+        "event blur on .field (target) { }\n",
+    ));
+    let js = &result.js;
+    assert!(
+        js.contains("delegateEvent(document, 'input', 'input.search', async (e, target, { preventDefault, stopPropagation, stopImmediatePropagation }) => {\n"),
+        "{}",
+        js
+    );
+    // `focus` doesn't bubble up to the document, so is caught on its way down
+    assert!(js.contains("async (event, field, { preventDefault, stopPropagation, stopImmediatePropagation }) => {\n"), "{}", js);
+    assert!(js.contains("  field.select();\n}, true);\n"), "{}", js);
+    // A parameter named `target` stands for the event
+    assert!(js.contains("async (target, _target, { preventDefault, stopPropagation, stopImmediatePropagation }) => {}, true);"), "{}", js);
+
+    let script = concat!(
+        "function filter(value, target) { console.log(value, target.name); }\n",
+        "const search = Object.assign(new Element(), { name: 'search', value: 'milk', closest: () => search });\n",
+        "const field = Object.assign(new Element(), { closest: () => field, select: () => console.log('selected') });\n",
+        "for (const { type, listener, capture } of listeners.slice(0, 2)) {\n",
+        "  console.log(type, capture);\n",
+        "  listener({ target: type === 'input' ? search : field });\n",
+        "}\n",
+    );
+    if let Some(output) = run_in_node(js, script) {
+        assert_eq!(output, "input false\nmilk search\nfocus true\nselected\n");
+    }
+}
+
+#[test]
+fn dynamic_tags_and_hyphenated_props_read_the_element() {
+    let result = compile(
+        "component Text {\n\
+           prop type: string = \"p\";\n\
+           prop v-if: boolean = true;\n\
+           render {\n\
+             <[[type]] v-if={v-if}><slot /></[[type]]>\n\
+           }\n\
+         }\n",
+    );
+    assert!(
+        result.js.contains("<${escapeHtml(this.type)}${htmlAttribute('v-if', this['v-if'])}><slot></slot></${escapeHtml(this.type)}>"),
+        "{}",
+        result.js
+    );
+}
+
+#[test]
+fn props_read_their_attribute_as_their_type() {
+    let result = compile(concat!(
+        "component Badge {\n",
+        "  prop count: number = 1;\n",
+        "  prop showLabel: boolean = false;\n",
+        "  prop tags: string[] = [];\n",
+        "  prop label: string;\n",
+        "  computed total() { return count * 2; }\n",
+        "  render { <p title={label}>[[showLabel ? label : total]] [[tags.length]]</p> }\n",
+        "}\n",
+    ));
+    let js = &result.js;
+    assert!(js.contains("return ['count', 'show-label', 'tags', 'label'];"), "{}", js);
+    assert!(js.contains("  get count() {\n    return readProp(this, 'count', 'number', 1);\n  }\n"), "{}", js);
+    assert!(js.contains("  get showLabel() {\n    return readProp(this, 'show-label', 'boolean', false);\n  }\n"), "{}", js);
+    assert!(js.contains("  get label() {\n    return readProp(this, 'label', 'string', undefined);\n  }\n"), "{}", js);
+
+    let script = concat!(
+        "const badge = mount('ema-badge');\n",
+        "console.log(badge.shadowRoot.innerHTML.trim());\n",
+        "badge.setAttribute('count', '4');\n",
+        "badge.setAttribute('tags', '[\"a\", \"b\"]');\n",
+        "console.log(badge.shadowRoot.innerHTML.trim());\n",
+        "badge.setAttribute('count', 'many');\n",
+        "badge.setAttribute('label', 'Hi');\n",
+        "badge.setAttribute('show-label', '');\n",
+        "console.log(badge.shadowRoot.innerHTML.trim());\n",
+        // An object or array attribute in a template is set as JSON
+        "console.log(htmlAttribute('tags', ['a']));\n",
+    );
+    if let Some(output) = run_in_node(js, script) {
+        assert_eq!(output, "<p>2 0</p>\n<p>8 2</p>\n<p title=\"Hi\">Hi 2</p>\n tags=\"[&quot;a&quot;]\"\n");
+    }
+}
+
+#[test]
+fn markup_values_render_as_nested_templates() {
+    let result = compile(concat!(
+        "component List {\n",
+        "  prop item: string = \"none\";\n",
+        "  state items: string[] = [\"a<b\"];\n",
+        "  state busy: boolean = false;\n",
+        "  render {\n",
+        "    <ul>{busy ? <spinner /> : \"Idle\"}{items.map(item => <li title={item}>[[item]]</li>)}{!busy && <p>[[item]]</p>}</ul>\n",
+        "  }\n",
+        "}\n",
+    ));
+    let js = &result.js;
+    assert!(js.contains("${this.state.busy ? `<spinner></spinner>` : escapeHtml('Idle')}"), "{}", js);
+    // The arrow's parameter shadows the prop
+    assert!(
+        js.contains("${this.state.items.map((item) => `<li${htmlAttribute('title', item)}>${escapeHtml(item)}</li>`).join('')}"),
+        "{}",
+        js
+    );
+    assert!(js.contains("${!this.state.busy ? `<p>${escapeHtml(this.item)}</p>` : ''}"), "{}", js);
+
+    let script = "console.log(mount('ema-list').shadowRoot.innerHTML.trim());";
+    if let Some(output) = run_in_node(js, script) {
+        assert_eq!(output, "<ul>Idle<li title=\"a&lt;b\">a&lt;b</li><p>none</p></ul>\n");
+    }
+}
+
+#[test]
+fn methods_read_the_element_and_write_state_through_set_state() {
+    let result = compile(concat!(
+        "component Counter {\n",
+        "  prop step: number = 2;\n",
+        "  state count: number = 0;\n",
+        "  state label: string = \"\";\n",
+        "  function inc() { count++; }\n",
+        "  function add(amount) { count += amount * step; setLabel(`at ${count}`); }\n",
+        "  function local(count) { count = 1; const setLabel = (text) => text; setLabel(\"x\"); }\n",
+        "  render { <p>[[label]] [[count]]</p> }\n",
+        "}\n",
+    ));
+    let js = &result.js;
+    assert!(js.contains("  inc() {\n    this.setState('count', this.state.count + 1);\n  }\n"), "{}", js);
+    assert!(
+        js.contains(concat!(
+            "  add(amount) {\n",
+            "    this.setState('count', this.state.count + amount * this.step);\n",
+            "    this.setState('label', `at ${this.state.count}`);\n",
+            "  }\n",
+        )),
+        "{}",
+        js
+    );
+    // A parameter or local shadows the state variable and its setter
+    assert!(js.contains("    count = 1;\n"), "{}", js);
+    assert!(js.contains("    setLabel('x');\n"), "{}", js);
+
+    let script = concat!(
+        "const counter = mount('ema-counter');\n",
+        "counter.inc();\n",
+        "counter.add(3);\n",
+        "console.log(counter.shadowRoot.innerHTML.trim());\n",
+    );
+    if let Some(output) = run_in_node(js, script) {
+        assert_eq!(output, "<p>at 7 7</p>\n");
+    }
+}
@@ -0,0 +1,419 @@
+// Lexer behavior: token boundaries, mode switches and recovery.
+//
+// Run with `cargo test --test lexer`.
+
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "../emadocs-compiler.rs"]
+mod compiler;
+
+use compiler::{codes, Lexer, Token, TokenType};
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    Lexer::new(source, "test.ema").tokenize().unwrap_or_else(|error| panic!("{}", error))
+}
+
+/// The significant tokens of `source` as `(type, text)`.
+fn lex(source: &str) -> Vec<(TokenType, String)> {
+    tokenize(source)
+        .into_iter()
+        .filter(|token| !token.is_trivia() && token.token_type != TokenType::Eof)
+        .map(|token| (token.token_type, token.value.to_string()))
+        .collect()
+}
+
+fn has(tokens: &[(TokenType, String)], token_type: TokenType, value: &str) -> bool {
+    tokens.iter().any(|(t, v)| *t == token_type && v == value)
+}
+
+/// The lexer's diagnostics for `source` as `(code, "line:column-line:column")`.
+fn diagnostics(source: &str) -> Vec<(String, String)> {
+    let mut lexer = Lexer::new(source, "test.ema");
+    let _ = lexer.tokenize();
+    lexer
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| {
+            let span = &diagnostic.span;
+            let range = format!("{}:{}-{}:{}", span.start.line, span.start.column, span.end.line, span.end.column);
+            (diagnostic.code.clone(), range)
+        })
+        .collect()
+}
+
+/// The .ema sources shipped with the framework, by path.
+fn shipped_sources() -> Vec<(String, String)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut paths = vec![
+        root.join("examples/test.ema"),
+        root.join("../../examples/todo-app.ema"),
+        root.join("../../index.ema"),
+    ];
+    let pattern = root.join("../../components/*/*.ema");
+    if let Ok(components) = glob::glob(&pattern.to_string_lossy()) {
+        paths.extend(components.filter_map(Result::ok));
+    }
+    paths
+        .iter()
+        .map(|path| (path.display().to_string(), fs::read_to_string(path).expect("shipped source")))
+        .collect()
+}
+
+#[test]
+fn shipped_sources_lex_to_the_end() {
+    for (path, source) in shipped_sources() {
+        let tokens = Lexer::new(&source, &path).tokenize().unwrap_or_else(|error| panic!("{}: {}", path, error));
+        assert_eq!(tokens.last().map(|token| &token.token_type), Some(&TokenType::Eof), "{}", path);
+    }
+}
+
+#[test]
+fn repeated_input_lexes_to_repeated_tokens() {
+    // The cursor must not drift however far into the input it gets
+    let page = "component Card {\n  prop title: string = \"x\";\n  // note\n}\n";
+    let once = tokenize(page);
+    let repeated = page.repeat(1000);
+    let many = tokenize(&repeated);
+    assert_eq!(many.len() - 1, (once.len() - 1) * 1000);
+    let tail = &many[many.len() - once.len()..];
+    for (expected, actual) in once.iter().zip(tail) {
+        assert_eq!((&expected.token_type, &expected.value), (&actual.token_type, &actual.value));
+    }
+}
+
+#[test]
+fn unicode_identifiers_and_strings() {
+    let tokens = lex("\u{FEFF}const größe = 'Kullanıcılar 🎉';\nlet née\u{0301} = größe;");
+    assert_eq!(tokens[0], (TokenType::Const, "const".to_string()));
+    assert!(has(&tokens, TokenType::Identifier, "größe"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Identifier, "née\u{0301}"), "{:?}", tokens);
+    assert!(tokens.iter().any(|(t, v)| *t == TokenType::String && v.contains("Kullanıcılar 🎉")), "{:?}", tokens);
+}
+
+#[test]
+fn tokens_cover_the_source_by_byte_offset() {
+    let mut sources = vec![(
+        "unicode".to_string(),
+        "const größe = 'Kullanıcılar 🎉';\n<p>© 2024 — ✓</p>\nstyle .ü { content: \"→\"; }\n".to_string(),
+    )];
+    sources.extend(shipped_sources());
+    for (path, source) in &sources {
+        let mut offset = 0;
+        for token in Lexer::new(source, path).tokenize().expect("tokens") {
+            assert_eq!(token.span.start.offset, offset, "{}: gap before {:?}", path, token);
+            assert!(source[offset..token.span.end.offset].contains(token.value), "{}: {:?}", path, token);
+            offset = token.span.end.offset;
+        }
+        assert_eq!(offset, source.len(), "{}", path);
+    }
+}
+
+#[test]
+fn token_spans_start_at_the_token() {
+    let source = "const ğ = 1;\n  total == \"ü\";";
+    let spans: Vec<(String, usize, usize, usize, usize)> = tokenize(source)
+        .into_iter()
+        .filter(|token| !token.is_trivia() && token.token_type != TokenType::Eof)
+        .map(|token| {
+            let text = source[token.span.start.offset..token.span.end.offset].to_string();
+            (text, token.span.start.line, token.span.start.column, token.span.end.line, token.span.end.column)
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ("const".to_string(), 1, 1, 1, 6),
+            ("ğ".to_string(), 1, 7, 1, 8),
+            ("=".to_string(), 1, 9, 1, 10),
+            ("1".to_string(), 1, 11, 1, 12),
+            (";".to_string(), 1, 12, 1, 13),
+            ("total".to_string(), 2, 3, 2, 8),
+            ("==".to_string(), 2, 9, 2, 11),
+            ("\"ü\"".to_string(), 2, 12, 2, 15),
+            (";".to_string(), 2, 15, 2, 16),
+        ]
+    );
+}
+
+#[test]
+fn unterminated_string_is_reported() {
+    assert_eq!(
+        diagnostics("const a = 'open\nconst b = 1;"),
+        [(codes::UNTERMINATED_STRING.to_string(), "1:11-1:16".to_string())]
+    );
+}
+
+#[test]
+fn unterminated_template_is_reported() {
+    assert_eq!(
+        diagnostics("const a = 1;\nconst t = `open\nstill open"),
+        [(codes::UNTERMINATED_TEMPLATE.to_string(), "2:11-3:11".to_string())]
+    );
+}
+
+#[test]
+fn unterminated_comment_is_reported() {
+    assert_eq!(
+        diagnostics("const a = 1; /* open\n"),
+        [(codes::UNTERMINATED_COMMENT.to_string(), "1:14-2:1".to_string())]
+    );
+}
+
+#[test]
+fn unexpected_characters_are_reported_and_lexing_goes_on() {
+    assert_eq!(
+        diagnostics("a § b;\nc ¤ d;"),
+        [
+            (codes::UNEXPECTED_CHARACTER.to_string(), "1:3-1:4".to_string()),
+            (codes::UNEXPECTED_CHARACTER.to_string(), "2:3-2:4".to_string()),
+        ]
+    );
+
+    // The characters the language uses are tokens
+    let tokens = lex("#save @media $el &");
+    assert!(has(&tokens, TokenType::Hash, "#"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::At, "@"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Identifier, "$el"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Ampersand, "&"), "{:?}", tokens);
+}
+
+#[test]
+fn style_used_as_a_name_stays_script() {
+    let source = "const style = document.createElement('style');\nstyle.textContent = `a ${1} b`;\nstyle = null;";
+    assert!(diagnostics(source).is_empty(), "{:?}", diagnostics(source));
+    let tokens = lex(source);
+    assert!(has(&tokens, TokenType::Identifier, "textContent"));
+    assert!(has(&tokens, TokenType::TemplateStart, "`"));
+}
+
+#[test]
+fn animation_object_key_stays_script() {
+    // A `style` block after it must still switch to CSS, where
+    // `margin-top` is one word
+    let source = "const a = { animation: 'x', style };\nstyle Button { margin-top: 0; }";
+    assert!(diagnostics(source).is_empty(), "{:?}", diagnostics(source));
+    assert!(has(&lex(source), TokenType::Identifier, "margin-top"));
+}
+
+#[test]
+fn style_blocks_lex_as_css() {
+    for source in [
+        "style Button { margin-top: 0; }",
+        "style .card { margin-top: 0; }",
+        "style text[type=\"h1\"] { margin-top: 0; }",
+        "style { margin-top: 0; }",
+        "animation fadeIn { from { margin-top: 0; } }",
+    ] {
+        assert!(diagnostics(source).is_empty(), "{}: {:?}", source, diagnostics(source));
+        let tokens = lex(source);
+        assert!(has(&tokens, TokenType::Identifier, "margin-top"), "{}: {:?}", source, tokens);
+    }
+}
+
+#[test]
+fn shipped_index_lexes_cleanly() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../index.ema");
+    let source = fs::read_to_string(path).expect("index.ema");
+    assert!(diagnostics(&source).is_empty(), "{:?}", diagnostics(&source));
+}
+
+#[test]
+fn each_region_lexes_by_its_own_rules() {
+    // `<` compares, or opens type parameters, in script
+    let tokens = lex("if (a < b) { x = a<b; }\ncomponent Button<T> { }");
+    assert_eq!(tokens.iter().filter(|(t, _)| *t == TokenType::Less).count(), 3, "{:?}", tokens);
+    assert!(!tokens.iter().any(|(t, _)| *t == TokenType::OpeningTagStart), "{:?}", tokens);
+
+    // Markup text keeps entities and punctuation as written
+    let tokens = lex("<footer>&copy; 2024 Emadocs - a + b</footer>");
+    assert!(has(&tokens, TokenType::Text, "&copy; 2024 Emadocs - a + b"), "{:?}", tokens);
+
+    // A tag in an expression switches to markup and back
+    let tokens = lex("const view = <p class=\"x\">hi</p>;\nconst n = 1;");
+    assert!(has(&tokens, TokenType::Text, "hi"), "{:?}", tokens);
+    assert_eq!(tokens.iter().filter(|(t, _)| *t == TokenType::Const).count(), 2, "{:?}", tokens);
+
+    // CSS words keep their hyphens
+    let tokens = lex("style .hero { margin-top: 0; }");
+    assert!(has(&tokens, TokenType::Identifier, "margin-top"), "{:?}", tokens);
+
+    // Script and style elements hold raw text up to their closing tag, in
+    // any case
+    let tokens = lex("<div><script>if (a </b) { s = \"<p>é</p>\"; }</SCRIPT><style>p { }</Style></div>");
+    assert!(has(&tokens, TokenType::Text, "if (a </b) { s = \"<p>é</p>\"; }"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Text, "p { }"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Identifier, "div"), "{:?}", tokens);
+}
+
+#[test]
+fn template_literals_nest_through_expression_holes() {
+    use TokenType::*;
+    let tokens = lex("`a ${ `b ${c}` } d ${ { k: \"}\" }.k + '`' }`;");
+    let expected = [
+        (TemplateStart, "`"),
+        (TemplateChunk, "a "),
+        (TemplateExprStart, "${"),
+        (TemplateStart, "`"),
+        (TemplateChunk, "b "),
+        (TemplateExprStart, "${"),
+        (Identifier, "c"),
+        (TemplateExprEnd, "}"),
+        (TemplateEnd, "`"),
+        (TemplateExprEnd, "}"),
+        (TemplateChunk, " d "),
+        (TemplateExprStart, "${"),
+        (LeftBrace, "{"),
+        (Identifier, "k"),
+        (Colon, ":"),
+        (String, "}"),
+        (RightBrace, "}"),
+        (Dot, "."),
+        (Identifier, "k"),
+        (Plus, "+"),
+        (String, "`"),
+        (TemplateExprEnd, "}"),
+        (TemplateEnd, "`"),
+        (Semicolon, ";"),
+    ];
+    let actual: Vec<(TokenType, &str)> = tokens.iter().map(|(t, v)| (t.clone(), v.as_str())).collect();
+    assert_eq!(actual, expected);
+
+    // An unclosed hole leaves the literal unterminated
+    assert_eq!(
+        diagnostics("const t = `a ${ b"),
+        [(codes::UNTERMINATED_TEMPLATE.to_string(), "1:11-1:18".to_string())]
+    );
+}
+
+#[test]
+fn interpolations_lex_in_text_and_attribute_values() {
+    let tokens = lex("<p class=\"badge--[[variant]]\">Hi [[user.name]]!</p>");
+    // The parser splits attribute values on their `[[`
+    assert!(has(&tokens, TokenType::String, "badge--[[variant]]"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Text, "Hi"), "{:?}", tokens);
+    let interpolation: Vec<&str> = tokens
+        .iter()
+        .skip_while(|(t, _)| *t != TokenType::InterpolationStart)
+        .map(|(_, v)| v.as_str())
+        .take(5)
+        .collect();
+    assert_eq!(interpolation, ["[[", "user", ".", "name", "]]"]);
+    assert!(has(&tokens, TokenType::Text, "!"), "{:?}", tokens);
+}
+
+#[test]
+fn numeric_literals() {
+    let tokens = lex("x = [0x1F, 0o17, 0b1010, 1e3, 2.5E-4, .5, 1_000_000, 0xFF_FF, 10n, 0x1Fn, 1.2.3];");
+    let numbers: Vec<(TokenType, &str)> = tokens
+        .iter()
+        .filter(|(t, _)| matches!(t, TokenType::Number | TokenType::BigInt))
+        .map(|(t, v)| (t.clone(), v.as_str()))
+        .collect();
+    assert_eq!(
+        numbers,
+        [
+            (TokenType::Number, "0x1F"),
+            (TokenType::Number, "0o17"),
+            (TokenType::Number, "0b1010"),
+            (TokenType::Number, "1e3"),
+            (TokenType::Number, "2.5E-4"),
+            (TokenType::Number, ".5"),
+            (TokenType::Number, "1_000_000"),
+            (TokenType::Number, "0xFF_FF"),
+            (TokenType::BigInt, "10n"),
+            (TokenType::BigInt, "0x1Fn"),
+            // A second `.` starts a member access, not more digits
+            (TokenType::Number, "1.2"),
+            (TokenType::Number, ".3"),
+        ]
+    );
+}
+
+#[test]
+fn invalid_numeric_literals_are_reported() {
+    for (source, range) in [
+        ("x = 0b12;", "1:5-1:9"),
+        ("x = 0x;", "1:5-1:7"),
+        ("x = 1__0;", "1:5-1:9"),
+        ("x = 1_;", "1:5-1:7"),
+        ("x = 1e;", "1:5-1:7"),
+        ("x = 1.5n;", "1:5-1:9"),
+        ("x = 3in;", "1:5-1:8"),
+    ] {
+        assert_eq!(diagnostics(source), [(codes::INVALID_NUMBER.to_string(), range.to_string())], "{}", source);
+    }
+}
+
+#[test]
+fn css_values_are_dimensions_and_percentages() {
+    let tokens = lex("style .hero { padding: 80px 40px; width: 50%; transition: 0.8s; line-height: 1.5; font-size: 1em; }");
+    for dimension in ["80px", "40px", "0.8s", "1em"] {
+        assert!(has(&tokens, TokenType::Dimension, dimension), "{}: {:?}", dimension, tokens);
+    }
+    assert!(has(&tokens, TokenType::Percentage, "50%"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Number, "1.5"), "{:?}", tokens);
+}
+
+#[test]
+fn streamed_tokens_match_tokenize_without_trivia() {
+    let mut sources = vec![("mixed".to_string(), "// c\nconst a = `x${b}`; /* d */\n<p>[[a]]</p>\nstyle .x { top: 1px; }\n".to_string())];
+    sources.extend(shipped_sources());
+    for (path, source) in &sources {
+        let expected: Vec<_> = Lexer::new(source, path)
+            .tokenize()
+            .expect("tokens")
+            .into_iter()
+            .filter(|token| !token.is_trivia())
+            .map(|token| (token.token_type, token.value, token.span))
+            .collect();
+        let mut lexer = Lexer::new(source, path);
+        lexer.set_skip_trivia(true);
+        let streamed: Vec<_> = lexer.by_ref().collect();
+        let actual: Vec<_> = streamed.iter().map(|token| (token.token_type.clone(), token.value, token.span.clone())).collect();
+        assert_eq!(actual, expected, "{}", path);
+        assert!(lexer.diagnostics().is_empty(), "{}: {:?}", path, lexer.diagnostics());
+        // Values borrow from the source rather than copying it
+        for token in streamed.iter().filter(|token| !token.value.is_empty()) {
+            let offset = token.value.as_ptr() as usize - source.as_ptr() as usize;
+            assert!(offset < source.len(), "{}: {:?}", path, token);
+        }
+    }
+}
+
+#[test]
+fn dollar_and_underscore_in_identifiers() {
+    let source = "const $el = jQuery$(_private, __proto, $);";
+    assert!(diagnostics(source).is_empty(), "{:?}", diagnostics(source));
+    let tokens = lex(source);
+    for name in ["$el", "jQuery$", "_private", "__proto", "$"] {
+        assert!(has(&tokens, TokenType::Identifier, name), "{}: {:?}", name, tokens);
+    }
+
+    // In CSS, `$` still starts the `$=` matcher
+    let source = "style a[href$=\".pdf\"] { color: red; }";
+    assert!(diagnostics(source).is_empty(), "{:?}", diagnostics(source));
+    let tokens = lex(source);
+    assert!(has(&tokens, TokenType::Identifier, "href"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Dollar, "$"), "{:?}", tokens);
+}
+
+#[test]
+fn slashes_divide_after_an_operand_and_open_a_regex_elsewhere() {
+    let tokens = lex("const r = /[/]\\/+/gi; x = a / b / c; f(/=/); return /a/.test(s)");
+    assert!(has(&tokens, TokenType::Regex, "/[/]\\/+/gi"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Regex, "/=/"), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Regex, "/a/"), "{:?}", tokens);
+    assert_eq!(tokens.iter().filter(|(t, _)| *t == TokenType::Divide).count(), 2, "{:?}", tokens);
+
+    assert_eq!(diagnostics("x = /abc\n;"), [(codes::UNTERMINATED_REGEX.to_string(), "1:5-1:9".to_string())]);
+}
+
+#[test]
+fn keywords_after_a_dot_are_operands() {
+    let tokens = lex("if (this.state <max) { half = this.state / 2; size = el?.style / 4; }");
+    assert!(!tokens.iter().any(|(t, _)| *t == TokenType::OpeningTagStart || *t == TokenType::Regex), "{:?}", tokens);
+    assert!(has(&tokens, TokenType::Less, "<"), "{:?}", tokens);
+    assert_eq!(tokens.iter().filter(|(t, _)| *t == TokenType::Divide).count(), 2, "{:?}", tokens);
+}
@@ -0,0 +1,851 @@
+// Parser behavior: what each construct parses to and which errors it
+// reports.
+//
+// Run with `cargo test --test parser`.
+
+#[allow(dead_code)]
+#[path = "../emadocs-compiler.rs"]
+mod compiler;
+
+use std::fs;
+use std::path::Path;
+
+use compiler::{
+    codes, ASTNode, ArrowBody, AttributeValue, CompileResult, EmadocsCompiler, Expr, KeyframeOffset, Lexer, Parser, Stmt,
+    SyntaxKind, TypeExpr, TypeParameter, Value,
+};
+
+/// The statements `source` parses to, asserting it has no errors.
+fn parse(source: &str) -> Vec<ASTNode> {
+    let tokens = Lexer::new(source, "test.ema").tokenize().expect("tokens");
+    let mut parser = Parser::new(tokens);
+    let program = parser.parse();
+    let errors: Vec<String> = parser.diagnostics().iter().map(ToString::to_string).collect();
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+    match program {
+        ASTNode::Program { body, .. } => body,
+        other => panic!("expected a program, got {:?}", other),
+    }
+}
+
+/// The `(code, line, column)` of each error parsing `source` reports.
+fn parse_errors(source: &str) -> Vec<(String, usize, usize)> {
+    let tokens = Lexer::new(source, "test.ema").tokenize().expect("tokens");
+    let mut parser = Parser::new(tokens);
+    parser.parse();
+    parser
+        .diagnostics()
+        .iter()
+        .map(|error| (error.code.clone(), error.span.start.line, error.span.start.column))
+        .collect()
+}
+
+fn compile(source: &str) -> CompileResult {
+    EmadocsCompiler::new(source.to_string(), "test.ema".to_string()).compile()
+}
+
+fn error_messages(result: &CompileResult) -> Vec<String> {
+    result.errors.iter().map(ToString::to_string).collect()
+}
+
+/// Asserts that compiling `source` fails with an error `code` at `line:column`.
+fn assert_error(source: &str, code: &str, line: usize, column: usize) {
+    let result = compile(source);
+    assert!(!result.success, "{} compiled", source);
+    assert!(
+        result.errors.iter().any(|error| error.code == code && error.span.start.line == line && error.span.start.column == column),
+        "{}: expected {} at {}:{}, got {:?}",
+        source,
+        code,
+        line,
+        column,
+        error_messages(&result)
+    );
+}
+
+/// The .ema sources shipped with the framework, by path.
+fn shipped_sources() -> Vec<(String, String)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut paths = vec![
+        root.join("examples/test.ema"),
+        root.join("../../examples/todo-app.ema"),
+        root.join("../../index.ema"),
+    ];
+    let pattern = root.join("../../components/*/*.ema");
+    if let Ok(components) = glob::glob(&pattern.to_string_lossy()) {
+        paths.extend(components.filter_map(Result::ok));
+    }
+    paths
+        .iter()
+        .map(|path| (path.display().to_string(), fs::read_to_string(path).expect("shipped source")))
+        .collect()
+}
+
+/// Asserts that the syntax tree of `source` prints back to it byte for byte.
+fn assert_round_trips(name: &str, source: &str) {
+    let tokens = Lexer::new(source, name).tokenize().unwrap_or_else(|error| panic!("{}", error));
+    let mut parser = Parser::new(tokens);
+    let _ = parser.parse();
+    assert_eq!(parser.syntax_tree().text(source), source, "{}", name);
+}
+
+#[test]
+fn nodes_span_their_source() {
+    let source = "// Header\ncomponent Card {\n}\n\nstate Store {\n  count: number = 0;\n}\n";
+    let body = parse(source);
+    let span = body[0].span();
+    assert_eq!((span.start.line, span.start.column), (2, 1));
+    assert_eq!((span.end.line, span.end.column), (3, 2));
+    assert_eq!(&source[span.start.offset..span.end.offset], "component Card {\n}");
+    let span = body[1].span();
+    assert_eq!((span.start.line, span.start.column), (5, 1));
+    assert!(source[span.start.offset..span.end.offset].starts_with("state Store {"));
+}
+
+#[test]
+fn interpolations_in_text_and_attributes() {
+    let body = parse("<div class=\"badge badge--[[variant]]\">Hi [[user.name]]!</div>\n");
+    let (attributes, children) = match &body[0] {
+        ASTNode::Element { attributes, children, .. } => (attributes, children),
+        other => panic!("expected an element, got {:?}", other),
+    };
+    match &attributes[0].value {
+        AttributeValue::Interpolated(parts) => {
+            assert!(matches!(&parts[0], ASTNode::Text { value, .. } if value == "badge badge--"), "{:?}", parts);
+            assert!(matches!(&parts[1], ASTNode::Interpolation { expression, .. } if expression.to_string() == "variant"), "{:?}", parts);
+        }
+        other => panic!("expected an interpolated value, got {:?}", other),
+    }
+    assert!(matches!(&children[0], ASTNode::Text { value, .. } if value == "Hi"), "{:?}", children);
+    assert!(matches!(&children[1], ASTNode::Text { value, .. } if value == " "), "{:?}", children);
+    assert!(matches!(&children[2], ASTNode::Interpolation { expression, .. } if expression.to_string() == "user.name"), "{:?}", children);
+    assert!(matches!(&children[3], ASTNode::Text { value, .. } if value == "!"), "{:?}", children);
+}
+
+#[test]
+fn syntax_tree_round_trips() {
+    for (path, source) in shipped_sources() {
+        assert_round_trips(&path, &source);
+    }
+    // Input the parser gives up on is still kept, as is every comment and
+    // blank line
+    for source in [
+        "component A {\n  prop title: ;\n}\n} page ] { <div><p>x</div>\n",
+        "// only a comment\n\n\t\n",
+        "\u{FEFF}component B { } /* tail */",
+        "",
+    ] {
+        assert_round_trips("broken.ema", source);
+    }
+}
+
+#[test]
+fn syntax_tree_keeps_trivia_in_its_nodes() {
+    let source = "// Card\ncomponent Card {\n}\n";
+    let tokens = Lexer::new(source, "test.ema").tokenize().expect("tokens");
+    let mut parser = Parser::new(tokens);
+    parser.parse();
+    let tree = parser.syntax_tree();
+    assert_eq!(tree.kind, SyntaxKind::Root);
+    let component = tree.child_nodes().next().expect("a component node");
+    assert_eq!(component.kind, SyntaxKind::Component);
+    assert_eq!(component.text(source), "component Card {\n}");
+}
+
+#[test]
+fn component_members() {
+    let body = parse(concat!(
+        "component Counter {\n",
+        "  prop label: string = \"Count\";\n",
+        "  prop step: number;\n",
+        "  event onChange: (value: number) => void;\n",
+        "  state count: number = 0;\n",
+        "  function increment() { }\n",
+        "  async function reset(to) { }\n",
+        "  render {\n",
+        "    <button>[[label]]</button>\n",
+        "  }\n",
+        "}\n",
+    ));
+    let (props, events, state, methods, render) = match &body[0] {
+        ASTNode::Component { props, events, state, methods, render, .. } => (props, events, state, methods, render),
+        other => panic!("expected a component, got {:?}", other),
+    };
+    let props: Vec<(&str, String, Option<String>)> = props
+        .iter()
+        .filter_map(|prop| match prop {
+            ASTNode::Prop { name, prop_type, default, .. } => {
+                Some((name.as_str(), prop_type.to_string(), default.as_ref().map(Value::to_js)))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        props,
+        [("label", "string".to_string(), Some("'Count'".to_string())), ("step", "number".to_string(), None)]
+    );
+    assert!(
+        matches!(&events[..], [ASTNode::EventDeclaration { name, signature, .. }] if name == "onChange" && signature.to_string() == "(value: number) => void"),
+        "{:?}",
+        events
+    );
+    assert!(
+        matches!(&state[..], [ASTNode::StateVariable { name, initial: Some(initial), .. }] if name == "count" && initial.to_string() == "0"),
+        "{:?}",
+        state
+    );
+    let methods: Vec<(&str, bool, usize)> = methods
+        .iter()
+        .filter_map(|method| match method {
+            ASTNode::Method { name, is_async, parameters, .. } => Some((name.as_str(), *is_async, parameters.len())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(methods, [("increment", false, 0), ("reset", true, 1)]);
+    assert!(matches!(&render[..], [ASTNode::Element { tag_name, .. }] if tag_name == "button"), "{:?}", render);
+
+    // `render()` is the same block
+    match &parse("component A {\n  render() {\n    <p>a</p>\n  }\n}\n")[0] {
+        ASTNode::Component { render, .. } => assert_eq!(render.len(), 1),
+        other => panic!("expected a component, got {:?}", other),
+    }
+}
+
+#[test]
+fn prop_types_and_defaults() {
+    let body = parse(concat!(
+        "component Badge {\n",
+        "  prop variant: \"minimal\" | \"neo\" = \"minimal\";\n",
+        "  prop tags?: string[];\n",
+        "  prop counts: Map<string, number>;\n",
+        "  prop onSelect: (id: number) => void;\n",
+        "  prop options: object = { dense: true, size: -2 };\n",
+        "  prop created: Date = new Date();\n",
+        "}\n",
+    ));
+    let props: Vec<(&str, &TypeExpr, Option<&Value>)> = match &body[0] {
+        ASTNode::Component { props, .. } => props
+            .iter()
+            .filter_map(|prop| match prop {
+                ASTNode::Prop { name, prop_type, default, .. } => Some((name.as_str(), prop_type, default.as_ref())),
+                _ => None,
+            })
+            .collect(),
+        other => panic!("expected a component, got {:?}", other),
+    };
+    assert_eq!(props.len(), 6, "{:?}", props);
+
+    let (_, variant, default) = props[0];
+    assert_eq!(
+        *variant,
+        TypeExpr::Union(vec![
+            TypeExpr::Literal(Value::String("minimal".to_string())),
+            TypeExpr::Literal(Value::String("neo".to_string())),
+        ])
+    );
+    assert_eq!(default, Some(&Value::String("minimal".to_string())));
+    assert!(variant.accepts(default.unwrap()));
+    assert!(!variant.accepts(&Value::String("bold".to_string())));
+
+    let (_, tags, _) = props[1];
+    assert_eq!(*tags, TypeExpr::Optional(Box::new(TypeExpr::Array(Box::new(TypeExpr::Primitive("string".to_string()))))));
+    assert_eq!(props[2].1.to_string(), "Map<string, number>");
+    assert!(matches!(props[3].1, TypeExpr::Function { parameters, .. } if parameters.len() == 1), "{:?}", props[3].1);
+
+    assert_eq!(props[4].2.map(Value::to_js).as_deref(), Some("{ dense: true, size: -2 }"));
+    assert!(matches!(props[5].2, Some(Value::Expression(source)) if source == "new Date()"), "{:?}", props[5].2);
+}
+
+/// The expression `source` parses to.
+fn expression(source: &str) -> Expr {
+    let tokens = Lexer::new(source, "test.ema").tokenize().expect("tokens");
+    Parser::new(tokens).parse_expression().unwrap_or_else(|error| panic!("{}: {}", source, error))
+}
+
+#[test]
+fn expressions_parse_by_precedence() {
+    match expression("a + b * c") {
+        Expr::Binary { operator, left, right } => {
+            assert_eq!(operator, "+");
+            assert!(matches!(*left, Expr::Identifier(ref name) if name == "a"));
+            assert!(matches!(*right, Expr::Binary { ref operator, .. } if operator == "*"));
+        }
+        other => panic!("expected a sum, got {:?}", other),
+    }
+    match expression("a ? b : c ? d : e") {
+        Expr::Conditional { alternate, .. } => assert!(matches!(*alternate, Expr::Conditional { .. })),
+        other => panic!("expected a conditional, got {:?}", other),
+    }
+    match expression("!ready && items.length > 0 || force") {
+        Expr::Binary { operator, left, .. } => {
+            assert_eq!(operator, "||");
+            assert!(matches!(*left, Expr::Binary { ref operator, .. } if operator == "&&"));
+        }
+        other => panic!("expected `||`, got {:?}", other),
+    }
+    assert_eq!(expression("(a + b) * c").to_string(), "(a + b) * c");
+}
+
+#[test]
+fn expressions_cover_the_script_grammar() {
+    match expression("todos?.map(todo => (<todo-item todo={todo} />))") {
+        Expr::Call { callee, arguments, optional: false } => {
+            assert!(matches!(*callee, Expr::Member { optional: true, ref property, .. } if property == "map"));
+            match &arguments[0] {
+                Expr::Arrow { parameters, body: ArrowBody::Expression(body), is_async: false } => {
+                    assert_eq!(parameters.len(), 1);
+                    assert!(matches!(**body, Expr::Markup(_)), "{:?}", body);
+                }
+                other => panic!("expected an arrow function, got {:?}", other),
+            }
+        }
+        other => panic!("expected a call, got {:?}", other),
+    }
+    assert!(matches!(expression("await api.get(`/users/${id}`)"), Expr::Await(_)));
+    assert!(matches!(expression("{ ...defaults, [key]: value, done }"), Expr::Object(ref properties) if properties.len() == 3));
+    assert!(matches!(expression("[first, ...rest]"), Expr::Array(ref items) if matches!(items[1], Expr::Spread(_))));
+    assert!(matches!(expression("`${a} and ${b}`"), Expr::Template { ref expressions, .. } if expressions.len() == 2));
+    assert!(matches!(expression("new Map(entries)"), Expr::New { .. }));
+    assert!(matches!(expression("count++"), Expr::Update { prefix: false, .. }));
+    assert!(matches!(expression("total += item.price"), Expr::Assign { ref operator, .. } if operator == "+="));
+}
+
+#[test]
+fn expressions_cover_every_operator() {
+    for source in [
+        "a << 2 | b >> 1 ^ c >>> 3 & d",
+        "x <<= 1",
+        "x >>= 2",
+        "x >>>= 3",
+        "x |= a & b",
+        "x &= 2",
+        "x ^= 3",
+        "x **= 2",
+        "x ??= fallback",
+        "x ||= 2",
+        "x &&= ready",
+        "a > b >= c",
+        "(-a) ** 2 + 2 ** -b",
+        "s.replace(/\\s+/g, ' ').split(/[/,]/)",
+        "total / count / 2",
+    ] {
+        assert_eq!(expression(source).to_string(), source);
+    }
+    // Shifts bind between comparison and addition
+    assert_eq!(expression("a + 1 << 2 < b").to_string(), "a + 1 << 2 < b");
+    match expression("a + 1 << 2 < b") {
+        Expr::Binary { operator, left, .. } => {
+            assert_eq!(operator, "<");
+            assert!(matches!(*left, Expr::Binary { ref operator, .. } if operator == "<<"));
+        }
+        other => panic!("expected a comparison, got {:?}", other),
+    }
+    // Type arguments close with `>>` too
+    assert_eq!(expression("new Map<string, Array<number>>()").to_string(), "new Map()");
+}
+
+#[test]
+fn object_literals_take_accessors_and_async_methods() {
+    let source = "{ get total() { return a + b; }, set total(value) { a = value; }, async save() { await db; }, get: 1 }";
+    let expected = concat!(
+        "{ get total() {\n  return a + b;\n}, ",
+        "set total(value) {\n  a = value;\n}, ",
+        "save: async function() {\n  await db;\n}, ",
+        "get: 1 }",
+    );
+    assert_eq!(expression(source).to_string(), expected);
+}
+
+#[test]
+fn unary_operand_of_exponent_needs_parentheses() {
+    let tokens = Lexer::new("-a ** 2", "test.ema").tokenize().expect("tokens");
+    let error = Parser::new(tokens).parse_expression().expect_err("`-a ** 2` parsed");
+    assert_eq!(error.code, codes::UNEXPECTED_TOKEN);
+    assert_eq!(error.span.start.column, 4);
+}
+
+#[test]
+fn animation_keyframes() {
+    let body = parse("animation pulse {\n  from { opacity: 0; }\n  0%, 50.5% { opacity: 0.5; }\n  TO { opacity: 1; }\n}\n");
+    let (name, keyframes) = match &body[0] {
+        ASTNode::Animation { name, keyframes, .. } => (name, keyframes),
+        other => panic!("expected an animation, got {:?}", other),
+    };
+    assert_eq!(name, "pulse");
+    let offsets: Vec<&[KeyframeOffset]> = keyframes
+        .iter()
+        .filter_map(|keyframe| match keyframe {
+            ASTNode::Keyframe { offsets, .. } => Some(&offsets[..]),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        offsets,
+        [
+            &[KeyframeOffset::From][..],
+            &[KeyframeOffset::Percentage(0.0), KeyframeOffset::Percentage(50.5)][..],
+            &[KeyframeOffset::To][..],
+        ]
+    );
+}
+
+#[test]
+fn keyframes_outside_0_to_100_percent_are_errors() {
+    assert_eq!(
+        parse_errors("animation grow {\n  150% { width: 0; }\n}\n"),
+        [(codes::INVALID_KEYFRAME.to_string(), 2, 3)]
+    );
+}
+
+/// The `(path, params, component, option names)` of each route in a router.
+fn routes(source: &str) -> Vec<(String, Vec<String>, String, Vec<String>)> {
+    match &parse(source)[0] {
+        ASTNode::Router { routes, .. } => routes
+            .iter()
+            .map(|route| match route {
+                ASTNode::Route { path, params, component, options, .. } => (
+                    path.clone(),
+                    params.clone(),
+                    component.clone(),
+                    options.iter().map(|(name, value)| format!("{}: {}", name, value)).collect(),
+                ),
+                other => panic!("expected a route, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("expected a router, got {:?}", other),
+    }
+}
+
+#[test]
+fn router_blocks_and_markup_parse_to_the_same_routes() {
+    let expected = vec![
+        ("/".to_string(), vec![], "HomePage".to_string(), vec![]),
+        (
+            "/users/:id/posts/:post".to_string(),
+            vec!["id".to_string(), "post".to_string()],
+            "UserPostPage".to_string(),
+            vec!["guard: requireAuth".to_string(), "layout: 'main'".to_string()],
+        ),
+        ("/files/*".to_string(), vec!["*".to_string()], "FilesPage".to_string(), vec![]),
+    ];
+    let block = routes(concat!(
+        "router {\n",
+        "  route \"/\" -> HomePage;\n",
+        "  route \"/users/:id/posts/:post\" -> UserPostPage { guard: requireAuth, layout: \"main\" };\n",
+        "  route \"/files/*\" -> FilesPage;\n",
+        "}\n",
+    ));
+    assert_eq!(block, expected);
+    let markup = routes(concat!(
+        "<router>\n",
+        "  <route path=\"/\" component={HomePage} />\n",
+        "  <route path=\"/users/:id/posts/:post\" component=\"UserPostPage\" guard={requireAuth} layout=\"main\" />\n",
+        "  <route path=\"/files/*\" component={FilesPage} />\n",
+        "</router>\n",
+    ));
+    assert_eq!(markup, expected);
+}
+
+#[test]
+fn router_markup_only_holds_complete_routes() {
+    for (source, column) in [
+        ("<router>\n  <div />\n</router>\n", 3),
+        ("<router>\n  <route component={HomePage} />\n</router>\n", 3),
+        ("<router>\n  <route path={home} component={HomePage} />\n</router>\n", 10),
+    ] {
+        assert_eq!(parse_errors(source).first(), Some(&(codes::INVALID_ROUTE.to_string(), 2, column)), "{}", source);
+    }
+}
+
+#[test]
+fn type_interface_and_enum_declarations() {
+    let body = parse(concat!(
+        "type Status = \"active\" | \"archived\";\n",
+        "type Page<T extends object = {}> { items: T[]; next?: string }\n",
+        "interface Admin extends User, Auditable { role: Role; grant(user: User, scope?: string): boolean }\n",
+        "enum Role { Viewer, Editor = 5, Owner, \"Super User\" = \"root\" }\n",
+    ));
+    match &body[0] {
+        ASTNode::Type { name, type_params, definition, .. } => {
+            assert_eq!(name, "Status");
+            assert!(type_params.is_empty());
+            assert!(matches!(definition, TypeExpr::Union(members) if members.len() == 2), "{:?}", definition);
+        }
+        other => panic!("expected a type, got {:?}", other),
+    }
+    match &body[1] {
+        ASTNode::Type { type_params, definition, .. } => {
+            assert_eq!(
+                type_params,
+                &[TypeParameter {
+                    name: "T".to_string(),
+                    constraint: Some(TypeExpr::Primitive("object".to_string())),
+                    default: Some(TypeExpr::Object(Vec::new())),
+                }]
+            );
+            assert_eq!(definition.to_string(), "{ items: T[]; next?: string }");
+        }
+        other => panic!("expected a type, got {:?}", other),
+    }
+    match &body[2] {
+        ASTNode::Interface { name, extends, fields, .. } => {
+            assert_eq!(name, "Admin");
+            let extends: Vec<String> = extends.iter().map(ToString::to_string).collect();
+            assert_eq!(extends, ["User", "Auditable"]);
+            let fields: Vec<(&str, String)> = fields.iter().map(|(name, field)| (name.as_str(), field.to_string())).collect();
+            assert_eq!(fields, [("role", "Role".to_string()), ("grant", "(user: User, scope?: string) => boolean".to_string())]);
+        }
+        other => panic!("expected an interface, got {:?}", other),
+    }
+    match &body[3] {
+        ASTNode::Enum { name, members, .. } => {
+            assert_eq!(name, "Role");
+            let members: Vec<(&str, Option<String>)> =
+                members.iter().map(|member| (member.name.as_str(), member.value.as_ref().map(ToString::to_string))).collect();
+            assert_eq!(
+                members,
+                [
+                    ("Viewer", None),
+                    ("Editor", Some("5".to_string())),
+                    ("Owner", None),
+                    ("Super User", Some("'root'".to_string())),
+                ]
+            );
+        }
+        other => panic!("expected an enum, got {:?}", other),
+    }
+}
+
+#[test]
+fn config_and_plugin_values_are_typed() {
+    let body = parse(concat!(
+        "config {\n",
+        "  entry: \"./src/index.ema\";\n",
+        "  port: 3000;\n",
+        "  offset: -1.5;\n",
+        "  minify: true;\n",
+        "  plugins: [EmadocsUI, Emadocs.Sass];\n",
+        "  devServer: { hot: false; proxy: null };\n",
+        "  build: process.env.NODE_ENV === \"production\";\n",
+        "}\n",
+        "plugin Analytics {\n",
+        "  name: \"analytics\";\n",
+        "  methods: { track: (event: string) => void; };\n",
+        "  init() { }\n",
+        "}\n",
+    ));
+    let properties = match &body[0] {
+        ASTNode::Config { properties, .. } => properties,
+        other => panic!("expected a config, got {:?}", other),
+    };
+    let expected = [
+        ("entry", Value::String("./src/index.ema".to_string())),
+        ("port", Value::Number(3000.0)),
+        ("offset", Value::Number(-1.5)),
+        ("minify", Value::Boolean(true)),
+        ("plugins", Value::Array(vec![Value::Reference("EmadocsUI".to_string()), Value::Reference("Emadocs.Sass".to_string())])),
+        ("devServer", Value::Object(vec![("hot".to_string(), Value::Boolean(false)), ("proxy".to_string(), Value::Null)])),
+        ("build", Value::Expression("process.env.NODE_ENV === \"production\"".to_string())),
+    ];
+    let properties: Vec<(&str, &Value)> = properties.iter().map(|(name, value)| (name.as_str(), value)).collect();
+    assert_eq!(properties, expected.iter().map(|(name, value)| (*name, value)).collect::<Vec<_>>());
+
+    match &body[1] {
+        ASTNode::Plugin { name, properties, signatures, methods, .. } => {
+            assert_eq!(name, "Analytics");
+            assert_eq!(properties, &[("name".to_string(), Value::String("analytics".to_string()))]);
+            let signatures: Vec<(&str, String)> =
+                signatures.iter().map(|(name, signature)| (name.as_str(), signature.to_string())).collect();
+            assert_eq!(signatures, [("track", "(event: string) => void".to_string())]);
+            assert!(matches!(&methods[..], [ASTNode::Method { name, .. }] if name == "init"), "{:?}", methods);
+        }
+        other => panic!("expected a plugin, got {:?}", other),
+    }
+}
+
+#[test]
+fn parsing_recovers_and_collects_every_error() {
+    let source = concat!(
+        "function f() {\n",
+        "  const = 1;\n",
+        "  ok();\n",
+        "}\n",
+        "component C { garbage ) ]\n",
+        "  state n: number = 0;\n",
+        "}\n",
+        "animation grow {\n",
+        "  150% { width: 0; }\n",
+        "}\n",
+        "} oops\n",
+        "component D { }\n",
+    );
+    let tokens = Lexer::new(source, "test.ema").tokenize().expect("tokens");
+    let mut parser = Parser::new(tokens);
+    let body = match parser.parse() {
+        ASTNode::Program { body, .. } => body,
+        other => panic!("expected a program, got {:?}", other),
+    };
+    let found: Vec<(&str, usize, usize)> =
+        parser.diagnostics().iter().map(|error| (error.code.as_str(), error.span.start.line, error.span.start.column)).collect();
+    assert_eq!(
+        found,
+        [
+            (codes::UNEXPECTED_TOKEN, 2, 9),
+            (codes::UNEXPECTED_TOKEN, 5, 15),
+            (codes::INVALID_KEYFRAME, 9, 3),
+            (codes::UNEXPECTED_TOKEN, 11, 1),
+        ]
+    );
+
+    // A statement resynchronizes at its `;`
+    match &body[0] {
+        ASTNode::Method { body, .. } => {
+            assert!(matches!(&body[0], Stmt::Raw(source) if source == "const = 1"), "{:?}", body);
+            assert!(matches!(&body[1], Stmt::Expression(Expr::Call { .. })), "{:?}", body);
+        }
+        other => panic!("expected a function, got {:?}", other),
+    }
+    // A member resynchronizes at the next member
+    match &body[1] {
+        ASTNode::Component { name, state, .. } => {
+            assert_eq!(name, "C");
+            assert!(matches!(state.as_slice(), [ASTNode::StateVariable { name, .. }] if name == "n"), "{:?}", state);
+        }
+        other => panic!("expected a component, got {:?}", other),
+    }
+    // Declarations after the errors still parse
+    assert!(matches!(body.last(), Some(ASTNode::Component { name, .. }) if name == "D"), "{:?}", body.last());
+}
+
+#[test]
+fn top_level_script_statements() {
+    let result = compile(
+        "const style = document.createElement('style');\n\
+         style.textContent = `a ${1} b`;\n\
+         document.head.appendChild(style);\n\
+         style .card { color: red; }\n",
+    );
+    assert!(result.success, "{:?}", error_messages(&result));
+    assert!(result.js.contains("style.textContent = `a ${1} b`;"), "{}", result.js);
+    assert!(result.js.contains("document.head.appendChild(style);"), "{}", result.js);
+    assert!(result.css.contains(".card"), "{}", result.css);
+}
+
+#[test]
+fn style_at_rule() {
+    let result = compile("style @media (max-width: 768px) {\n  .hero { color: red; }\n}\n");
+    assert!(result.success, "{:?}", error_messages(&result));
+    assert!(result.css.contains("@media (max-width: 768px)"), "{}", result.css);
+    assert!(result.css.contains(".hero"), "{}", result.css);
+}
+
+#[test]
+fn errors_in_bodies_are_reported() {
+    let source = "function f(){ if (x { y(); } const = 5; foo(; }\ncomponent Foo { garbage ) ] }\n";
+    assert_error(source, codes::UNEXPECTED_TOKEN, 1, 21);
+    assert_error(source, codes::UNEXPECTED_TOKEN, 2, 17);
+}
+
+#[test]
+fn imported_string_names_need_an_alias() {
+    assert_error("import { \"a-b\" } from \"./m.js\";\n", codes::UNEXPECTED_TOKEN, 1, 8);
+    assert!(compile("import { \"a-b\" as ab } from \"./m.js\";\n").success);
+}
+
+#[test]
+fn skipped_tokens_are_reported() {
+    assert_error("} component A { }", codes::UNEXPECTED_TOKEN, 1, 1);
+    assert_error("page Home ] { }", codes::UNEXPECTED_TOKEN, 1, 11);
+    assert_error("state S { a: number = 1; ) }", codes::UNEXPECTED_TOKEN, 1, 26);
+    assert_error("<component Box ]>\n}\n", codes::UNEXPECTED_TOKEN, 1, 16);
+}
+
+#[test]
+fn closing_tags_must_match() {
+    assert_error("<div><span>hi</div>", codes::MISMATCHED_TAG, 1, 6);
+    assert_error("<div><p>x</span></p></div>", codes::MISMATCHED_TAG, 1, 10);
+    assert_error("<div>x", codes::MISMATCHED_TAG, 1, 1);
+
+    let result = compile("<div><p>x</span></p></div>");
+    assert_eq!(result.errors.len(), 1, "{:?}", error_messages(&result));
+    assert!(compile("<div><span>a</span></DIV>").success);
+}
+
+#[test]
+fn partial_ast_is_available() {
+    let result = compile("component A { }\n} oops\ncomponent B { }\n");
+    assert!(!result.success);
+    let body = match result.ast {
+        Some(ASTNode::Program { body, .. }) => body,
+        other => panic!("expected a program, got {:?}", other),
+    };
+    let names: Vec<&str> = body
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Component { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, ["A", "B"]);
+}
+
+#[test]
+fn state_and_api_members() {
+    let result = compile(
+        "state TodoState {\n  todos: Todo[] = [];\n  loading: boolean = false;\n}\n\
+         api TodoAPI {\n  baseUrl: \"https://api.example.com\";\n  async getTodos(): Promise<Todo[]> {\n    return [];\n  }\n}\n",
+    );
+    assert!(result.success, "{:?}", error_messages(&result));
+    let body = match result.ast {
+        Some(ASTNode::Program { body, .. }) => body,
+        other => panic!("expected a program, got {:?}", other),
+    };
+    match &body[0] {
+        ASTNode::State { properties, .. } => assert_eq!(properties.len(), 2),
+        other => panic!("expected state, got {:?}", other),
+    }
+    match &body[1] {
+        ASTNode::Api { properties, methods, .. } => {
+            assert_eq!(properties.len(), 1);
+            assert_eq!(methods.len(), 1);
+        }
+        other => panic!("expected an api, got {:?}", other),
+    }
+}
+
+#[test]
+fn every_error_is_collected() {
+    let result = compile(concat!(
+        "function f() {\n",
+        "  const = 1;\n",
+        "  ok();\n",
+        "}\n",
+        "<ul>\n",
+        "  <li>one</span></li>\n",
+        "  <li ]>two</li>\n",
+        "  <li>three</li>\n",
+        "</ul>\n",
+        "component C { garbage ) ]\n",
+        "  state n: number = 0;\n",
+        "}\n",
+    ));
+    let found: Vec<(&str, usize)> = result.errors.iter().map(|error| (error.code.as_str(), error.span.start.line)).collect();
+    assert_eq!(
+        found,
+        [
+            (codes::UNEXPECTED_TOKEN, 2),
+            (codes::MISMATCHED_TAG, 6),
+            (codes::UNEXPECTED_TOKEN, 7),
+            (codes::UNEXPECTED_TOKEN, 10),
+        ],
+        "{:?}",
+        error_messages(&result)
+    );
+
+    let body = match result.ast {
+        Some(ASTNode::Program { body, .. }) => body,
+        other => panic!("expected a program, got {:?}", other),
+    };
+    // A statement resynchronizes at its `;`
+    match &body[0] {
+        ASTNode::Method { body, .. } => {
+            assert!(matches!(&body[0], Stmt::Raw(source) if source == "const = 1"), "{:?}", body);
+            assert!(matches!(&body[1], Stmt::Expression(Expr::Call { .. })), "{:?}", body);
+        }
+        other => panic!("expected a function, got {:?}", other),
+    }
+    // A tag resynchronizes at its `>`, keeping its siblings
+    match &body[1] {
+        ASTNode::Element { children, .. } => {
+            let items = children.iter().filter(|child| matches!(child, ASTNode::Element { tag_name, .. } if tag_name == "li"));
+            assert_eq!(items.count(), 3, "{:?}", children);
+        }
+        other => panic!("expected a list, got {:?}", other),
+    }
+    // A member resynchronizes at the next member
+    match &body[2] {
+        ASTNode::Component { name, state, .. } => {
+            assert_eq!(name, "C");
+            assert!(matches!(state.as_slice(), [ASTNode::StateVariable { name, .. }] if name == "n"), "{:?}", state);
+        }
+        other => panic!("expected a component, got {:?}", other),
+    }
+}
+
+#[test]
+fn render_keeps_every_root() {
+    let result = compile("component Pair {\n  render {\n    <dt>a</dt>\n    <dd>b</dd>\n  }\n}\n");
+    assert!(result.success, "{:?}", error_messages(&result));
+    match result.ast {
+        Some(ASTNode::Program { body, .. }) => match &body[0] {
+            ASTNode::Component { render, .. } => assert_eq!(render.len(), 2),
+            other => panic!("expected a component, got {:?}", other),
+        },
+        other => panic!("expected a program, got {:?}", other),
+    }
+    assert!(result.js.contains("<dt>a</dt>\n      <dd>b</dd>"), "{}", result.js);
+
+    assert_error("component A {\n  render {\n    <p>a</p>\n    return 1;\n  }\n}\n", codes::UNEXPECTED_TOKEN, 4, 5);
+}
+
+#[test]
+fn component_type_parameters() {
+    let result = compile("component List<T extends Item, K = string> {\n  prop items: T[] = [];\n}\n");
+    assert!(result.success, "{:?}", error_messages(&result));
+    let type_params = match result.ast {
+        Some(ASTNode::Program { body, .. }) => match body.into_iter().next() {
+            Some(ASTNode::Component { type_params, .. }) => type_params,
+            other => panic!("expected a component, got {:?}", other),
+        },
+        other => panic!("expected a program, got {:?}", other),
+    };
+    let names: Vec<&str> = type_params.iter().map(|parameter| parameter.name.as_str()).collect();
+    assert_eq!(names, ["T", "K"]);
+    assert_eq!(type_params[0].constraint.as_ref().map(ToString::to_string).as_deref(), Some("Item"));
+    assert_eq!(type_params[1].default.as_ref().map(ToString::to_string).as_deref(), Some("string"));
+}
+
+#[test]
+fn member_and_parameter_types() {
+    let result = compile(
+        "component Form {\n\
+           event onSubmit: (values: Values, valid: boolean) => void;\n\
+           event onReset: () => void;\n\
+           state errors: string[] = [];\n\
+           state touched: Map<string, boolean> = new Map();\n\
+           function validate(field: \"name\" | \"email\", value?: string = \"\") { }\n\
+         }\n",
+    );
+    assert!(result.success, "{:?}", error_messages(&result));
+    let (events, state, methods) = match result.ast {
+        Some(ASTNode::Program { body, .. }) => match body.into_iter().next() {
+            Some(ASTNode::Component { events, state, methods, .. }) => (events, state, methods),
+            other => panic!("expected a component, got {:?}", other),
+        },
+        other => panic!("expected a program, got {:?}", other),
+    };
+    let signatures: Vec<String> = events
+        .iter()
+        .filter_map(|event| match event {
+            ASTNode::EventDeclaration { signature, .. } => Some(signature.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(signatures, ["(values: Values, valid: boolean) => void", "() => void"]);
+    let types: Vec<String> = state
+        .iter()
+        .filter_map(|variable| match variable {
+            ASTNode::StateVariable { type_annotation: Some(type_annotation), .. } => Some(type_annotation.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(types, ["string[]", "Map<string, boolean>"]);
+    let parameters = match &methods[0] {
+        ASTNode::Method { parameters, .. } => parameters,
+        other => panic!("expected a method, got {:?}", other),
+    };
+    match &parameters[0] {
+        ASTNode::Parameter { type_annotation: Some(TypeExpr::Union(members)), .. } => assert_eq!(members.len(), 2),
+        other => panic!("expected a union parameter, got {:?}", other),
+    }
+}
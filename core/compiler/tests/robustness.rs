@@ -0,0 +1,218 @@
+// The compiler must finish on any input, without panicking, however broken
+// or deeply nested it is.
+//
+// Run with `cargo test --test robustness`. Each .ema source shipped with the
+// framework is compiled as is and then as a number of mutants: copies with
+// ranges deleted, duplicated or moved and brackets, quotes and tags spliced
+// in, picked by a fixed-seed xorshift generator so failures reproduce. Each
+// compile runs on its own thread under a watchdog that fails the test when
+// it doesn't return in time. The syntax tree of every compile, broken or
+// not, must print back to its source.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[allow(dead_code)]
+#[path = "../emadocs-compiler.rs"]
+mod compiler;
+
+use compiler::EmadocsCompiler;
+
+const MUTANTS_PER_SOURCE: usize = 32;
+const TIMEOUT: Duration = Duration::from_secs(10);
+// The main thread's default, which the compiler binary runs on
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+// Fragments spliced into mutants: openers and closers of every nesting
+// construct, and tokens that switch the lexer's mode
+const FRAGMENTS: &[&str] = &[
+    "{", "}", "(", ")", "[", "]", "<", ">", "</", "/>", "<div>", "</div>", "\"", "'", "`", "${", "[[", "]]",
+    "/*", "*/", "//", ",", ";", ":", "=", "=>", "?", ".", "...", "@", "#", "&", "|", "\n",
+    "component", "prop", "state", "render", "style", "event", "on", "import", "export", "from", "type",
+    "interface", "enum", "function", "async", "computed", "watch", "router", "route", "<route", "as", "*",
+];
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn corpus() -> Vec<(String, String)> {
+    let root = manifest_dir();
+    let mut paths = vec![
+        root.join("examples/test.ema"),
+        root.join("../../examples/todo-app.ema"),
+        root.join("../../index.ema"),
+    ];
+    let pattern = root.join("../../components/*/*.ema");
+    if let Ok(components) = glob::glob(&pattern.to_string_lossy()) {
+        paths.extend(components.filter_map(Result::ok));
+    }
+
+    paths
+        .iter()
+        .filter_map(|path: &PathBuf| {
+            let source = fs::read_to_string(path).ok()?;
+            Some((path.display().to_string(), source))
+        })
+        .collect()
+}
+
+/// xorshift64: small, fast and reproducible from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            0
+        } else {
+            (self.next() % bound as u64) as usize
+        }
+    }
+}
+
+/// Applies one to eight random edits to `source`, on char boundaries.
+fn mutate(source: &str, rng: &mut Rng) -> String {
+    let mut chars: Vec<char> = source.chars().collect();
+    for _ in 0..1 + rng.below(8) {
+        let at = rng.below(chars.len() + 1);
+        let len = 1 + rng.below(64);
+        let end = (at + len).min(chars.len());
+        match rng.below(5) {
+            0 => {
+                chars.drain(at..end);
+            }
+            1 => {
+                let copy: Vec<char> = chars[at..end].to_vec();
+                chars.splice(at..at, copy);
+            }
+            2 => {
+                let moved: Vec<char> = chars.drain(at..end).collect();
+                let to = rng.below(chars.len() + 1);
+                chars.splice(to..to, moved);
+            }
+            3 => chars.truncate(at),
+            _ => {
+                let fragment = FRAGMENTS[rng.below(FRAGMENTS.len())];
+                chars.splice(at..at, fragment.chars());
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+/// Compiles `source` on a thread of its own, failing if that panics,
+/// doesn't finish within `TIMEOUT` or its syntax tree doesn't print back
+/// to the source.
+fn compile_within_limits(name: &str, source: String) {
+    let (sender, receiver) = mpsc::channel();
+    let file = name.to_string();
+    let input = source.clone();
+    let worker = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let result = EmadocsCompiler::new(input, file).compile();
+            let _ = sender.send(result.syntax_tree.map(|tree| tree.text()));
+        })
+        .expect("failed to spawn the compiler thread");
+
+    match receiver.recv_timeout(TIMEOUT) {
+        Ok(text) => {
+            worker.join().expect("the compiler thread panicked");
+            assert_eq!(text.as_deref(), Some(source.as_str()), "{} doesn't round-trip", name);
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => panic!("compiling {} panicked", name),
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("compiling {} didn't finish in {:?}", name, TIMEOUT),
+    }
+}
+
+#[test]
+fn corpus_compiles_within_limits() {
+    let corpus = corpus();
+    assert!(!corpus.is_empty(), "no .ema sources found");
+    for (name, source) in corpus {
+        compile_within_limits(&name, source);
+    }
+}
+
+#[test]
+fn mutants_compile_within_limits() {
+    for (index, (name, source)) in corpus().into_iter().enumerate() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15 ^ (index as u64 + 1));
+        for mutant in 0..MUTANTS_PER_SOURCE {
+            let mutated = mutate(&source, &mut rng);
+            compile_within_limits(&format!("{} (mutant {})", name, mutant), mutated);
+        }
+    }
+}
+
+#[test]
+fn deep_nesting_is_rejected() {
+    let depth = 100_000;
+    let cases = [
+        ("parentheses", format!("const x = {}1{};", "(".repeat(depth), ")".repeat(depth))),
+        ("arrays", format!("const x = {}{};", "[".repeat(depth), "]".repeat(depth))),
+        ("objects", format!("const x = {}{};", "{a: ".repeat(depth), "}".repeat(depth))),
+        ("unary operators", format!("const x = {}1;", "!".repeat(depth))),
+        ("blocks", format!("function f() {{ {}{} }}", "{".repeat(depth), "}".repeat(depth))),
+        ("elements", format!("{}{}", "<div>".repeat(depth), "</div>".repeat(depth))),
+        ("types", format!("type T = {}string{};", "(".repeat(depth), ")".repeat(depth))),
+        ("style rules", format!("style Button {{ {}{} }}", "a { ".repeat(depth), "}".repeat(depth))),
+        ("templates", format!("const x = {}{};", "`${".repeat(depth), "}`".repeat(depth))),
+    ];
+
+    for (name, source) in cases {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let _ = sender.send(EmadocsCompiler::new(source, "deep.ema".to_string()).compile());
+            })
+            .expect("failed to spawn the compiler thread");
+
+        let result = receiver
+            .recv_timeout(TIMEOUT)
+            .unwrap_or_else(|error| panic!("deeply nested {}: {:?}", name, error));
+        assert!(!result.success, "deeply nested {} compiled", name);
+        assert!(
+            result.errors.iter().any(|error| error.code == compiler::codes::NESTING_TOO_DEEP),
+            "deeply nested {} gave {:?}",
+            name,
+            result.errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn stray_tokens_make_progress() {
+    // Inputs that once left a parser loop without consuming anything
+    let cases = [
+        "component Box<T, ;, U> { }",
+        "component Box<",
+        "import { a b c ; } from \"./m.ema\";",
+        "import { , , } from",
+        "export { a + b };",
+        "export {",
+        "function f(,,,) {}",
+        "function f(: = ) {}",
+        "event click on (((",
+        "route \"/\" ->",
+        "<router><route path=></router>",
+        "type T = { a: ; b }",
+        "enum E { = }",
+        "style { @media { } ",
+    ];
+
+    for source in cases {
+        compile_within_limits(source, source.to_string());
+    }
+}
@@ -0,0 +1,59 @@
+// The .ema sources shipped with the framework compile without errors or
+// warnings.
+//
+// Run with `cargo test --test shipped`.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[allow(dead_code)]
+#[path = "../emadocs-compiler.rs"]
+mod compiler;
+
+use compiler::EmadocsCompiler;
+
+fn assert_compiles_cleanly(relative: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(relative);
+    let source = fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+    let result = EmadocsCompiler::new(source, relative.to_string()).compile();
+    let diagnostics: Vec<String> = result.errors.iter().chain(&result.warnings).map(ToString::to_string).collect();
+    assert!(result.success && diagnostics.is_empty(), "{}", diagnostics.join("\n"));
+    assert_valid_js(relative, &result.js);
+}
+
+/// Fails when node rejects the syntax of `js`; skipped without node.
+fn assert_valid_js(relative: &str, js: &str) {
+    let name: String = relative.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let path = std::env::temp_dir().join(format!("emadocs-{}-{}.js", std::process::id(), name));
+    fs::write(&path, js).expect("write the script");
+    let output = Command::new("node").arg("--check").arg(&path).output();
+    fs::remove_file(&path).ok();
+    match output {
+        Ok(output) => assert!(output.status.success(), "{}: {}", relative, String::from_utf8_lossy(&output.stderr)),
+        Err(_) => eprintln!("node not found, skipping the syntax check of {}", relative),
+    }
+}
+
+#[test]
+fn index_compiles_cleanly() {
+    assert_compiles_cleanly("../../index.ema");
+}
+
+#[test]
+fn examples_compile_cleanly() {
+    assert_compiles_cleanly("examples/test.ema");
+    assert_compiles_cleanly("../../examples/todo-app.ema");
+}
+
+#[test]
+fn components_compile_cleanly() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let pattern = root.join("../../components/*/*.ema");
+    let components: Vec<_> = glob::glob(&pattern.to_string_lossy()).expect("pattern").filter_map(Result::ok).collect();
+    assert!(!components.is_empty(), "no components under {}", pattern.display());
+    for path in components {
+        let relative = path.strip_prefix(root).expect("under the manifest dir");
+        assert_compiles_cleanly(&relative.to_string_lossy());
+    }
+}